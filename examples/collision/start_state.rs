@@ -7,7 +7,7 @@ use barn::input::Keycode;
 use barn::math::bounding_box_2d::BoundingBox2D;
 use barn::graphics::barn_gfx::BarnGFX;
 use barn::graphics::color::Color;
-use barn::game::state::State;
+use barn::game::state::{State, Transition};
 use barn::game::barn_context::BarnContext;
 use barn::game::context::Context;
 use barn::math::vector2::Vector2;
@@ -20,7 +20,7 @@ pub struct StartState {
 }
 
 impl State<BarnContext> for StartState {
-    fn update(&mut self, context: &mut BarnContext, dt: f32) -> Option<Box<dyn State<BarnContext>>> {
+    fn update(&mut self, context: &mut BarnContext, dt: f32) -> Transition<BarnContext> {
         println!("[DEBUG] dt: {}", dt);
         let mut vel = Vector2::ZERO;
         let delta = dt * PLAYER_SPEED;
@@ -39,10 +39,10 @@ impl State<BarnContext> for StartState {
         let tiles = &mut [self.tile1.bb.clone(), self.tile2.bb.clone()].to_vec();
         self.player.bb.resolve_bb_intersect(tiles, &mut vel);
         println!("[DEBUG] Player pos after: x={}, y={}", self.player.bb.origin.x, self.player.bb.origin.y);
-        None
+        Transition::None
     }
 
-    fn render(&mut self, context: &mut BarnContext, renderer: &mut barn::graphics::wgpu_renderer::WgpuRenderer) {
+    fn render(&mut self, context: &mut BarnContext, renderer: &mut dyn barn::graphics::renderer::Renderer) {
         let bb = &self.player.bb;
         renderer.draw_rect(bb.origin.x as i32, bb.origin.y as i32, bb.width, bb.height, [0.0, 0.0, 1.0, 1.0]);
         let t1 = &self.tile1.bb;
@@ -1,6 +1,6 @@
-use barn::game::state::State;
+use barn::game::state::{State, Transition};
 use barn::game::barn_context::BarnContext;
-use barn::graphics::wgpu_renderer::WgpuRenderer;
+use barn::graphics::renderer::Renderer;
 
 pub struct StartState {
     pub text: String,
@@ -11,11 +11,11 @@ pub struct StartState {
 }
 
 impl State<BarnContext> for StartState {
-    fn update(&mut self, _context: &mut BarnContext, _dt: f32) -> Option<Box<dyn State<BarnContext>>> { 
-        None 
+    fn update(&mut self, _context: &mut BarnContext, _dt: f32) -> Transition<BarnContext> { 
+        Transition::None
     }
     
-    fn render(&mut self, _context: &mut BarnContext, renderer: &mut WgpuRenderer) {
+    fn render(&mut self, _context: &mut BarnContext, renderer: &mut dyn Renderer) {
         // Set background to cornflower blue
         renderer.set_clear_color(0.392, 0.584, 0.929, 1.0);
         
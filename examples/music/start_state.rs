@@ -1,6 +1,6 @@
-use barn::game::state::State;
+use barn::game::state::{State, Transition};
 use barn::game::barn_context::BarnContext;
-use barn::graphics::wgpu_renderer::WgpuRenderer;
+use barn::graphics::renderer::Renderer;
 use barn::fonts::font_details::FontDetails;
 
 pub struct StartState {
@@ -13,9 +13,9 @@ pub struct StartState {
 }
 
 impl State<BarnContext> for StartState {
-    fn update(&mut self, _context: &mut BarnContext, _dt: f32) -> Option<Box<dyn State<BarnContext>>> { None }
+    fn update(&mut self, _context: &mut BarnContext, _dt: f32) -> Transition<BarnContext> { Transition::None }
 
-    fn render(&mut self, _context: &mut BarnContext, renderer: &mut WgpuRenderer) {
+    fn render(&mut self, _context: &mut BarnContext, renderer: &mut dyn Renderer) {
         // Set background color
         renderer.set_clear_color(0.2, 0.2, 0.5, 1.0);
         // Draw text
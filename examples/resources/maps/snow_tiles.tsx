@@ -0,0 +1,25 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" tiledversion="1.10.2" name="snow_tiles" tilewidth="32" tileheight="32" tilecount="16" columns="16">
+ <properties>
+  <property name="season" value="winter"/>
+ </properties>
+ <image source="../images/snow_ground_1.png" width="512" height="32"/>
+ <tile id="0" type="ground">
+  <properties>
+   <property name="solid" type="bool" value="true"/>
+   <property name="friction" type="float" value="0.2"/>
+  </properties>
+ </tile>
+ <tile id="1" type="ground">
+  <properties>
+   <property name="solid" type="bool" value="true"/>
+  </properties>
+  <objectgroup draworder="index">
+   <object id="1" x="0" y="8" width="32" height="24"/>
+  </objectgroup>
+  <animation>
+   <frame tileid="1" duration="250"/>
+   <frame tileid="2" duration="250"/>
+  </animation>
+ </tile>
+</tileset>
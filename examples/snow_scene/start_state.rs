@@ -1,8 +1,9 @@
-use barn::graphics::wgpu_renderer::WgpuRenderer;
+use barn::graphics::renderer::Renderer;
 use barn::graphics::color::Color;
 use barn::graphics::wgpu_sprite::WgpuSprite;
+use barn::graphics::parallax::{ParallaxBackground, ParallaxLayer};
 use barn::graphics::Rect;
-use barn::game::state::State;
+use barn::game::state::{State, Transition};
 use barn::game::barn_context::BarnContext;
 use barn::math::vector2::Vector2;
 use barn::graphics::texture::Texture;
@@ -14,8 +15,7 @@ pub struct StartState {
     pub snow_layer1: Option<SnowFallLayer>,
     pub snow_layer2: Option<SnowFallLayer>,
     pub snow_layer3: Option<SnowFallLayer>,
-    pub cloud_offset1: f32,
-    pub cloud_offset2: f32,
+    pub clouds: ParallaxBackground,
     pub window_width: f32,
     pub window_height: f32,
     pub moon_sprite_data: Option<(Rect, Rect, String)>,
@@ -25,7 +25,7 @@ pub struct StartState {
 }
 
 impl State<BarnContext> for StartState {
-    fn update(&mut self, context: &mut BarnContext, dt: f32) -> Option<Box<dyn State<BarnContext>>> { 
+    fn update(&mut self, context: &mut BarnContext, dt: f32) -> Transition<BarnContext> { 
         let snow_speed = 30.0 * dt;
         if let Some(ref mut layer1) = self.snow_layer1 {
             layer1.update(snow_speed);
@@ -37,19 +37,11 @@ impl State<BarnContext> for StartState {
             layer3.update(snow_speed);
         }
 
-        let speed = 10.0 * dt;
-        self.cloud_offset1 += speed;
-        self.cloud_offset2 += speed;
-        if self.cloud_offset1 > self.window_width {
-            self.cloud_offset1 -= self.window_width + 1.0;
-        }
-        if self.cloud_offset2 > self.window_width {
-            self.cloud_offset2 -= self.window_width + 1.0;
-        }
-        None
+        self.clouds.update(dt);
+        Transition::None
     }
 
-    fn render(&mut self, context: &mut BarnContext, renderer: &mut WgpuRenderer) {
+    fn render(&mut self, context: &mut BarnContext, renderer: &mut dyn Renderer) {
         println!("[DEBUG] StartState::render called");
         println!("[DEBUG] moon_sprite_data: {:?}", self.moon_sprite_data);
         println!("[DEBUG] gradient_sprite_data: {:?}", self.gradient_sprite_data);
@@ -62,8 +54,8 @@ impl State<BarnContext> for StartState {
         renderer.set_clear_color(0.0, 0.0, 0.0, 1.0);
         
         // Update window dimensions if they changed
-        let current_width = renderer.size.width as f32;
-        let current_height = renderer.size.height as f32;
+        let current_width = renderer.size().0 as f32;
+        let current_height = renderer.size().1 as f32;
         
         if current_width != self.window_width || current_height != self.window_height {
             self.window_width = current_width;
@@ -76,7 +68,7 @@ impl State<BarnContext> for StartState {
         if self.snow_ground_sprite_data.is_none() {
             println!("[DEBUG] Loading ground texture...");
             if let Ok(()) = renderer.load_texture("examples/resources/images/snow_ground_1.png") {
-                if let Some(tex) = renderer.textures.get("examples/resources/images/snow_ground_1.png") {
+                if let Some(tex) = renderer.texture_size("examples/resources/images/snow_ground_1.png") {
                     self.snow_ground_sprite_data = Some(((0.0, 0.0, 512.0, 32.0), (0.0, current_height - 64.0, current_width, 64.0), "examples/resources/images/snow_ground_1.png".to_string()));
                     println!("[DEBUG] Ground sprite data set");
                 }
@@ -89,7 +81,7 @@ impl State<BarnContext> for StartState {
         if self.cloud_sprite_data.is_none() {
             println!("[DEBUG] Loading cloud texture...");
             if let Ok(()) = renderer.load_texture("examples/resources/images/cloud2.png") {
-                if let Some(tex) = renderer.textures.get("examples/resources/images/cloud2.png") {
+                if let Some(tex) = renderer.texture_size("examples/resources/images/cloud2.png") {
                     self.cloud_sprite_data = Some(((0.0, 0.0, 256.0, 64.0), (0.0, 0.0, 256.0, 64.0), "examples/resources/images/cloud2.png".to_string()));
                     println!("[DEBUG] Cloud sprite data set");
                 }
@@ -102,7 +94,7 @@ impl State<BarnContext> for StartState {
         if self.moon_sprite_data.is_none() {
             println!("[DEBUG] Loading moon texture...");
             if let Ok(()) = renderer.load_texture("examples/resources/images/moon.png") {
-                if let Some(tex) = renderer.textures.get("examples/resources/images/moon.png") {
+                if let Some(tex) = renderer.texture_size("examples/resources/images/moon.png") {
                     self.moon_sprite_data = Some(((0.0, 0.0, 128.0, 128.0), (1000.0, 48.0, 128.0, 128.0), "examples/resources/images/moon.png".to_string()));
                     println!("[DEBUG] Moon sprite data set");
                 }
@@ -115,7 +107,7 @@ impl State<BarnContext> for StartState {
         if self.gradient_sprite_data.is_none() {
             println!("[DEBUG] Loading gradient texture...");
             if let Ok(()) = renderer.load_texture("examples/resources/images/evening_gradient.png") {
-                if let Some(tex) = renderer.textures.get("examples/resources/images/evening_gradient.png") {
+                if let Some(tex) = renderer.texture_size("examples/resources/images/evening_gradient.png") {
                     self.gradient_sprite_data = Some(((0.0, 0.0, 1.0, 512.0), (0.0, 0.0, 512.0, 512.0), "examples/resources/images/evening_gradient.png".to_string()));
                     println!("[DEBUG] Gradient sprite data set");
                 }
@@ -127,11 +119,11 @@ impl State<BarnContext> for StartState {
         // Render background gradient first
         if let Some((src, _dst, texture_path)) = &self.gradient_sprite_data {
             println!("[DEBUG] Drawing gradient: src={:?}, dst=(0,0,window,window), texture_path={}", src, texture_path);
-            let tex_size = renderer.textures.get(texture_path).map(|tex| (tex.size.width, tex.size.height));
+            let tex_size = renderer.texture_size(texture_path);
             if let Some(tex_size) = tex_size {
                 println!("[DEBUG] Texture size for {}: {:?}", texture_path, tex_size);
                 let dst = (0.0, 0.0, current_width, current_height);
-                let sprite = WgpuSprite::new(*src, dst, [1.0, 1.0, 1.0, 1.0], &renderer.device, texture_path.clone(), tex_size, renderer.size.width as f32, renderer.size.height as f32);
+                let sprite = WgpuSprite::deferred(*src, dst, [1.0, 1.0, 1.0, 1.0], texture_path, tex_size);
                 println!("[DEBUG] About to draw sprite: texture_path={:?}, src={:?}, dst={:?}, color={:?}", sprite.texture_path, sprite.src, sprite.dst, sprite.color);
                 renderer.draw_sprite(sprite);
             }
@@ -140,10 +132,10 @@ impl State<BarnContext> for StartState {
         // Render moon
         if let Some((src, dst, texture_path)) = &self.moon_sprite_data {
             println!("[DEBUG] Drawing moon: src={:?}, dst={:?}, texture_path={}", src, dst, texture_path);
-            let tex_size = renderer.textures.get(texture_path).map(|tex| (tex.size.width, tex.size.height));
+            let tex_size = renderer.texture_size(texture_path);
             if let Some(tex_size) = tex_size {
                 println!("[DEBUG] Texture size for {}: {:?}", texture_path, tex_size);
-                let sprite = WgpuSprite::new(*src, *dst, [1.0, 1.0, 1.0, 1.0], &renderer.device, texture_path.clone(), tex_size, renderer.size.width as f32, renderer.size.height as f32);
+                let sprite = WgpuSprite::deferred(*src, *dst, [1.0, 1.0, 1.0, 1.0], texture_path, tex_size);
                 println!("[DEBUG] About to draw sprite: texture_path={:?}, src={:?}, dst={:?}, color={:?}", sprite.texture_path, sprite.src, sprite.dst, sprite.color);
                 renderer.draw_sprite(sprite);
             }
@@ -152,38 +144,19 @@ impl State<BarnContext> for StartState {
         // Render ground
         if let Some((src, dst, texture_path)) = &self.snow_ground_sprite_data {
             println!("[DEBUG] Drawing ground: src={:?}, dst={:?}, texture_path={}", src, dst, texture_path);
-            let tex_size = renderer.textures.get(texture_path).map(|tex| (tex.size.width, tex.size.height));
+            let tex_size = renderer.texture_size(texture_path);
             if let Some(tex_size) = tex_size {
                 println!("[DEBUG] Texture size for {}: {:?}", texture_path, tex_size);
-                let sprite = WgpuSprite::new(*src, *dst, [1.0, 1.0, 1.0, 1.0], &renderer.device, texture_path.clone(), tex_size, renderer.size.width as f32, renderer.size.height as f32);
+                let sprite = WgpuSprite::deferred(*src, *dst, [1.0, 1.0, 1.0, 1.0], texture_path, tex_size);
                 println!("[DEBUG] About to draw sprite: texture_path={:?}, src={:?}, dst={:?}, color={:?}", sprite.texture_path, sprite.src, sprite.dst, sprite.color);
                 renderer.draw_sprite(sprite);
             }
         }
         
         // Render clouds
-        if let Some((src, _dst, texture_path)) = &self.cloud_sprite_data {
-            println!("[DEBUG] Drawing clouds: src={:?}, dst=full width, texture_path={}", src, texture_path);
-            let tex_size = renderer.textures.get(texture_path).map(|tex| (tex.size.width, tex.size.height));
-            if let Some(tex_size) = tex_size {
-                println!("[DEBUG] Texture size for {}: {:?}", texture_path, tex_size);
-                let dst_y = 30.0;
-                let dst_h = 128.0;
-                let dst_w = current_width;
-                let cloud_color = [1.0, 1.0, 1.0, 0.25];
-
-                // Cloud 1
-                let dst1 = (self.cloud_offset1, dst_y, dst_w, dst_h);
-                let sprite1 = WgpuSprite::new(*src, dst1, cloud_color, &renderer.device, texture_path.clone(), tex_size, renderer.size.width as f32, renderer.size.height as f32);
-                println!("[DEBUG] About to draw sprite: texture_path={:?}, src={:?}, dst={:?}, color={:?}", sprite1.texture_path, sprite1.src, sprite1.dst, sprite1.color);
-                renderer.draw_sprite(sprite1);
-
-                // Cloud 2
-                let dst2 = (self.cloud_offset2, 140.0, dst_w, dst_h);
-                let sprite2 = WgpuSprite::new(*src, dst2, cloud_color, &renderer.device, texture_path.clone(), tex_size, renderer.size.width as f32, renderer.size.height as f32);
-                println!("[DEBUG] About to draw sprite: texture_path={:?}, src={:?}, dst={:?}, color={:?}", sprite2.texture_path, sprite2.src, sprite2.dst, sprite2.color);
-                renderer.draw_sprite(sprite2);
-            }
+        if self.cloud_sprite_data.is_some() {
+            println!("[DEBUG] Drawing clouds");
+            self.clouds.draw(renderer, [current_width / 2.0, current_height / 2.0], 1.0);
         }
         
         // Draw snow particles as rectangles
@@ -237,8 +210,20 @@ impl StartState {
             snow_layer1: None,
             snow_layer2: None,
             snow_layer3: None,
-            cloud_offset1: -512.0,
-            cloud_offset2: 512.0,
+            // Two drifting bands of the same cloud image, stretched to the window width
+            clouds: ParallaxBackground::new()
+                .with_layer(ParallaxLayer::new("examples/resources/images/cloud2.png", (0.0, 0.0, 256.0, 64.0))
+                    .with_size(512.0, 128.0)
+                    .with_offset(0.0, 30.0)
+                    .with_velocity(10.0, 0.0)
+                    .with_repeat(true, false)
+                    .with_color([1.0, 1.0, 1.0, 0.25]))
+                .with_layer(ParallaxLayer::new("examples/resources/images/cloud2.png", (0.0, 0.0, 256.0, 64.0))
+                    .with_size(512.0, 128.0)
+                    .with_offset(256.0, 140.0)
+                    .with_velocity(10.0, 0.0)
+                    .with_repeat(true, false)
+                    .with_color([1.0, 1.0, 1.0, 0.25])),
             window_width: 0.0,
             window_height: 0.0,
             moon_sprite_data: None,
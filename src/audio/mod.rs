@@ -5,8 +5,14 @@ use std::io::BufReader;
 
 pub struct AudioManager {
     sinks: HashMap<String, Sink>,
-    output_stream: OutputStream,
-    output_handle: rodio::OutputStreamHandle,
+    /// The audio device, or `None` for a silent manager.
+    output: Option<(OutputStream, rodio::OutputStreamHandle)>,
+    /// Sounds stopped by `pause_all`, to be restarted by `resume_all`.
+    paused_by_manager: Vec<String>,
+    /// Playback speeds set with `set_speed`, before the global speed is applied.
+    speeds: HashMap<String, f32>,
+    /// Multiplies every sound's speed, e.g. to follow the game's time scale.
+    global_speed: f32,
 }
 
 impl AudioManager {
@@ -15,10 +21,24 @@ impl AudioManager {
         
         Ok(AudioManager {
             sinks: HashMap::new(),
-            output_stream,
-            output_handle,
+            output: Some((output_stream, output_handle)),
+            paused_by_manager: Vec::new(),
+            speeds: HashMap::new(),
+            global_speed: 1.0,
         })
     }
+
+    /// Creates a manager that decodes and tracks sounds without an audio device,
+    /// for headless runs and machines without sound.
+    pub fn silent() -> Self {
+        AudioManager {
+            sinks: HashMap::new(),
+            output: None,
+            paused_by_manager: Vec::new(),
+            speeds: HashMap::new(),
+            global_speed: 1.0,
+        }
+    }
     
     pub fn load_audio(&mut self, name: &str, path: &str, repeat: bool) -> Result<(), Box<dyn std::error::Error>> {
         let file = File::open(path)?;
@@ -29,9 +49,13 @@ impl AudioManager {
         } else {
             Box::new(source)
         };
-        let sink = Sink::try_new(&self.output_handle)?;
+        let sink = match &self.output {
+            Some((_, output_handle)) => Sink::try_new(output_handle)?,
+            None => Sink::new_idle().0,
+        };
         sink.append(source);
         sink.pause();
+        sink.set_speed(self.speeds.get(name).unwrap_or(&1.0) * self.global_speed);
         self.sinks.insert(name.to_string(), sink);
         Ok(())
     }
@@ -77,6 +101,44 @@ impl AudioManager {
             sink.set_volume(volume);
         }
     }
+
+    /// Sets a sound's playback speed. Speed also changes pitch; 1.0 is normal.
+    pub fn set_speed(&mut self, name: &str, speed: f32) {
+        self.speeds.insert(name.to_string(), speed);
+        if let Some(sink) = self.sinks.get(name) {
+            sink.set_speed(speed * self.global_speed);
+        }
+    }
+
+    /// Sets the speed every sound's own speed is multiplied by.
+    pub fn set_global_speed(&mut self, speed: f32) {
+        if speed == self.global_speed {
+            return;
+        }
+        self.global_speed = speed;
+        for (name, sink) in self.sinks.iter() {
+            sink.set_speed(self.speeds.get(name).unwrap_or(&1.0) * speed);
+        }
+    }
+
+    /// Pauses every playing sound, remembering which ones `resume_all` should restart.
+    pub fn pause_all(&mut self) {
+        for (name, sink) in self.sinks.iter() {
+            if !sink.is_paused() && !sink.empty() {
+                sink.pause();
+                self.paused_by_manager.push(name.clone());
+            }
+        }
+    }
+
+    /// Restarts the sounds paused by `pause_all`. Sounds paused individually stay paused.
+    pub fn resume_all(&mut self) {
+        for name in self.paused_by_manager.drain(..) {
+            if let Some(sink) = self.sinks.get(&name) {
+                sink.play();
+            }
+        }
+    }
 }
 
 // Legacy type aliases for compatibility
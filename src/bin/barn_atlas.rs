@@ -0,0 +1,87 @@
+//! Packs images into texture atlas pages plus a JSON manifest that can be loaded
+//! with `TextureAtlas::from_manifest`.
+//!
+//! Usage: barn_atlas [--max-size N] [--padding N] [--extrude N] [--pot] <name> <output_dir> <inputs>...
+//!
+//! Inputs may be image files or directories, which are searched recursively. Regions
+//! are named after the file stem, or the path relative to the input directory for
+//! files found in directories, e.g. `player/idle_0`.
+
+use std::path::{Path, PathBuf};
+
+use barn::graphics::atlas::TextureAtlasBuilder;
+
+const USAGE: &str = "usage: barn_atlas [--max-size N] [--padding N] [--extrude N] [--pot] <name> <output_dir> <inputs>...";
+
+fn main() {
+    if let Err(e) = run(std::env::args().skip(1).collect()) {
+        eprintln!("barn_atlas: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut builder = TextureAtlasBuilder::new();
+    let mut positional = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| -> Result<u32, Box<dyn std::error::Error>> {
+            let value = args.next().ok_or_else(|| format!("{} needs a value", flag))?;
+            Ok(value.parse().map_err(|_| format!("invalid value '{}' for {}", value, flag))?)
+        };
+        match arg.as_str() {
+            "--max-size" => builder = builder.with_max_size(value("--max-size")?),
+            "--padding" => builder = builder.with_padding(value("--padding")?),
+            "--extrude" => builder = builder.with_extrude(value("--extrude")?),
+            "--pot" => builder = builder.with_power_of_two(true),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}\n{}", flag, USAGE).into()),
+            _ => positional.push(arg),
+        }
+    }
+    if positional.len() < 3 {
+        return Err(USAGE.into());
+    }
+    let name = &positional[0];
+    let output = &positional[1];
+
+    let mut count = 0;
+    for input in positional[2..].iter() {
+        let input = Path::new(input);
+        if input.is_dir() {
+            let mut files = Vec::new();
+            collect_images(input, &mut files)?;
+            files.sort();
+            for file in files {
+                let relative = file.strip_prefix(input)?.with_extension("");
+                let region = relative.to_string_lossy().replace('\\', "/");
+                builder.add_file(&region, &file.to_string_lossy())?;
+                count += 1;
+            }
+        } else {
+            let region = input.file_stem().ok_or("input has no file name")?.to_string_lossy().into_owned();
+            builder.add_file(&region, &input.to_string_lossy())?;
+            count += 1;
+        }
+    }
+
+    let atlas = builder.build(name)?;
+    let manifest = atlas.save(output)?;
+    println!("Packed {} images into {} page(s): {}", count, atlas.pages.len(), manifest);
+    Ok(())
+}
+
+fn collect_images(directory: &Path, files: &mut Vec<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_images(&path, files)?;
+        } else if image::ImageFormat::from_path(&path).is_ok() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -1,14 +1,28 @@
 use crate::audio::AudioManager;
 use crate::input::KeyboardHandler;
-use crate::game::state::State;
+use crate::game::state::{State, Transition};
 use crate::game::context::Context;
-use crate::graphics::wgpu_renderer::WgpuRenderer;
+use crate::game::event_bus::EventBus;
+use crate::game::scheduler::Scheduler;
+use crate::game::time::TimeController;
+use crate::game::window_command::{WindowCommand, WindowCommands};
+use crate::graphics::renderer::Renderer;
 use std::rc::Rc;
 use std::cell::RefCell;
 
 pub struct BarnContext {
     pub audio_manager: AudioManager,
     pub keyboard: Rc<RefCell<KeyboardHandler>>,
+    /// Window changes applied by the game after the current update, e.g.
+    /// `context.window.set_title("Paused")` or `context.window.quit()`.
+    pub window: WindowCommands,
+    /// Events shared between states and systems. The game also publishes
+    /// `GameEvent`s here.
+    pub events: EventBus,
+    /// Timers and sequences, e.g. `context.scheduler.after(2.0, || ...)`.
+    pub scheduler: Scheduler,
+    /// Time scale, hit-stop and gameplay pause, e.g. `context.time.set_time_scale(0.25)`.
+    pub time: TimeController,
 }
 
 impl Context for BarnContext {
@@ -16,20 +30,53 @@ impl Context for BarnContext {
         self.keyboard.borrow_mut()
     }
 
-    fn update(&mut self, state: &mut Box<dyn State<Self>>, dt: f32) -> Option<Box<dyn State<Self>>> {
+    fn update(&mut self, state: &mut Box<dyn State<Self>>, dt: f32) -> Transition<Self> {
         state.update(self, dt)
     }
 
-    fn render_state(&mut self, state: &mut Box<dyn State<Self>>, renderer: &mut WgpuRenderer) {
+    fn render_state(&mut self, state: &mut Box<dyn State<Self>>, renderer: &mut dyn Renderer) {
         state.render(self, renderer);
     }
+
+    fn set_audio_paused(&mut self, paused: bool) {
+        if paused {
+            self.audio_manager.pause_all();
+        } else {
+            self.audio_manager.resume_all();
+        }
+    }
+
+    fn events(&mut self) -> Option<&mut EventBus> {
+        Some(&mut self.events)
+    }
+
+    fn scheduler(&mut self) -> Option<&mut Scheduler> {
+        Some(&mut self.scheduler)
+    }
+
+    fn time(&mut self) -> Option<&mut TimeController> {
+        Some(&mut self.time)
+    }
+
+    fn set_audio_speed(&mut self, speed: f32) {
+        self.audio_manager.set_global_speed(speed);
+    }
+
+    fn take_window_commands(&mut self) -> Vec<WindowCommand> {
+        self.window.take()
+    }
 }
 
 impl BarnContext {
     pub fn new(keyboard: Rc<RefCell<KeyboardHandler>>) -> Self {
         BarnContext {
-            audio_manager: AudioManager::new().unwrap(),
+            // Keep running without sound when no audio device is available
+            audio_manager: AudioManager::new().unwrap_or_else(|_| AudioManager::silent()),
             keyboard,
+            window: WindowCommands::new(),
+            events: EventBus::new(),
+            scheduler: Scheduler::new(),
+            time: TimeController::new(),
         }
     }
 
@@ -61,7 +108,11 @@ impl BarnContext {
         self.audio_manager.set_volume(name, volume);
     }
 
-    pub fn load_texture(&mut self, name: &str, path: &str, renderer: &mut WgpuRenderer) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn set_speed(&mut self, name: &str, speed: f32) {
+        self.audio_manager.set_speed(name, speed);
+    }
+
+    pub fn load_texture(&mut self, name: &str, path: &str, renderer: &mut dyn Renderer) -> Result<(), Box<dyn std::error::Error>> {
         renderer.load_texture(path)
     }
 }
@@ -1,16 +1,58 @@
 use crate::input::KeyboardHandler;
-use crate::graphics::wgpu_renderer::WgpuRenderer;
-use super::state::State;
+use crate::graphics::renderer::Renderer;
+use super::event_bus::EventBus;
+use super::scheduler::Scheduler;
+use super::state::{State, Transition};
+use super::time::TimeController;
+use super::window_command::WindowCommand;
 
 pub trait Context {
     /// Get a mutable reference to the keyboard handler
     fn get_input_handler(&mut self) -> std::cell::RefMut<KeyboardHandler>;
     
     /// Update the context with the given state and delta time
-    fn update(&mut self, state: &mut Box<dyn State<Self>>, dt: f32) -> Option<Box<dyn State<Self>>> 
+    fn update(&mut self, state: &mut Box<dyn State<Self>>, dt: f32) -> Transition<Self> 
     where Self: std::marker::Sized;
     
+    /// Run one fixed simulation step of the given state
+    fn fixed_update(&mut self, state: &mut Box<dyn State<Self>>, dt: f32) -> Transition<Self>
+    where Self: std::marker::Sized {
+        state.fixed_update(self, dt)
+    }
+    
     /// Render the state using the provided renderer
-    fn render_state(&mut self, state: &mut Box<dyn State<Self>>, renderer: &mut WgpuRenderer) 
+    fn render_state(&mut self, state: &mut Box<dyn State<Self>>, renderer: &mut dyn Renderer) 
     where Self: std::marker::Sized;
+    
+    /// Render the state with the interpolation alpha of a fixed timestep
+    fn render_state_interpolated(&mut self, state: &mut Box<dyn State<Self>>, renderer: &mut dyn Renderer, alpha: f32)
+    where Self: std::marker::Sized {
+        state.render_interpolated(self, renderer, alpha);
+    }
+
+    /// Pause or resume audio while the game is paused by losing focus
+    fn set_audio_paused(&mut self, _paused: bool) {}
+
+    /// The event bus the game publishes window and input events to, if any
+    fn events(&mut self) -> Option<&mut EventBus> {
+        None
+    }
+
+    /// The scheduler the game advances before each update, if any
+    fn scheduler(&mut self) -> Option<&mut Scheduler> {
+        None
+    }
+
+    /// The time controller that scales the `dt` passed to states, if any
+    fn time(&mut self) -> Option<&mut TimeController> {
+        None
+    }
+
+    /// Set the playback speed of all audio to follow the time scale
+    fn set_audio_speed(&mut self, _speed: f32) {}
+
+    /// Take the window commands queued by states since the last frame
+    fn take_window_commands(&mut self) -> Vec<WindowCommand> {
+        Vec::new()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,335 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use winit::keyboard::Key;
+
+/// Window and input events published to a context's `EventBus` by the game.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    /// A key went down, named or character. Held-key repeats are not published.
+    KeyPressed(Key),
+    KeyReleased(Key),
+    /// The window's new inner size in physical pixels.
+    Resized(u32, u32),
+    FocusChanged(bool),
+    /// The window was minimized or hidden (`false`), or shown again (`true`).
+    VisibilityChanged(bool),
+    Suspended,
+    Resumed,
+    ScaleFactorChanged(f64),
+}
+
+/// Identifies a listener added with `EventBus::subscribe`, for `unsubscribe`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SubscriptionId {
+    type_id: TypeId,
+    id: u64,
+}
+
+/// A cursor into one event type's queue. Each consumer keeps its own reader, so
+/// every consumer sees every event once.
+#[derive(Debug)]
+pub struct EventReader<E> {
+    next: u64,
+    event_type: PhantomData<fn() -> E>,
+}
+
+/// Typed publish/subscribe events shared by states and systems.
+///
+/// Any `'static` type can be an event. `emit` calls the type's subscribers
+/// immediately and queues the event for readers. Queued events stay readable
+/// until the end of the frame after the one they were emitted in, so states
+/// updated before the emitter still see them next frame.
+///
+/// # Examples
+///
+/// ```
+/// use barn::game::event_bus::EventBus;
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// struct CoinCollected { value: u32 }
+///
+/// let mut events = EventBus::new();
+/// let mut hud = events.reader::<CoinCollected>();
+/// let mut sound = events.reader::<CoinCollected>();
+/// let total = Rc::new(Cell::new(0));
+/// let counter = Rc::clone(&total);
+/// events.subscribe(move |coin: &CoinCollected| counter.set(counter.get() + coin.value));
+///
+/// events.emit(CoinCollected { value: 5 });
+/// assert_eq!(total.get(), 5);
+/// assert_eq!(events.read(&mut hud).map(|coin| coin.value).collect::<Vec<_>>(), vec![5]);
+/// assert_eq!(events.read(&mut hud).count(), 0);
+///
+/// // Still readable during the next frame, then dropped.
+/// events.update();
+/// assert_eq!(events.read(&mut sound).count(), 1);
+/// events.update();
+/// assert_eq!(events.iter::<CoinCollected>().count(), 0);
+/// ```
+#[derive(Default)]
+pub struct EventBus {
+    channels: HashMap<TypeId, Box<dyn AnyChannel>>,
+    next_subscription: u64,
+}
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        EventBus::default()
+    }
+
+    /// Calls the subscribers of `E` and queues the event for readers.
+    pub fn emit<E: 'static>(&mut self, event: E) {
+        let channel = self.channel_mut::<E>();
+        for (_, listener) in channel.listeners.iter_mut() {
+            listener(&event);
+        }
+        channel.current.push(event);
+    }
+
+    /// Adds a listener called immediately for every `E` emitted from now on.
+    pub fn subscribe<E: 'static>(&mut self, listener: impl FnMut(&E) + 'static) -> SubscriptionId {
+        let id = self.next_subscription;
+        self.next_subscription += 1;
+        self.channel_mut::<E>().listeners.push((id, Box::new(listener)));
+        SubscriptionId { type_id: TypeId::of::<E>(), id }
+    }
+
+    /// Removes a listener. Returns false if it was already removed.
+    pub fn unsubscribe(&mut self, subscription: SubscriptionId) -> bool {
+        match self.channels.get_mut(&subscription.type_id) {
+            Some(channel) => channel.unsubscribe(subscription.id),
+            None => false,
+        }
+    }
+
+    /// Creates a reader that receives the `E` events emitted after this call.
+    pub fn reader<E: 'static>(&self) -> EventReader<E> {
+        let next = self.channel::<E>().map_or(0, |channel| channel.end());
+        EventReader { next, event_type: PhantomData }
+    }
+
+    /// Returns the queued events `reader` has not seen yet and marks them as read.
+    pub fn read<'a, E: 'static>(&'a self, reader: &mut EventReader<E>) -> impl Iterator<Item = &'a E> {
+        let channel = self.channel::<E>();
+        // Events dropped before the reader got to them are skipped
+        let skip = channel.map_or(0, |channel| reader.next.saturating_sub(channel.start) as usize);
+        if let Some(channel) = channel {
+            reader.next = channel.end();
+        }
+        self.iter::<E>().skip(skip)
+    }
+
+    /// Returns every queued `E`, from last frame and this one, without a reader.
+    pub fn iter<E: 'static>(&self) -> impl Iterator<Item = &E> {
+        match self.channel::<E>() {
+            Some(channel) => channel.previous.iter().chain(channel.current.iter()),
+            None => [].iter().chain([].iter()),
+        }
+    }
+
+    /// Starts a new frame, dropping the events emitted before the previous one.
+    /// Called by the game before each update.
+    pub fn update(&mut self) {
+        for channel in self.channels.values_mut() {
+            channel.update();
+        }
+    }
+
+    /// Drops every queued event. Subscribers are kept.
+    pub fn clear(&mut self) {
+        for channel in self.channels.values_mut() {
+            channel.clear();
+        }
+    }
+
+    fn channel<E: 'static>(&self) -> Option<&Channel<E>> {
+        self.channels.get(&TypeId::of::<E>()).and_then(|channel| channel.as_any().downcast_ref())
+    }
+
+    fn channel_mut<E: 'static>(&mut self) -> &mut Channel<E> {
+        self.channels
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| Box::new(Channel::<E>::new()))
+            .as_any_mut()
+            .downcast_mut()
+            .unwrap()
+    }
+}
+
+/// A subscriber and the id `unsubscribe` finds it by.
+type Listener<E> = (u64, Box<dyn FnMut(&E)>);
+
+/// The queue and listeners of one event type.
+struct Channel<E> {
+    /// Events emitted during the previous frame.
+    previous: Vec<E>,
+    /// Events emitted during this frame.
+    current: Vec<E>,
+    /// Sequence number of the first event in `previous`.
+    start: u64,
+    listeners: Vec<Listener<E>>,
+}
+
+impl<E> Channel<E> {
+    fn new() -> Channel<E> {
+        Channel { previous: Vec::new(), current: Vec::new(), start: 0, listeners: Vec::new() }
+    }
+
+    /// Sequence number of the next event emitted.
+    fn end(&self) -> u64 {
+        self.start + (self.previous.len() + self.current.len()) as u64
+    }
+}
+
+/// The type-erased operations the bus runs on every channel.
+trait AnyChannel {
+    fn update(&mut self);
+    fn clear(&mut self);
+    fn unsubscribe(&mut self, id: u64) -> bool;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<E: 'static> AnyChannel for Channel<E> {
+    fn update(&mut self) {
+        self.start += self.previous.len() as u64;
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    fn clear(&mut self) {
+        self.start = self.end();
+        self.previous.clear();
+        self.current.clear();
+    }
+
+    fn unsubscribe(&mut self, id: u64) -> bool {
+        let count = self.listeners.len();
+        self.listeners.retain(|(listener, _)| *listener != id);
+        self.listeners.len() != count
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Debug, PartialEq)]
+    struct Hit(u32);
+
+    struct Other;
+
+    fn read(events: &EventBus, reader: &mut EventReader<Hit>) -> Vec<u32> {
+        events.read(reader).map(|hit| hit.0).collect()
+    }
+
+    #[test]
+    fn reads_cross_one_frame_boundary() {
+        let mut events = EventBus::new();
+        let mut early = events.reader::<Hit>();
+        let mut late = events.reader::<Hit>();
+        events.emit(Hit(1));
+        assert_eq!(read(&events, &mut early), vec![1]);
+
+        // Emitted last frame and this one, each seen once
+        events.update();
+        events.emit(Hit(2));
+        assert_eq!(read(&events, &mut early), vec![2]);
+        assert_eq!(read(&events, &mut late), vec![1, 2]);
+
+        events.update();
+        assert_eq!(read(&events, &mut late), Vec::<u32>::new());
+        assert_eq!(events.iter::<Hit>().count(), 1);
+        events.update();
+        assert_eq!(events.iter::<Hit>().count(), 0);
+    }
+
+    #[test]
+    fn readers_that_fall_behind_skip_dropped_events() {
+        let mut events = EventBus::new();
+        let mut reader = events.reader::<Hit>();
+        events.emit(Hit(1));
+        events.update();
+        events.emit(Hit(2));
+        events.update();
+        events.emit(Hit(3));
+        assert_eq!(read(&events, &mut reader), vec![2, 3]);
+    }
+
+    #[test]
+    fn new_readers_only_see_later_events() {
+        let mut events = EventBus::new();
+        events.emit(Hit(1));
+        events.update();
+        events.emit(Hit(2));
+        let mut reader = events.reader::<Hit>();
+        assert!(read(&events, &mut reader).is_empty());
+        events.emit(Hit(3));
+        assert_eq!(read(&events, &mut reader), vec![3]);
+    }
+
+    #[test]
+    fn readers_created_before_the_first_event_see_it() {
+        let mut events = EventBus::new();
+        let mut reader = events.reader::<Hit>();
+        assert!(read(&events, &mut reader).is_empty());
+        events.emit(Other);
+        events.emit(Hit(7));
+        assert_eq!(read(&events, &mut reader), vec![7]);
+    }
+
+    #[test]
+    fn clear_drops_queued_events_but_keeps_subscribers() {
+        let mut events = EventBus::new();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&seen);
+        events.subscribe(move |hit: &Hit| log.borrow_mut().push(hit.0));
+        let mut reader = events.reader::<Hit>();
+        events.emit(Hit(1));
+        events.update();
+        events.emit(Hit(2));
+        events.clear();
+        assert!(read(&events, &mut reader).is_empty());
+        events.emit(Hit(3));
+        assert_eq!(read(&events, &mut reader), vec![3]);
+        assert_eq!(*seen.borrow(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn unsubscribe_removes_only_that_listener() {
+        let mut events = EventBus::new();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let (first, second) = (Rc::clone(&seen), Rc::clone(&seen));
+        let a = events.subscribe(move |hit: &Hit| first.borrow_mut().push(("a", hit.0)));
+        events.subscribe(move |hit: &Hit| second.borrow_mut().push(("b", hit.0)));
+        events.emit(Hit(1));
+        assert!(events.unsubscribe(a));
+        assert!(!events.unsubscribe(a));
+        events.emit(Hit(2));
+        assert_eq!(*seen.borrow(), vec![("a", 1), ("b", 1), ("b", 2)]);
+    }
+
+    #[test]
+    fn event_types_are_independent() {
+        let mut events = EventBus::new();
+        let subscription = events.subscribe(|_: &Other| panic!("only hits are emitted"));
+        let mut reader = events.reader::<Hit>();
+        events.emit(Hit(1));
+        assert_eq!(events.iter::<Other>().count(), 0);
+        assert_eq!(read(&events, &mut reader), vec![1]);
+        // Unsubscribing from a type without a channel yet finds nothing
+        let mut empty = EventBus::new();
+        assert!(!empty.unsubscribe(subscription));
+    }
+}
@@ -1,68 +1,76 @@
 use crate::audio::AudioManager;
 use crate::graphics::wgpu_renderer::WgpuRenderer;
+use crate::graphics::render_settings::RenderSettings;
+use crate::game::game_config::{GameConfig, WindowMode};
 use crate::input::KeyboardHandler;
-use crate::game::state::State;
+use crate::game::state::{State, Transition};
 use crate::game::context::Context;
-use std::time::Instant;
+use crate::game::timestep::FixedTimestep;
+use crate::game::window_command::{Attention, CursorGrab, WindowCommand};
+use crate::game::event_bus::GameEvent;
+use crate::game::screen_transition::{ScreenTransition, TRANSITION_FROM_TARGET, TRANSITION_TO_TARGET};
+use crate::graphics::renderer::Renderer;
+use std::time::{Duration, Instant};
 use std::rc::Rc;
 use std::cell::RefCell;
 use winit::{
     event::{Event, WindowEvent},
-    event_loop::EventLoop,
-    window::WindowBuilder,
+    event_loop::{ControlFlow, EventLoop},
+    monitor::MonitorHandle,
+    window::{CursorGrabMode, Fullscreen, Icon, UserAttentionType, Window, WindowBuilder},
 };
 use std::sync::Arc;
 
-pub struct Game<C: Context> {
-    pub renderer: Option<WgpuRenderer>,
+/// Runs a stack of states in a window. With `SoftwareRenderer` as `R`, the game can
+/// also be driven frame by frame without a window; see `Game::headless`.
+pub struct Game<C: Context, R: Renderer = WgpuRenderer> {
+    pub renderer: Option<R>,
     pub keyboard: Rc<RefCell<KeyboardHandler>>,
     pub audio_manager: AudioManager,
     pub context: Option<C>,
-    pub current_state: Option<Box<dyn State<C>>>,
+    /// The state stack. The last state is on top and receives input first.
+    pub states: Vec<Box<dyn State<C>>>,
     pub running: bool,
     pub last_frame_time: Instant,
-    pub window_title: String,
-    pub window_width: u32,
-    pub window_height: u32,
+    pub config: GameConfig,
+    pub fixed_timestep: Option<FixedTimestep>,
+    /// The screen transition in progress. Keyboard input is ignored while it runs.
+    pub screen_transition: Option<ScreenTransition>,
+    pub focused: bool,
+    /// False while the window is minimized or fully hidden.
+    pub visible: bool,
+    pub suspended: bool,
 }
 
 impl<C: Context> Game<C> {
     pub fn new(window_title: &str, window_width: u32, window_height: u32) -> Result<Self, Box<dyn std::error::Error>> {
-        env_logger::init();
-        
-        let keyboard = Rc::new(RefCell::new(KeyboardHandler::new()));
-        let audio_manager = AudioManager::new()?;
+        Game::from_config(GameConfig::new(window_title, window_width, window_height))
+    }
+
+    pub fn from_config(config: GameConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        // Another game or the application may already have installed a logger
+        let _ = env_logger::try_init();
         
-        Ok(Game {
-            renderer: None,
-            keyboard,
-            audio_manager,
-            context: None, // Will be initialized in run() when we have a mutable reference to self
-            current_state: None,
-            running: true,
-            last_frame_time: Instant::now(),
-            window_title: window_title.to_string(),
-            window_width,
-            window_height,
-        })
+        // The renderer needs the window, which is created in run()
+        Ok(Game::with_parts(None, AudioManager::new()?, config))
     }
     
-    pub fn get_keyboard(&self) -> &Rc<RefCell<KeyboardHandler>> {
-        &self.keyboard
+    /// Sets the renderer options used when the window is created.
+    pub fn with_render_settings(mut self, render_settings: RenderSettings) -> Self {
+        self.config.render_settings = render_settings;
+        self
     }
-    
+
     pub fn run(
         mut self,
         mut initial_state: Box<dyn State<C>>,
         mut context: C,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let event_loop = EventLoop::new()?;
-        let window = Arc::new(WindowBuilder::new()
-            .with_title(&self.window_title)
-            .with_inner_size(winit::dpi::LogicalSize::new(self.window_width as f64, self.window_height as f64))
-            .build(&event_loop)?);
+        let window = Arc::new(self.window_builder(&event_loop)?.build(&event_loop)?);
+        window.set_cursor_visible(self.config.cursor_visible);
         let window_for_renderer = Arc::clone(&window);
-        let (renderer, mut surface, mut config) = WgpuRenderer::new(&window_for_renderer)?;
+        let (renderer, mut surface) = WgpuRenderer::new(&window_for_renderer, self.config.render_settings)?;
         self.renderer = Some(renderer);
         
         // Initialize context
@@ -72,7 +80,7 @@ impl<C: Context> Game<C> {
         if let Some(context) = &mut self.context {
             initial_state.on_enter(context);
         }
-        self.current_state = Some(initial_state);
+        self.states.push(initial_state);
         
         event_loop.run(move |event, elwt| {
             let window = Arc::clone(&window);
@@ -80,53 +88,63 @@ impl<C: Context> Game<C> {
                 Event::WindowEvent { event, .. } => {
                     match event {
                         WindowEvent::CloseRequested => {
-                            self.running = false;
+                            self.shutdown();
                             elwt.exit();
                         }
                         WindowEvent::KeyboardInput { event, .. } => {
-                            if let Some(winit::keyboard::NamedKey::Escape) = match &event.logical_key {
+                            if let (true, Some(winit::keyboard::NamedKey::Escape)) = (self.config.quit_on_escape, match &event.logical_key {
                                 winit::keyboard::Key::Character(_) => None,
                                 winit::keyboard::Key::Named(named) => Some(*named),
                                 winit::keyboard::Key::Unidentified(_) => None,
                                 winit::keyboard::Key::Dead(_) => None,
-                            } {
-                                self.running = false;
+                            }) {
+                                self.shutdown();
                                 elwt.exit();
                             }
-                            self.keyboard.borrow_mut().handle_event(&event);
+                            if self.screen_transition.is_none() {
+                                self.keyboard.borrow_mut().handle_event(&event);
+                                if !event.repeat {
+                                    let key = event.logical_key.clone();
+                                    self.publish(match event.state {
+                                        winit::event::ElementState::Pressed => GameEvent::KeyPressed(key),
+                                        winit::event::ElementState::Released => GameEvent::KeyReleased(key),
+                                    });
+                                }
+                            }
                         }
                         WindowEvent::Resized(new_size) => {
                             if let Some(renderer) = &mut self.renderer {
-                                renderer.resize(new_size, &mut surface, &mut config);
+                                renderer.resize(new_size, &mut surface);
                             }
+                            self.publish(GameEvent::Resized(new_size.width, new_size.height));
+                            // Some platforms report minimizing as a resize to zero
+                            self.set_visible(new_size.width > 0 && new_size.height > 0);
                         }
+                        WindowEvent::Focused(focused) => self.set_focused(focused),
+                        WindowEvent::Occluded(occluded) => self.set_visible(!occluded),
+                        WindowEvent::ScaleFactorChanged { scale_factor, .. } => self.scale_factor_changed(scale_factor),
                         WindowEvent::RedrawRequested => {
-                            let now = Instant::now();
-                            let dt = now.duration_since(self.last_frame_time).as_secs_f32();
-                            self.last_frame_time = now;
-                            // Handle state update and rendering
-                            if let Some(mut state) = self.current_state.take() {
-                                if let Some(context) = &mut self.context {
-                                    // Update state
-                                    if let Some(new_state) = context.update(&mut state, dt) {
-                                        // Call on_exit for current state
-                                        state.on_exit(context);
-                                        // Set new state and call on_enter
-                                        let mut next_state = new_state;
-                                        next_state.on_enter(context);
-                                        state = next_state;
-                                    }
-                                    // Render state
+                            let dt = self.frame_dt();
+                            self.update_and_render(dt);
+                            let commands = self.context.as_mut().map(|context| context.take_window_commands()).unwrap_or_default();
+                            for command in commands {
+                                if let Some(new_size) = self.apply_window_command(&window, command) {
                                     if let Some(renderer) = &mut self.renderer {
-                                        context.render_state(&mut state, renderer);
+                                        renderer.resize(new_size, &mut surface);
                                     }
                                 }
-                                // Put state back
-                                self.current_state = Some(state);
+                            }
+                            if self.states.is_empty() {
+                                self.running = false;
+                                elwt.exit();
+                                return;
                             }
                             // Present the renderer
                             if let Some(renderer) = &mut self.renderer {
-                                renderer.present(&mut surface);
+                                if let Err(e) = renderer.present(&mut surface) {
+                                    log::error!("Failed to present frame: {}", e);
+                                    elwt.exit();
+                                }
                             }
                         }
                         _ => {}
@@ -135,7 +153,23 @@ impl<C: Context> Game<C> {
                 Event::DeviceEvent { event, .. } => {
                     self.keyboard.borrow_mut().handle_device_event(&event);
                 }
+                Event::Suspended => self.set_suspended(true),
+                Event::Resumed => self.set_suspended(false),
                 Event::AboutToWait => {
+                    // Nothing changes while paused, so only redraw when the platform asks
+                    if self.is_paused() {
+                        elwt.set_control_flow(ControlFlow::Wait);
+                        return;
+                    }
+                    // Sleep until the next frame is due when the frame rate is capped
+                    if let Some(fps) = self.config.target_fps.filter(|fps| *fps > 0.0) {
+                        let next_frame = self.last_frame_time + Duration::from_secs_f32(1.0 / fps);
+                        if Instant::now() < next_frame {
+                            elwt.set_control_flow(ControlFlow::WaitUntil(next_frame));
+                            return;
+                        }
+                    }
+                    elwt.set_control_flow(ControlFlow::Poll);
                     window.request_redraw();
                     self.keyboard.borrow_mut().update();
                 }
@@ -145,4 +179,727 @@ impl<C: Context> Game<C> {
         
         Ok(())
     }
-} 
\ No newline at end of file
+
+    /// Builds the window described by the config.
+    fn window_builder(&self, event_loop: &EventLoop<()>) -> Result<WindowBuilder, Box<dyn std::error::Error>> {
+        let config = &self.config;
+        let mut builder = WindowBuilder::new()
+            .with_title(&config.title)
+            .with_inner_size(winit::dpi::LogicalSize::new(config.size.0 as f64, config.size.1 as f64))
+            .with_resizable(config.resizable);
+        if let Some((width, height)) = config.min_size {
+            builder = builder.with_min_inner_size(winit::dpi::LogicalSize::new(width as f64, height as f64));
+        }
+        if let Some((width, height)) = config.max_size {
+            builder = builder.with_max_inner_size(winit::dpi::LogicalSize::new(width as f64, height as f64));
+        }
+        if let Some((x, y)) = config.position {
+            builder = builder.with_position(winit::dpi::LogicalPosition::new(x as f64, y as f64));
+        }
+        if let Some(path) = &config.icon_path {
+            let image = image::open(path)
+                .map_err(|e| format!("Failed to load window icon {}: {}", path, e))?
+                .into_rgba8();
+            let (width, height) = image.dimensions();
+            builder = builder.with_window_icon(Some(Icon::from_rgba(image.into_raw(), width, height)?));
+        }
+        Ok(builder.with_fullscreen(Self::fullscreen(config.window_mode, event_loop.primary_monitor())))
+    }
+
+    fn fullscreen(mode: WindowMode, monitor: Option<MonitorHandle>) -> Option<Fullscreen> {
+        match mode {
+            WindowMode::Windowed => None,
+            WindowMode::Borderless => Some(Fullscreen::Borderless(None)),
+            // Use the largest, then fastest, video mode; without one fall back to borderless
+            WindowMode::Fullscreen => Some(monitor
+                .and_then(|monitor| monitor.video_modes().max_by_key(|mode| {
+                    let size = mode.size();
+                    (size.width * size.height, mode.refresh_rate_millihertz())
+                }))
+                .map(Fullscreen::Exclusive)
+                .unwrap_or(Fullscreen::Borderless(None))),
+        }
+    }
+
+    /// Applies a command queued by a state. Returns the new size when the window was
+    /// resized right away, so the renderer can follow before the next frame.
+    fn apply_window_command(&mut self, window: &Window, command: WindowCommand) -> Option<winit::dpi::PhysicalSize<u32>> {
+        match command {
+            WindowCommand::Quit => self.shutdown(),
+            WindowCommand::SetWindowMode(mode) => {
+                window.set_fullscreen(Self::fullscreen(mode, window.current_monitor()));
+                self.config.window_mode = mode;
+            },
+            WindowCommand::SetTitle(title) => {
+                window.set_title(&title);
+                self.config.title = title;
+            },
+            WindowCommand::SetSize(width, height) => {
+                self.config.size = (width, height);
+                return window.request_inner_size(winit::dpi::LogicalSize::new(width as f64, height as f64));
+            },
+            WindowCommand::SetCursorGrab(grab) => {
+                // Each platform supports only some grab modes, so try the other one as well
+                let result = match grab {
+                    CursorGrab::None => window.set_cursor_grab(CursorGrabMode::None),
+                    CursorGrab::Confined => window.set_cursor_grab(CursorGrabMode::Confined)
+                        .or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked)),
+                    CursorGrab::Locked => window.set_cursor_grab(CursorGrabMode::Locked)
+                        .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined)),
+                };
+                if let Err(e) = result {
+                    log::warn!("Failed to grab cursor: {}", e);
+                }
+            },
+            WindowCommand::SetCursorVisible(visible) => {
+                window.set_cursor_visible(visible);
+                self.config.cursor_visible = visible;
+            },
+            WindowCommand::RequestAttention(attention) => {
+                window.request_user_attention(Some(match attention {
+                    Attention::Critical => UserAttentionType::Critical,
+                    Attention::Informational => UserAttentionType::Informational,
+                }));
+            },
+        }
+        None
+    }
+}
+
+impl<C: Context, R: Renderer> Game<C, R> {
+    /// Creates a game with an empty state stack around `renderer` and `audio_manager`.
+    pub(crate) fn with_parts(renderer: Option<R>, audio_manager: AudioManager, config: GameConfig) -> Self {
+        Game {
+            renderer,
+            keyboard: Rc::new(RefCell::new(KeyboardHandler::new())),
+            audio_manager,
+            context: None,
+            states: Vec::new(),
+            running: true,
+            last_frame_time: Instant::now(),
+            config,
+            fixed_timestep: None,
+            screen_transition: None,
+            focused: true,
+            visible: true,
+            suspended: false,
+        }
+    }
+
+    /// Runs `State::fixed_update` at a fixed rate in addition to the per-frame
+    /// `update`, and renders with `State::render_interpolated`.
+    pub fn with_fixed_timestep(mut self, fixed_timestep: FixedTimestep) -> Self {
+        self.fixed_timestep = Some(fixed_timestep);
+        self
+    }
+
+    pub fn get_keyboard(&self) -> &Rc<RefCell<KeyboardHandler>> {
+        &self.keyboard
+    }
+
+    /// Whether updates are stopped because the window is unfocused, hidden or
+    /// suspended and the config pauses on focus loss.
+    pub fn is_paused(&self) -> bool {
+        self.config.pause_on_focus_loss && (!self.focused || !self.visible || self.suspended)
+    }
+
+    /// Records a focus change and tells every state. Called by the event loop;
+    /// headless games can call it to simulate focus changes.
+    pub fn set_focused(&mut self, focused: bool) {
+        if self.focused != focused {
+            self.update_pause(|game| game.focused = focused);
+            self.publish(GameEvent::FocusChanged(focused));
+            self.for_each_state(|state, context| state.on_focus_changed(context, focused));
+        }
+    }
+
+    /// Records the window being minimized or hidden, or shown again, and tells every state.
+    pub fn set_visible(&mut self, visible: bool) {
+        if self.visible != visible {
+            self.update_pause(|game| game.visible = visible);
+            self.publish(GameEvent::VisibilityChanged(visible));
+            self.for_each_state(|state, context| state.on_visibility_changed(context, visible));
+        }
+    }
+
+    /// Records the application being suspended or resumed and tells every state.
+    pub fn set_suspended(&mut self, suspended: bool) {
+        if self.suspended != suspended {
+            self.update_pause(|game| game.suspended = suspended);
+            self.publish(if suspended { GameEvent::Suspended } else { GameEvent::Resumed });
+            self.for_each_state(|state, context| if suspended {
+                state.on_app_suspended(context)
+            } else {
+                state.on_app_resumed(context)
+            });
+        }
+    }
+
+    pub fn scale_factor_changed(&mut self, scale_factor: f64) {
+        self.publish(GameEvent::ScaleFactorChanged(scale_factor));
+        self.for_each_state(|state, context| state.on_scale_factor_changed(context, scale_factor));
+    }
+
+    /// Returns the seconds since the previous frame and starts timing the next one.
+    pub(crate) fn frame_dt(&mut self) -> f32 {
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_frame_time).as_secs_f32();
+        self.last_frame_time = now;
+        dt
+    }
+
+    /// Applies `change` and pauses or resumes audio when that pauses or resumes the game.
+    fn update_pause(&mut self, change: impl FnOnce(&mut Self)) {
+        let was_paused = self.is_paused();
+        change(self);
+        let paused = self.is_paused();
+        if paused == was_paused {
+            return;
+        }
+        if paused {
+            self.audio_manager.pause_all();
+        } else {
+            self.audio_manager.resume_all();
+            // Don't count the paused time as one long frame
+            self.last_frame_time = Instant::now();
+            if let Some(timestep) = self.fixed_timestep.as_mut() {
+                timestep.reset();
+            }
+        }
+        if let Some(context) = self.context.as_mut() {
+            context.set_audio_paused(paused);
+        }
+    }
+
+    /// Emits `event` on the context's event bus, if it has one.
+    pub(crate) fn publish(&mut self, event: GameEvent) {
+        if let Some(events) = self.context.as_mut().and_then(|context| context.events()) {
+            events.emit(event);
+        }
+    }
+
+    /// Calls `hook` on every state, bottom first.
+    fn for_each_state(&mut self, mut hook: impl FnMut(&mut Box<dyn State<C>>, &mut C)) {
+        if let Some(context) = self.context.as_mut() {
+            for state in self.states.iter_mut() {
+                hook(state, context);
+            }
+        }
+    }
+
+    /// Exits every state, top first, so the game can close cleanly.
+    pub(crate) fn shutdown(&mut self) {
+        self.running = false;
+        if let Some(context) = self.context.as_mut() {
+            while let Some(mut state) = self.states.pop() {
+                state.on_exit(context);
+            }
+        }
+    }
+
+    /// Updates the active states and renders the visible ones for one frame.
+    pub(crate) fn update_and_render(&mut self, dt: f32) {
+        if !self.is_paused() {
+            self.update(dt);
+        }
+        self.render();
+    }
+
+    fn update(&mut self, unscaled_dt: f32) {
+        if let Some(events) = self.context.as_mut().and_then(|context| context.events()) {
+            events.update();
+        }
+        let mut dt = unscaled_dt;
+        if let Some(context) = self.context.as_mut() {
+            if let Some(time) = context.time() {
+                dt = time.advance(unscaled_dt);
+                if let Some(speed) = time.next_audio_speed() {
+                    context.set_audio_speed(speed);
+                }
+            }
+        }
+        if let Some(scheduler) = self.context.as_mut().and_then(|context| context.scheduler()) {
+            scheduler.update(dt);
+        }
+        if let Some(transition) = self.screen_transition.as_mut() {
+            transition.update(unscaled_dt);
+            if transition.is_finished() {
+                self.screen_transition = None;
+            }
+        }
+        self.update_states(|context, state| context.update(state, dt));
+        // Run the fixed simulation steps that fit into this frame
+        if let Some(timestep) = self.fixed_timestep.as_mut() {
+            let step = timestep.step();
+            let steps = timestep.advance(dt);
+            for _ in 0..steps {
+                self.update_states(|context, state| context.fixed_update(state, step));
+            }
+        }
+    }
+
+    fn render(&mut self) {
+        let alpha = self.fixed_timestep.as_ref().map(|timestep| timestep.alpha());
+        if let (Some(renderer), Some(context)) = (&mut self.renderer, &mut self.context) {
+            match &self.screen_transition {
+                Some(transition) => {
+                    renderer.begin_render_target(TRANSITION_TO_TARGET);
+                    Self::render_states(&mut self.states, context, renderer, alpha);
+                    renderer.end_render_target();
+                    transition.draw(renderer, TRANSITION_FROM_TARGET, TRANSITION_TO_TARGET);
+                },
+                None => Self::render_states(&mut self.states, context, renderer, alpha),
+            }
+        }
+    }
+
+    /// Renders the top state and every state it lets show beneath it, from the bottom up.
+    fn render_states(states: &mut [Box<dyn State<C>>], context: &mut C, renderer: &mut dyn Renderer, alpha: Option<f32>) {
+        let first = Self::first_active(states, |state| state.render_below());
+        for state in states[first..].iter_mut() {
+            match alpha {
+                Some(alpha) => context.render_state_interpolated(state, renderer, alpha),
+                None => context.render_state(state, renderer),
+            }
+        }
+    }
+
+    /// Updates the top state and every state it lets update beneath it, from the
+    /// bottom up, then applies the transitions they returned in the same order.
+    /// `Pop` and `Replace` act on the state that returned them, not the top one.
+    fn update_states(&mut self, mut update: impl FnMut(&mut C, &mut Box<dyn State<C>>) -> Transition<C>) {
+        let Some(context) = self.context.as_mut() else {
+            return;
+        };
+        let first = Self::first_active(&self.states, |state| state.update_below());
+        let mut transitions: Vec<(usize, Transition<C>)> = self.states[first..].iter_mut()
+            .enumerate()
+            .map(|(offset, state)| (first + offset, update(context, state)))
+            .collect();
+        for i in 0..transitions.len() {
+            let (index, transition) = std::mem::replace(&mut transitions[i], (0, Transition::None));
+            let (removes, clears) = match Self::stack_change(&transition) {
+                Transition::Pop => (true, false),
+                Transition::ClearTo(_) => (false, true),
+                _ => (false, false),
+            };
+            self.apply_transition(index, transition);
+            // Every state that returned a later transition was just exited
+            if clears {
+                break;
+            }
+            // Keep later indices pointing at the states that returned them
+            if removes {
+                for (later, _) in transitions[i + 1..].iter_mut().filter(|(later, _)| *later > index) {
+                    *later -= 1;
+                }
+            }
+        }
+    }
+
+    /// Returns the change an animated transition makes once its animation is set up.
+    fn stack_change(transition: &Transition<C>) -> &Transition<C> {
+        match transition {
+            Transition::Animated(inner, _) => Self::stack_change(inner),
+            other => other,
+        }
+    }
+
+    /// Returns the index of the lowest state reached by walking down from the top
+    /// while `reaches_below` holds.
+    fn first_active(states: &[Box<dyn State<C>>], reaches_below: impl Fn(&dyn State<C>) -> bool) -> usize {
+        let mut first = states.len().saturating_sub(1);
+        while first > 0 && reaches_below(states[first].as_ref()) {
+            first -= 1;
+        }
+        first
+    }
+
+    /// Applies a transition returned by the state at `index`. `Push` and `ClearTo`
+    /// act on the whole stack; `Pop` and `Replace` act on that state, and only a
+    /// popped top state resumes the one below.
+    fn apply_transition(&mut self, index: usize, transition: Transition<C>) {
+        let Some(context) = self.context.as_mut() else {
+            return;
+        };
+        let states = &mut self.states;
+        match transition {
+            Transition::None => {},
+            Transition::Push(mut next) => {
+                if let Some(top) = states.last_mut() {
+                    top.on_pause(context);
+                }
+                next.on_enter(context);
+                states.push(next);
+            },
+            Transition::Pop => {
+                if index >= states.len() {
+                    return;
+                }
+                let was_top = index + 1 == states.len();
+                states.remove(index).on_exit(context);
+                if was_top {
+                    if let Some(top) = states.last_mut() {
+                        top.on_resume(context);
+                    }
+                }
+            },
+            Transition::Replace(mut next) => {
+                if index >= states.len() {
+                    return;
+                }
+                states[index].on_exit(context);
+                next.on_enter(context);
+                states[index] = next;
+            },
+            Transition::ClearTo(mut next) => {
+                while let Some(mut state) = states.pop() {
+                    state.on_exit(context);
+                }
+                next.on_enter(context);
+                states.push(next);
+            },
+            Transition::Animated(transition, mut effect) => {
+                // Keep the outgoing screen as it looks now, before the stack changes
+                if let Some(renderer) = self.renderer.as_mut() {
+                    renderer.begin_render_target(TRANSITION_FROM_TARGET);
+                    Self::render_states(states, context, renderer, None);
+                    renderer.end_render_target();
+                }
+                effect.reset();
+                self.screen_transition = Some(effect);
+                // Release held keys so nothing stays pressed while input is blocked
+                self.keyboard.borrow_mut().keys.clear();
+                self.apply_transition(index, *transition);
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::barn_context::BarnContext;
+    use crate::graphics::software_renderer::SoftwareRenderer;
+    use winit::keyboard::Key;
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    /// A state that logs its hooks and returns `next` from its first update.
+    struct Probe {
+        name: &'static str,
+        log: Log,
+        update_below: bool,
+        next: Option<Transition<BarnContext>>,
+    }
+
+    impl Probe {
+        fn new(name: &'static str, log: &Log) -> Probe {
+            Probe { name, log: Rc::clone(log), update_below: false, next: None }
+        }
+
+        fn overlay(mut self) -> Probe {
+            self.update_below = true;
+            self
+        }
+
+        fn then(mut self, next: Transition<BarnContext>) -> Probe {
+            self.next = Some(next);
+            self
+        }
+
+        fn record(&self, hook: &str) {
+            self.log.borrow_mut().push(format!("{} {}", hook, self.name));
+        }
+    }
+
+    impl State<BarnContext> for Probe {
+        fn update(&mut self, _context: &mut BarnContext, _dt: f32) -> Transition<BarnContext> {
+            self.next.take().unwrap_or(Transition::None)
+        }
+        fn render(&mut self, _context: &mut BarnContext, _renderer: &mut dyn Renderer) {}
+        fn on_enter(&mut self, _context: &mut BarnContext) {
+            self.record("enter");
+        }
+        fn on_exit(&mut self, _context: &mut BarnContext) {
+            self.record("exit");
+        }
+        fn on_resume(&mut self, _context: &mut BarnContext) {
+            self.record("resume");
+        }
+        fn update_below(&self) -> bool {
+            self.update_below
+        }
+        fn get_name(&mut self) -> String {
+            self.name.to_string()
+        }
+    }
+
+    /// Starts a headless game with `states` stacked bottom first, then runs one frame.
+    fn run_stack(states: Vec<Probe>, log: &Log) -> Game<BarnContext, SoftwareRenderer> {
+        let mut game: Game<BarnContext, SoftwareRenderer> = Game::headless(GameConfig::new("Test", 4, 4));
+        let context = BarnContext::new(game.get_keyboard().clone());
+        let mut states = states.into_iter();
+        game.start(Box::new(states.next().unwrap()), context);
+        game.states.extend(states.map(|state| Box::new(state) as Box<dyn State<BarnContext>>));
+        log.borrow_mut().clear();
+        game.step(0.016);
+        game
+    }
+
+    #[test]
+    fn replace_below_an_overlay_replaces_the_state_that_returned_it() {
+        let log = Log::default();
+        let level = Probe::new("Level", &log).then(Transition::Replace(Box::new(Probe::new("Level2", &log))));
+        let mut game = run_stack(vec![level, Probe::new("Overlay", &log).overlay()], &log);
+        assert_eq!(game.state_names(), vec!["Level2", "Overlay"]);
+        assert_eq!(*log.borrow(), vec!["exit Level", "enter Level2"]);
+    }
+
+    #[test]
+    fn pop_below_an_overlay_leaves_the_overlay_on_top() {
+        let log = Log::default();
+        let states = vec![
+            Probe::new("Base", &log),
+            Probe::new("Level", &log).overlay().then(Transition::Pop),
+            Probe::new("Overlay", &log).overlay(),
+        ];
+        let mut game = run_stack(states, &log);
+        assert_eq!(game.state_names(), vec!["Base", "Overlay"]);
+        // The overlay still covers Base, so it isn't resumed
+        assert_eq!(*log.borrow(), vec!["exit Level"]);
+    }
+
+    #[test]
+    fn later_transitions_follow_states_shifted_by_a_pop() {
+        let log = Log::default();
+        let states = vec![
+            Probe::new("Base", &log),
+            Probe::new("Level", &log).overlay().then(Transition::Pop),
+            Probe::new("Overlay", &log).overlay().then(Transition::Replace(Box::new(Probe::new("Menu", &log)))),
+        ];
+        let mut game = run_stack(states, &log);
+        assert_eq!(game.state_names(), vec!["Base", "Menu"]);
+        assert_eq!(*log.borrow(), vec!["exit Level", "exit Overlay", "enter Menu"]);
+    }
+
+    #[test]
+    fn popping_the_top_resumes_the_state_below() {
+        let log = Log::default();
+        let states = vec![
+            Probe::new("Level", &log).then(Transition::Pop),
+            Probe::new("Pause", &log).overlay().then(Transition::Pop),
+        ];
+        let game = run_stack(states, &log);
+        assert!(!game.is_running());
+        assert_eq!(*log.borrow(), vec!["exit Level", "exit Pause"]);
+
+        let log = Log::default();
+        let states = vec![Probe::new("Level", &log), Probe::new("Pause", &log).then(Transition::Pop)];
+        let mut game = run_stack(states, &log);
+        assert_eq!(game.state_names(), vec!["Level"]);
+        assert_eq!(*log.borrow(), vec!["exit Pause", "resume Level"]);
+    }
+
+    #[test]
+    fn clear_to_drops_transitions_from_the_cleared_states() {
+        let log = Log::default();
+        let states = vec![
+            Probe::new("Level", &log).then(Transition::ClearTo(Box::new(Probe::new("Title", &log)))),
+            Probe::new("Overlay", &log).overlay().then(Transition::Push(Box::new(Probe::new("Menu", &log)))),
+        ];
+        let mut game = run_stack(states, &log);
+        assert_eq!(game.state_names(), vec!["Title"]);
+        assert_eq!(*log.borrow(), vec!["exit Overlay", "exit Level", "enter Title"]);
+    }
+
+    /// A state that records the `dt` of every update.
+    struct Clock {
+        dts: Rc<RefCell<Vec<f32>>>,
+    }
+
+    impl State<BarnContext> for Clock {
+        fn update(&mut self, _context: &mut BarnContext, dt: f32) -> Transition<BarnContext> {
+            self.dts.borrow_mut().push(dt);
+            Transition::None
+        }
+        fn render(&mut self, _context: &mut BarnContext, _renderer: &mut dyn Renderer) {}
+        fn on_enter(&mut self, _context: &mut BarnContext) {}
+        fn on_exit(&mut self, _context: &mut BarnContext) {}
+        fn get_name(&mut self) -> String {
+            String::from("Clock")
+        }
+    }
+
+    fn clock_game(config: GameConfig) -> (Game<BarnContext, SoftwareRenderer>, Rc<RefCell<Vec<f32>>>) {
+        let dts = Rc::new(RefCell::new(Vec::new()));
+        let mut game = Game::headless(config);
+        let context = BarnContext::new(game.get_keyboard().clone());
+        game.start(Box::new(Clock { dts: Rc::clone(&dts) }), context);
+        (game, dts)
+    }
+
+    #[test]
+    fn focus_loss_freezes_updates() {
+        let (mut game, dts) = clock_game(GameConfig::new("Test", 4, 4));
+        game.step(0.1);
+        game.set_focused(false);
+        assert!(game.is_paused());
+        game.run_frames(3, 0.1);
+        game.set_focused(true);
+        game.step(0.1);
+        assert_eq!(*dts.borrow(), vec![0.1, 0.1]);
+        let elapsed = game.context.as_mut().unwrap().time().unwrap().elapsed();
+        assert!((elapsed - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn focus_loss_keeps_updating_when_not_configured_to_pause() {
+        let mut config = GameConfig::new("Test", 4, 4);
+        config.pause_on_focus_loss = false;
+        let (mut game, dts) = clock_game(config);
+        game.set_focused(false);
+        assert!(!game.is_paused());
+        game.run_frames(2, 0.1);
+        assert_eq!(dts.borrow().len(), 2);
+    }
+
+    #[test]
+    fn regaining_focus_does_not_catch_up_the_paused_time() {
+        let (game, _) = clock_game(GameConfig::new("Test", 4, 4));
+        let mut game = game.with_fixed_timestep(FixedTimestep::new(10.0));
+        game.step(0.05);
+        assert!(game.fixed_timestep.as_ref().unwrap().alpha() > 0.0);
+
+        game.set_focused(false);
+        // The window stays unfocused for a while
+        game.last_frame_time -= Duration::from_secs(5);
+        game.set_focused(true);
+        assert!(game.frame_dt() < 1.0);
+        assert_eq!(game.fixed_timestep.as_ref().unwrap().alpha(), 0.0);
+    }
+
+    #[test]
+    fn regaining_focus_keeps_a_manual_pause() {
+        let (mut game, dts) = clock_game(GameConfig::new("Test", 4, 4));
+        game.context.as_mut().unwrap().time().unwrap().set_paused(true);
+        game.set_focused(false);
+        game.set_focused(true);
+        assert!(!game.is_paused());
+        assert!(game.context.as_mut().unwrap().time().unwrap().is_paused());
+        game.step(0.1);
+        assert_eq!(*dts.borrow(), vec![0.0]);
+    }
+
+    #[test]
+    fn character_keys_reach_event_readers() {
+        let (mut game, _) = clock_game(GameConfig::new("Test", 4, 4));
+        let mut reader = game.context.as_ref().unwrap().events.reader::<GameEvent>();
+        game.press_key(Key::Character("a".into()));
+        game.release_key(Key::Character("a".into()));
+        let events = &game.context.as_ref().unwrap().events;
+        assert_eq!(
+            events.read(&mut reader).cloned().collect::<Vec<_>>(),
+            vec![GameEvent::KeyPressed(Key::Character("a".into())), GameEvent::KeyReleased(Key::Character("a".into()))]
+        );
+        assert!(game.get_keyboard().borrow().keys.is_empty());
+    }
+
+    /// A state that draws a line of text and returns `next` from its first update.
+    struct Label {
+        text: &'static str,
+        x: f32,
+        color: [f32; 4],
+        next: Option<Transition<BarnContext>>,
+    }
+
+    impl State<BarnContext> for Label {
+        fn update(&mut self, _context: &mut BarnContext, _dt: f32) -> Transition<BarnContext> {
+            self.next.take().unwrap_or(Transition::None)
+        }
+        fn render(&mut self, _context: &mut BarnContext, renderer: &mut dyn Renderer) {
+            renderer.draw_text(self.text, self.x, 12.0, 12.0, self.color);
+        }
+        fn on_enter(&mut self, _context: &mut BarnContext) {}
+        fn on_exit(&mut self, _context: &mut BarnContext) {}
+        fn get_name(&mut self) -> String {
+            self.text.to_string()
+        }
+    }
+
+    #[test]
+    fn text_is_drawn_into_both_screens_of_a_transition() {
+        use crate::game::screen_transition::{Direction, TransitionEffect};
+
+        const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+        const BLUE: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+        let mut game: Game<BarnContext, SoftwareRenderer> = Game::headless(GameConfig::new("Test", 64, 16));
+        game.renderer.as_mut().unwrap().load_font("examples/resources/fonts/press-start/PressStart2P-vaV7.ttf").unwrap();
+        let context = BarnContext::new(game.get_keyboard().clone());
+        let level = Label { text: "B", x: 40.0, color: BLUE, next: None };
+        let wipe = ScreenTransition::new(TransitionEffect::Wipe(Direction::Left), 1.0);
+        let next = Transition::Replace(Box::new(level)).animated(wipe);
+        game.start(Box::new(Label { text: "A", x: 8.0, color: RED, next: Some(next) }), context);
+
+        game.step(0.0);
+        // Halfway through, the left half still shows the title and the right half the level
+        let frame = game.step(0.5);
+        let count = |color: fn(&[u8; 4]) -> bool, columns: std::ops::Range<u32>| {
+            columns.flat_map(|x| (0..16).map(move |y| (x, y))).filter(|&(x, y)| color(&frame.get_pixel(x, y).0)).count()
+        };
+        let red = |pixel: &[u8; 4]| pixel[0] > 128 && pixel[2] < 64;
+        let blue = |pixel: &[u8; 4]| pixel[2] > 128 && pixel[0] < 64;
+        assert!(count(red, 0..32) > 0);
+        assert!(count(blue, 32..64) > 0);
+        assert_eq!(count(red, 32..64) + count(blue, 0..32), 0);
+    }
+
+    /// A state that fills its left half with `left` and its right half with `right`.
+    struct Halves {
+        left: [f32; 4],
+        right: [f32; 4],
+        next: Option<Transition<BarnContext>>,
+    }
+
+    impl State<BarnContext> for Halves {
+        fn update(&mut self, _context: &mut BarnContext, _dt: f32) -> Transition<BarnContext> {
+            self.next.take().unwrap_or(Transition::None)
+        }
+        fn render(&mut self, _context: &mut BarnContext, renderer: &mut dyn Renderer) {
+            let (width, height) = renderer.size();
+            renderer.draw_rect(0, 0, width / 2, height, self.left);
+            renderer.draw_rect((width / 2) as i32, 0, width - width / 2, height, self.right);
+        }
+        fn on_enter(&mut self, _context: &mut BarnContext) {}
+        fn on_exit(&mut self, _context: &mut BarnContext) {}
+        fn get_name(&mut self) -> String {
+            String::from("Halves")
+        }
+    }
+
+    #[test]
+    fn outgoing_screen_is_scaled_to_a_resized_window() {
+        use crate::game::screen_transition::{Direction, TransitionEffect};
+
+        const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+        const GREEN: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+        const BLUE: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+        let mut game: Game<BarnContext, SoftwareRenderer> = Game::headless(GameConfig::new("Test", 4, 4));
+        let context = BarnContext::new(game.get_keyboard().clone());
+        let level = Halves { left: BLUE, right: BLUE, next: None };
+        let wipe = ScreenTransition::new(TransitionEffect::Wipe(Direction::Left), 1.0);
+        let next = Transition::Replace(Box::new(level)).animated(wipe);
+        game.start(Box::new(Halves { left: RED, right: GREEN, next: Some(next) }), context);
+        game.step(0.0);
+
+        game.context.as_mut().unwrap().window.set_size(8, 8);
+        game.step(0.0);
+        // Halfway through, the left half of the window shows the whole left half of the old screen
+        let frame = game.step(0.5);
+        assert_eq!(frame.dimensions(), (8, 8));
+        for x in 0..4 {
+            assert_eq!(frame.get_pixel(x, 7).0, [255, 0, 0, 255]);
+        }
+        assert_eq!(frame.get_pixel(4, 0).0, [0, 0, 255, 255]);
+    }
+}
@@ -0,0 +1,291 @@
+use serde::Deserialize;
+use crate::graphics::render_settings::{RenderSettings, VSync};
+
+/// How the window occupies the screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowMode {
+    Windowed,
+    /// Exclusive fullscreen at the primary monitor's largest video mode.
+    Fullscreen,
+    /// A borderless window covering the current monitor.
+    Borderless,
+}
+
+/// Window and runtime options for `Game::from_config`.
+///
+/// Sizes and positions are in logical pixels. Settings can also be read from a
+/// TOML file with `GameConfig::load`, or deserialized from any serde format laid
+/// out the same way; missing keys keep their defaults.
+///
+/// # Examples
+///
+/// ```
+/// use barn::game::game_config::{GameConfig, WindowMode};
+///
+/// let config = GameConfig::from_toml(r#"
+///     [window]
+///     title = "Barn"
+///     size = [1280, 720]
+///     mode = "borderless"
+///
+///     [runtime]
+///     target_fps = 60
+///     quit_on_escape = false
+/// "#).unwrap();
+/// assert_eq!(config.size, (1280, 720));
+/// assert_eq!(config.window_mode, WindowMode::Borderless);
+/// assert_eq!(config.target_fps, Some(60.0));
+/// assert!(!config.quit_on_escape);
+/// assert!(config.resizable);
+/// ```
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "ConfigFile")]
+pub struct GameConfig {
+    pub title: String,
+    pub size: (u32, u32),
+    pub resizable: bool,
+    pub window_mode: WindowMode,
+    pub min_size: Option<(u32, u32)>,
+    pub max_size: Option<(u32, u32)>,
+    /// Path of an image used as the window icon.
+    pub icon_path: Option<String>,
+    pub cursor_visible: bool,
+    /// Position of the window's top left corner. `None` lets the platform choose.
+    pub position: Option<(i32, i32)>,
+    /// Upper limit on frames per second. `None` renders as fast as vsync allows.
+    pub target_fps: Option<f32>,
+    /// Whether pressing Escape closes the game.
+    pub quit_on_escape: bool,
+    /// Whether updates and audio stop while the window is unfocused, hidden or suspended.
+    pub pause_on_focus_loss: bool,
+    /// Renderer options used when the window is created. States change them at
+    /// runtime with `Renderer::apply_settings`.
+    pub render_settings: RenderSettings,
+}
+
+impl GameConfig {
+    pub fn new(title: &str, width: u32, height: u32) -> GameConfig {
+        GameConfig {
+            title: title.to_string(),
+            size: (width, height),
+            resizable: true,
+            window_mode: WindowMode::Windowed,
+            min_size: None,
+            max_size: None,
+            icon_path: None,
+            cursor_visible: true,
+            position: None,
+            target_fps: None,
+            quit_on_escape: true,
+            pause_on_focus_loss: true,
+            render_settings: RenderSettings::default(),
+        }
+    }
+
+    pub fn with_resizable(mut self, resizable: bool) -> GameConfig {
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn with_window_mode(mut self, window_mode: WindowMode) -> GameConfig {
+        self.window_mode = window_mode;
+        self
+    }
+
+    pub fn with_min_size(mut self, width: u32, height: u32) -> GameConfig {
+        self.min_size = Some((width, height));
+        self
+    }
+
+    pub fn with_max_size(mut self, width: u32, height: u32) -> GameConfig {
+        self.max_size = Some((width, height));
+        self
+    }
+
+    pub fn with_icon(mut self, path: &str) -> GameConfig {
+        self.icon_path = Some(path.to_string());
+        self
+    }
+
+    pub fn with_cursor_visible(mut self, visible: bool) -> GameConfig {
+        self.cursor_visible = visible;
+        self
+    }
+
+    pub fn with_position(mut self, x: i32, y: i32) -> GameConfig {
+        self.position = Some((x, y));
+        self
+    }
+
+    pub fn with_target_fps(mut self, fps: f32) -> GameConfig {
+        self.target_fps = Some(fps);
+        self
+    }
+
+    pub fn with_vsync(mut self, vsync: VSync) -> GameConfig {
+        self.render_settings.vsync = vsync;
+        self
+    }
+
+    pub fn with_quit_on_escape(mut self, quit_on_escape: bool) -> GameConfig {
+        self.quit_on_escape = quit_on_escape;
+        self
+    }
+
+    pub fn with_pause_on_focus_loss(mut self, pause_on_focus_loss: bool) -> GameConfig {
+        self.pause_on_focus_loss = pause_on_focus_loss;
+        self
+    }
+
+    pub fn with_render_settings(mut self, render_settings: RenderSettings) -> GameConfig {
+        self.render_settings = render_settings;
+        self
+    }
+
+    /// Reads a TOML settings file. See `from_toml` for the recognized keys.
+    pub fn load(path: &str) -> Result<GameConfig, Box<dyn std::error::Error>> {
+        let source = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        GameConfig::from_toml(&source).map_err(|e| format!("{}: {}", path, e).into())
+    }
+
+    /// Parses TOML settings. The `[window]` table accepts `title`, `size`,
+    /// `resizable`, `mode` (`"windowed"`, `"fullscreen"` or `"borderless"`),
+    /// `min_size`, `max_size`, `icon`, `cursor_visible` and `position`; the
+    /// `[runtime]` table accepts `target_fps`, `vsync` (`"on"`, `"adaptive"` or
+    /// `"off"`), `msaa`, `quit_on_escape` and `pause_on_focus_loss`. Sizes and
+    /// positions are two-element arrays.
+    pub fn from_toml(source: &str) -> Result<GameConfig, Box<dyn std::error::Error>> {
+        Ok(toml::from_str(source)?)
+    }
+}
+
+/// The layout of a settings file, before defaults are filled in.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ConfigFile {
+    window: WindowTable,
+    runtime: RuntimeTable,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct WindowTable {
+    title: Option<String>,
+    size: Option<(u32, u32)>,
+    resizable: Option<bool>,
+    mode: Option<WindowMode>,
+    min_size: Option<(u32, u32)>,
+    max_size: Option<(u32, u32)>,
+    icon: Option<String>,
+    cursor_visible: Option<bool>,
+    position: Option<(i32, i32)>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct RuntimeTable {
+    target_fps: Option<f32>,
+    vsync: Option<VSync>,
+    msaa: Option<u32>,
+    quit_on_escape: Option<bool>,
+    pause_on_focus_loss: Option<bool>,
+}
+
+impl TryFrom<ConfigFile> for GameConfig {
+    type Error = String;
+
+    fn try_from(file: ConfigFile) -> Result<GameConfig, String> {
+        let defaults = GameConfig::new("Barn", 800, 600);
+        let (window, runtime) = (file.window, file.runtime);
+        if runtime.target_fps.is_some_and(|fps| fps <= 0.0 || !fps.is_finite()) {
+            return Err("runtime.target_fps must be a positive number".to_string());
+        }
+        Ok(GameConfig {
+            title: window.title.unwrap_or(defaults.title),
+            size: window.size.unwrap_or(defaults.size),
+            resizable: window.resizable.unwrap_or(defaults.resizable),
+            window_mode: window.mode.unwrap_or(defaults.window_mode),
+            min_size: window.min_size,
+            max_size: window.max_size,
+            icon_path: window.icon,
+            cursor_visible: window.cursor_visible.unwrap_or(defaults.cursor_visible),
+            position: window.position,
+            target_fps: runtime.target_fps,
+            quit_on_escape: runtime.quit_on_escape.unwrap_or(defaults.quit_on_escape),
+            pause_on_focus_loss: runtime.pause_on_focus_loss.unwrap_or(defaults.pause_on_focus_loss),
+            render_settings: RenderSettings {
+                vsync: runtime.vsync.unwrap_or(defaults.render_settings.vsync),
+                msaa_samples: runtime.msaa.unwrap_or(defaults.render_settings.msaa_samples),
+                ..defaults.render_settings
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        GameConfig::from_toml(source).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn missing_keys_keep_their_defaults() {
+        let config = GameConfig::from_toml("[window]\ntitle = \"Farm\"").unwrap();
+        assert_eq!(config, GameConfig::new("Farm", 800, 600));
+        assert_eq!(GameConfig::from_toml("").unwrap(), GameConfig::new("Barn", 800, 600));
+    }
+
+    #[test]
+    fn runtime_settings_are_read() {
+        let config = GameConfig::from_toml(r#"
+            [window]
+            min_size = [320, 180]
+            position = [-20, 40]
+            icon = "icon.png"
+
+            [runtime]
+            target_fps = 29.97
+            vsync = "adaptive"
+            msaa = 4
+            pause_on_focus_loss = false
+        "#).unwrap();
+        assert_eq!(config.min_size, Some((320, 180)));
+        assert_eq!(config.position, Some((-20, 40)));
+        assert_eq!(config.icon_path.as_deref(), Some("icon.png"));
+        assert_eq!(config.target_fps, Some(29.97));
+        assert_eq!(config.render_settings.vsync, VSync::Adaptive);
+        assert_eq!(config.render_settings.msaa_samples, 4);
+        assert!(!config.pause_on_focus_loss);
+    }
+
+    #[test]
+    fn unknown_keys_and_tables_are_ignored() {
+        let config = GameConfig::from_toml("saved = 2024-05-01T10:00:00Z\n[window]\ntheme = \"dark\"\n[audio]\nvolume = 0.5").unwrap();
+        assert_eq!(config, GameConfig::new("Barn", 800, 600));
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        assert!(error("[window]\nmode = \"maximized\"").contains("maximized"));
+        assert!(error("[window]\nsize = [-1, 720]").contains("size"));
+        assert!(error("[window]\nsize = [1280]").contains("size"));
+        assert!(error("[window]\nresizable = \"yes\"").contains("resizable"));
+        assert!(error("[runtime]\nvsync = true").contains("vsync"));
+        assert!(error("[runtime]\ntarget_fps = 0").contains("target_fps"));
+    }
+
+    #[test]
+    fn malformed_toml_is_rejected() {
+        assert!(GameConfig::from_toml("[window]\n[window]").is_err());
+        assert!(GameConfig::from_toml("[window]\ntitle = \"Barn").is_err());
+    }
+
+    #[test]
+    fn load_names_the_file_in_errors() {
+        let error = GameConfig::load("tests/resources/missing.toml").err().unwrap().to_string();
+        assert!(error.starts_with("tests/resources/missing.toml: "));
+    }
+}
@@ -0,0 +1,146 @@
+use crate::audio::AudioManager;
+use crate::game::context::Context;
+use crate::game::event_bus::GameEvent;
+use crate::game::game::Game;
+use crate::game::game_config::GameConfig;
+use crate::game::state::State;
+use crate::game::window_command::WindowCommand;
+use crate::graphics::software_renderer::SoftwareRenderer;
+use image::RgbaImage;
+use winit::keyboard::Key;
+
+/// Driving a game without a window, event loop, GPU or audio device, e.g. to test
+/// gameplay in CI. Frames are rendered by a `SoftwareRenderer`.
+///
+/// # Examples
+///
+/// ```
+/// use barn::game::barn_context::BarnContext;
+/// use barn::game::context::Context;
+/// use barn::game::game::Game;
+/// use barn::game::game_config::GameConfig;
+/// use barn::game::state::{State, Transition};
+/// use barn::graphics::renderer::Renderer;
+/// use barn::graphics::software_renderer::SoftwareRenderer;
+/// use winit::keyboard::NamedKey;
+///
+/// struct Mover { x: f32 }
+///
+/// impl State<BarnContext> for Mover {
+///     fn update(&mut self, context: &mut BarnContext, dt: f32) -> Transition<BarnContext> {
+///         if context.get_input_handler().is_key_pressed(NamedKey::ArrowRight) {
+///             self.x += 100.0 * dt;
+///         }
+///         Transition::None
+///     }
+///     fn render(&mut self, _context: &mut BarnContext, renderer: &mut dyn Renderer) {
+///         renderer.draw_rect(self.x as i32, 0, 4, 4, [1.0, 1.0, 1.0, 1.0]);
+///     }
+///     fn on_enter(&mut self, _context: &mut BarnContext) {}
+///     fn on_exit(&mut self, _context: &mut BarnContext) {}
+///     fn get_name(&mut self) -> String { String::from("Mover") }
+/// }
+///
+/// let mut game: Game<BarnContext, SoftwareRenderer> = Game::headless(GameConfig::new("Test", 32, 8));
+/// let context = BarnContext::new(game.get_keyboard().clone());
+/// game.start(Box::new(Mover { x: 0.0 }), context);
+/// game.press_key(NamedKey::ArrowRight);
+/// game.run_frames(10, 0.02);
+/// // Ten frames at 100 pixels per second moved the square 20 pixels.
+/// let frame = game.frame();
+/// assert_eq!(frame.get_pixel(21, 1).0, [255, 255, 255, 255]);
+/// assert_ne!(frame.get_pixel(1, 1).0, [255, 255, 255, 255]);
+/// assert_eq!(game.state_names(), vec!["Mover"]);
+/// ```
+impl<C: Context> Game<C, SoftwareRenderer> {
+    /// Creates a game that renders into an image of the config's size instead of a
+    /// window. Sound is decoded but not played.
+    pub fn headless(config: GameConfig) -> Game<C, SoftwareRenderer> {
+        Game::with_parts(Some(SoftwareRenderer::new(config.size.0, config.size.1)), AudioManager::silent(), config)
+    }
+
+    /// Enters the initial state, like `Game::run` does before the first frame.
+    pub fn start(&mut self, mut initial_state: Box<dyn State<C>>, mut context: C) {
+        initial_state.on_enter(&mut context);
+        self.context = Some(context);
+        self.states.push(initial_state);
+    }
+
+    /// Updates and renders one frame of `dt` seconds, applies the window commands
+    /// queued during it and returns the rendered image.
+    pub fn step(&mut self, dt: f32) -> &RgbaImage {
+        if !self.states.is_empty() {
+            self.update_and_render(dt);
+            let commands = self.context.as_mut().map(|context| context.take_window_commands()).unwrap_or_default();
+            for command in commands {
+                self.apply_headless_command(command);
+            }
+            self.keyboard.borrow_mut().update();
+        }
+        self.renderer.as_mut().unwrap().present()
+    }
+
+    /// Runs `frames` frames of `dt` seconds each, stopping early once the game quits.
+    pub fn run_frames(&mut self, frames: u32, dt: f32) -> &RgbaImage {
+        for _ in 0..frames {
+            if !self.is_running() {
+                break;
+            }
+            self.step(dt);
+        }
+        self.frame()
+    }
+
+    /// Returns the last rendered image.
+    pub fn frame(&self) -> &RgbaImage {
+        self.renderer.as_ref().unwrap().frame()
+    }
+
+    /// Whether any state is left. Quitting or popping the last state ends the game.
+    pub fn is_running(&self) -> bool {
+        !self.states.is_empty()
+    }
+
+    /// Holds a key down from the next frame on. Ignored during screen transitions,
+    /// like real input. Character keys are only published as `GameEvent`s, since
+    /// the keyboard handler tracks named keys.
+    pub fn press_key(&mut self, key: impl Into<Key>) {
+        self.set_key(key.into(), true);
+    }
+
+    pub fn release_key(&mut self, key: impl Into<Key>) {
+        self.set_key(key.into(), false);
+    }
+
+    fn set_key(&mut self, key: Key, pressed: bool) {
+        if self.screen_transition.is_some() {
+            return;
+        }
+        if let Key::Named(named) = key {
+            self.keyboard.borrow_mut().keys.insert(named, pressed);
+        }
+        self.publish(if pressed { GameEvent::KeyPressed(key) } else { GameEvent::KeyReleased(key) });
+    }
+
+    /// Returns the names of the states on the stack, bottom first.
+    pub fn state_names(&mut self) -> Vec<String> {
+        self.states.iter_mut().map(|state| state.get_name()).collect()
+    }
+
+    /// Applies a window command to the config; resizing also resizes the renderer.
+    fn apply_headless_command(&mut self, command: WindowCommand) {
+        match command {
+            WindowCommand::Quit => self.shutdown(),
+            WindowCommand::SetWindowMode(mode) => self.config.window_mode = mode,
+            WindowCommand::SetTitle(title) => self.config.title = title,
+            WindowCommand::SetSize(width, height) => {
+                self.config.size = (width, height);
+                if let Some(renderer) = self.renderer.as_mut() {
+                    renderer.resize(width, height);
+                }
+            },
+            WindowCommand::SetCursorVisible(visible) => self.config.cursor_visible = visible,
+            WindowCommand::SetCursorGrab(_) | WindowCommand::RequestAttention(_) => {},
+        }
+    }
+}
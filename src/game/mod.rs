@@ -2,3 +2,11 @@ pub mod context;
 pub mod barn_context;
 pub mod game;
 pub mod state;
+pub mod timestep;
+pub mod screen_transition;
+pub mod game_config;
+pub mod window_command;
+pub mod headless;
+pub mod event_bus;
+pub mod scheduler;
+pub mod time;
@@ -0,0 +1,457 @@
+use crate::math::easing::Easing;
+use std::collections::VecDeque;
+
+/// Identifies a timer or sequence started on a `Scheduler`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimerHandle(u64);
+
+/// One-shot and repeating timers and running sequences, advanced by the game's
+/// `dt` before states update. Nothing advances while the game or the scheduler
+/// is paused.
+///
+/// Timers either call a callback or are polled through their handle with
+/// `just_fired`. A finished timer stays queryable until the next update.
+///
+/// # Examples
+///
+/// ```
+/// use barn::game::scheduler::Scheduler;
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// let mut scheduler = Scheduler::new();
+/// let spawned = Rc::new(Cell::new(0));
+/// let counter = Rc::clone(&spawned);
+/// scheduler.every(0.5, move || counter.set(counter.get() + 1));
+/// let bomb = scheduler.timer(1.2);
+/// let cancelled = scheduler.after(0.1, || panic!("cancelled timers never fire"));
+/// scheduler.cancel(cancelled);
+///
+/// scheduler.update(1.0);
+/// assert_eq!(spawned.get(), 2);
+/// assert!(!scheduler.just_fired(bomb));
+/// scheduler.update(0.25);
+/// assert!(scheduler.just_fired(bomb));
+/// assert!(!scheduler.is_active(bomb));
+/// ```
+pub struct Scheduler {
+    entries: Vec<Entry>,
+    next_id: u64,
+    paused: bool,
+    /// Multiplies the `dt` passed to `update`, e.g. 0.5 runs timers at half speed.
+    pub time_scale: f32,
+}
+
+struct Entry {
+    id: u64,
+    task: Task,
+    /// Whether the timer fired or the sequence completed during the last update.
+    fired: bool,
+    finished: bool,
+}
+
+enum Task {
+    Timer {
+        remaining: f32,
+        /// The period of a repeating timer.
+        interval: Option<f32>,
+        callback: Option<Box<dyn FnMut()>>,
+    },
+    Sequence(Sequence),
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler::new()
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler { entries: Vec::new(), next_id: 0, paused: false, time_scale: 1.0 }
+    }
+
+    /// Calls `callback` once after `seconds`. Cancel it with the returned handle.
+    pub fn after(&mut self, seconds: f32, callback: impl FnMut() + 'static) -> TimerHandle {
+        self.start_timer(seconds, None, Some(Box::new(callback)))
+    }
+
+    /// Calls `callback` every `seconds` until cancelled.
+    pub fn every(&mut self, seconds: f32, callback: impl FnMut() + 'static) -> TimerHandle {
+        self.start_timer(seconds, Some(seconds), Some(Box::new(callback)))
+    }
+
+    /// Starts a one-shot timer without a callback, to be polled with `just_fired`.
+    pub fn timer(&mut self, seconds: f32) -> TimerHandle {
+        self.start_timer(seconds, None, None)
+    }
+
+    /// Starts a repeating timer without a callback, to be polled with `just_fired`.
+    pub fn repeating_timer(&mut self, seconds: f32) -> TimerHandle {
+        self.start_timer(seconds, Some(seconds), None)
+    }
+
+    /// Runs `sequence` until its last step completes.
+    pub fn run(&mut self, sequence: Sequence) -> TimerHandle {
+        self.push(Task::Sequence(sequence))
+    }
+
+    /// Stops a timer or sequence. Returns false if it already finished.
+    pub fn cancel(&mut self, handle: TimerHandle) -> bool {
+        let count = self.entries.len();
+        self.entries.retain(|entry| entry.id != handle.0 || entry.finished);
+        self.entries.len() != count
+    }
+
+    /// Whether the timer or sequence is still scheduled.
+    pub fn is_active(&self, handle: TimerHandle) -> bool {
+        self.entry(handle).is_some_and(|entry| !entry.finished)
+    }
+
+    /// Whether the timer fired, or the sequence completed, during the last update.
+    pub fn just_fired(&self, handle: TimerHandle) -> bool {
+        self.entry(handle).is_some_and(|entry| entry.fired)
+    }
+
+    /// Seconds until the timer next fires, or `None` for sequences and finished timers.
+    pub fn remaining(&self, handle: TimerHandle) -> Option<f32> {
+        match self.entry(handle) {
+            Some(Entry { task: Task::Timer { remaining, .. }, finished: false, .. }) => Some(remaining.max(0.0)),
+            _ => None,
+        }
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Cancels every timer and sequence.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Advances every timer and sequence by `dt` seconds scaled by `time_scale`,
+    /// calling callbacks in the order the timers were started.
+    pub fn update(&mut self, dt: f32) {
+        self.entries.retain(|entry| !entry.finished);
+        // A fire is only reported for one update, even when the next one is paused
+        for entry in self.entries.iter_mut() {
+            entry.fired = false;
+        }
+        if self.paused {
+            return;
+        }
+        let dt = dt * self.time_scale;
+        for entry in self.entries.iter_mut() {
+            match &mut entry.task {
+                Task::Timer { remaining, interval, callback } => {
+                    *remaining -= dt;
+                    while *remaining <= 0.0 && !entry.finished {
+                        entry.fired = true;
+                        if let Some(callback) = callback.as_mut() {
+                            callback();
+                        }
+                        match interval {
+                            // A zero interval fires once per update instead of forever
+                            Some(interval) if *interval <= 0.0 => {
+                                *remaining = 0.0;
+                                break;
+                            },
+                            Some(interval) => *remaining += *interval,
+                            None => entry.finished = true,
+                        }
+                    }
+                },
+                Task::Sequence(sequence) => {
+                    if sequence.update(dt) {
+                        entry.fired = true;
+                        entry.finished = true;
+                    }
+                },
+            }
+        }
+    }
+
+    fn start_timer(&mut self, seconds: f32, interval: Option<f32>, callback: Option<Box<dyn FnMut()>>) -> TimerHandle {
+        self.push(Task::Timer { remaining: seconds, interval, callback })
+    }
+
+    fn push(&mut self, task: Task) -> TimerHandle {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.push(Entry { id, task, fired: false, finished: false });
+        TimerHandle(id)
+    }
+
+    fn entry(&self, handle: TimerHandle) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.id == handle.0)
+    }
+}
+
+enum Step {
+    Wait(f32),
+    WaitUntil(Box<dyn FnMut() -> bool>),
+    Action(Box<dyn FnMut()>),
+    Tween {
+        duration: f32,
+        easing: Easing,
+        apply: Box<dyn FnMut(f32)>,
+    },
+}
+
+/// A chain of waits, tweens and actions that runs one step after another, e.g.
+/// for cutscenes. Time left over when a step completes carries into the next.
+///
+/// Run it with `Scheduler::run`, or call `update` from a state.
+///
+/// # Examples
+///
+/// ```
+/// use barn::game::scheduler::Sequence;
+/// use barn::math::easing::Easing;
+/// use std::cell::{Cell, RefCell};
+/// use std::rc::Rc;
+///
+/// let x = Rc::new(Cell::new(0.0));
+/// let log = Rc::new(RefCell::new(Vec::new()));
+/// let (tween_x, first, last) = (Rc::clone(&x), Rc::clone(&log), Rc::clone(&log));
+/// let mut cutscene = Sequence::new()
+///     .then(move || first.borrow_mut().push("fade in"))
+///     .wait(1.0)
+///     .tween(2.0, Easing::Linear, move |t| tween_x.set(100.0 * t))
+///     .then(move || last.borrow_mut().push("done"));
+///
+/// assert!(!cutscene.update(2.0));
+/// assert_eq!(x.get(), 50.0);
+/// assert!(cutscene.update(1.0));
+/// assert_eq!(x.get(), 100.0);
+/// assert_eq!(*log.borrow(), vec!["fade in", "done"]);
+/// ```
+#[derive(Default)]
+pub struct Sequence {
+    steps: VecDeque<Step>,
+    /// Time spent in the current step.
+    elapsed: f32,
+}
+
+impl Sequence {
+    pub fn new() -> Sequence {
+        Sequence::default()
+    }
+
+    pub fn wait(mut self, seconds: f32) -> Sequence {
+        self.steps.push_back(Step::Wait(seconds));
+        self
+    }
+
+    /// Waits until `condition` returns true. It is checked once per update.
+    pub fn wait_until(mut self, condition: impl FnMut() -> bool + 'static) -> Sequence {
+        self.steps.push_back(Step::WaitUntil(Box::new(condition)));
+        self
+    }
+
+    /// Calls `action` once and moves on immediately.
+    pub fn then(mut self, action: impl FnMut() + 'static) -> Sequence {
+        self.steps.push_back(Step::Action(Box::new(action)));
+        self
+    }
+
+    /// Calls `apply` every update for `seconds` with the eased progress from 0 to 1.
+    /// The last call always passes 1.
+    pub fn tween(mut self, seconds: f32, easing: Easing, apply: impl FnMut(f32) + 'static) -> Sequence {
+        self.steps.push_back(Step::Tween { duration: seconds, easing, apply: Box::new(apply) });
+        self
+    }
+
+    /// Advances the sequence by `dt` seconds. Returns true once every step has completed.
+    pub fn update(&mut self, mut dt: f32) -> bool {
+        while let Some(step) = self.steps.front_mut() {
+            match step {
+                Step::Wait(duration) => {
+                    self.elapsed += dt;
+                    if self.elapsed < *duration {
+                        return false;
+                    }
+                    dt = self.elapsed - *duration;
+                },
+                Step::WaitUntil(condition) => {
+                    if !condition() {
+                        return false;
+                    }
+                },
+                Step::Action(action) => action(),
+                Step::Tween { duration, easing, apply } => {
+                    self.elapsed += dt;
+                    let t = if *duration > 0.0 { (self.elapsed / *duration).min(1.0) } else { 1.0 };
+                    apply(easing.apply(t));
+                    if self.elapsed < *duration {
+                        return false;
+                    }
+                    dt = self.elapsed - *duration;
+                },
+            }
+            self.elapsed = 0.0;
+            self.steps.pop_front();
+        }
+        true
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    fn counter() -> (Rc<Cell<u32>>, impl FnMut() + 'static) {
+        let count = Rc::new(Cell::new(0));
+        let handle = Rc::clone(&count);
+        (count, move || handle.set(handle.get() + 1))
+    }
+
+    #[test]
+    fn pausing_right_after_a_fire_clears_just_fired() {
+        let mut scheduler = Scheduler::new();
+        let timer = scheduler.repeating_timer(1.0);
+        scheduler.update(1.0);
+        assert!(scheduler.just_fired(timer));
+        scheduler.set_paused(true);
+        scheduler.update(1.0);
+        assert!(!scheduler.just_fired(timer));
+        assert_eq!(scheduler.remaining(timer), Some(1.0));
+        scheduler.set_paused(false);
+        scheduler.update(1.0);
+        assert!(scheduler.just_fired(timer));
+    }
+
+    #[test]
+    fn finished_timers_stay_queryable_until_the_next_update() {
+        let mut scheduler = Scheduler::new();
+        let timer = scheduler.timer(0.5);
+        scheduler.update(0.5);
+        assert!(scheduler.just_fired(timer));
+        assert!(!scheduler.is_active(timer));
+        assert_eq!(scheduler.remaining(timer), None);
+        assert!(!scheduler.cancel(timer));
+        scheduler.update(0.0);
+        assert!(!scheduler.just_fired(timer));
+    }
+
+    #[test]
+    fn long_updates_catch_up_on_every_repeat() {
+        let mut scheduler = Scheduler::new();
+        let (count, callback) = counter();
+        let timer = scheduler.every(0.25, callback);
+        scheduler.update(1.1);
+        assert_eq!(count.get(), 4);
+        assert!((scheduler.remaining(timer).unwrap() - 0.15).abs() < 1e-5);
+    }
+
+    #[test]
+    fn zero_intervals_fire_once_per_update() {
+        let mut scheduler = Scheduler::new();
+        let (count, callback) = counter();
+        scheduler.every(0.0, callback);
+        scheduler.update(0.016);
+        scheduler.update(0.016);
+        assert_eq!(count.get(), 2);
+    }
+
+    #[test]
+    fn callbacks_run_in_start_order() {
+        let mut scheduler = Scheduler::new();
+        let order = Rc::new(RefCell::new(Vec::new()));
+        for (name, delay) in [("slow", 0.3), ("fast", 0.1)] {
+            let order = Rc::clone(&order);
+            scheduler.after(delay, move || order.borrow_mut().push(name));
+        }
+        scheduler.update(1.0);
+        assert_eq!(*order.borrow(), vec!["slow", "fast"]);
+    }
+
+    #[test]
+    fn time_scale_and_pause_stop_time() {
+        let mut scheduler = Scheduler::new();
+        let timer = scheduler.timer(1.0);
+        scheduler.time_scale = 0.5;
+        scheduler.update(1.0);
+        assert_eq!(scheduler.remaining(timer), Some(0.5));
+        scheduler.set_paused(true);
+        scheduler.update(10.0);
+        assert!(scheduler.is_active(timer));
+        scheduler.time_scale = 0.0;
+        scheduler.set_paused(false);
+        scheduler.update(10.0);
+        assert_eq!(scheduler.remaining(timer), Some(0.5));
+    }
+
+    #[test]
+    fn cancel_and_clear_stop_timers() {
+        let mut scheduler = Scheduler::new();
+        let (count, callback) = counter();
+        let timer = scheduler.after(0.1, callback);
+        assert!(scheduler.cancel(timer));
+        assert!(!scheduler.cancel(timer));
+        let (other, callback) = counter();
+        let sequence = scheduler.run(Sequence::new().wait(0.1).then(callback));
+        scheduler.clear();
+        scheduler.update(1.0);
+        assert_eq!((count.get(), other.get()), (0, 0));
+        assert!(!scheduler.is_active(sequence));
+    }
+
+    #[test]
+    fn sequences_report_completion_once() {
+        let mut scheduler = Scheduler::new();
+        let sequence = scheduler.run(Sequence::new().wait(0.5).wait(0.5));
+        scheduler.update(0.6);
+        assert!(!scheduler.just_fired(sequence));
+        assert_eq!(scheduler.remaining(sequence), None);
+        scheduler.update(0.6);
+        assert!(scheduler.just_fired(sequence));
+        assert!(!scheduler.is_active(sequence));
+    }
+
+    #[test]
+    fn leftover_time_carries_into_the_next_step() {
+        let values = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&values);
+        let mut sequence = Sequence::new().wait(0.5).tween(1.0, Easing::Linear, move |t| log.borrow_mut().push(t));
+        assert!(!sequence.update(0.75));
+        assert_eq!(*values.borrow(), vec![0.25]);
+        assert!(sequence.update(5.0));
+        assert_eq!(*values.borrow(), vec![0.25, 1.0]);
+        assert!(sequence.is_finished());
+    }
+
+    #[test]
+    fn wait_until_blocks_until_the_condition_holds() {
+        let open = Rc::new(Cell::new(false));
+        let gate = Rc::clone(&open);
+        let (count, callback) = counter();
+        let mut sequence = Sequence::new().wait_until(move || gate.get()).then(callback);
+        assert!(!sequence.update(10.0));
+        open.set(true);
+        assert!(sequence.update(0.0));
+        assert_eq!(count.get(), 1);
+    }
+
+    #[test]
+    fn zero_length_tweens_apply_the_end_value() {
+        let values = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&values);
+        let mut sequence = Sequence::new().tween(0.0, Easing::Linear, move |t| log.borrow_mut().push(t));
+        assert!(sequence.update(0.0));
+        assert_eq!(*values.borrow(), vec![1.0]);
+        // An empty sequence is already done
+        assert!(Sequence::new().update(0.0));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::graphics::clip::ClipMask;
+use crate::graphics::mesh::MeshVertex;
+use crate::graphics::renderer::Renderer;
+use crate::graphics::wgpu_sprite::WgpuSprite;
+use crate::graphics::Rect;
+use crate::math::easing::Easing;
+
+/// Render target holding the last frame of the states being left.
+pub const TRANSITION_FROM_TARGET: &str = "barn:transition_from";
+/// Render target holding the states being entered, redrawn every frame.
+pub const TRANSITION_TO_TARGET: &str = "barn:transition_to";
+
+/// The direction the incoming screen moves in during a wipe or slide.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    /// Unit vector in screen pixels, with y pointing down.
+    fn vector(self) -> [f32; 2] {
+        match self {
+            Direction::Left => [-1.0, 0.0],
+            Direction::Right => [1.0, 0.0],
+            Direction::Up => [0.0, -1.0],
+            Direction::Down => [0.0, 1.0],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransitionEffect {
+    /// Fades the outgoing screen to a color over the first half, then fades the
+    /// incoming screen in from it.
+    Fade([f32; 4]),
+    /// Blends the incoming screen over the outgoing one.
+    Crossfade,
+    /// Uncovers the incoming screen from the edge it moves in from.
+    Wipe(Direction),
+    /// Pushes the outgoing screen off while the incoming one slides in behind it.
+    Slide(Direction),
+    /// Reveals the incoming screen through a circle growing from the center.
+    Iris,
+}
+
+/// An animated change between two screens, started by returning
+/// `Transition::animated` from a state.
+///
+/// Both screens are drawn from render targets: the outgoing states are drawn once
+/// into `TRANSITION_FROM_TARGET` when the transition starts, and the incoming states
+/// are drawn into `TRANSITION_TO_TARGET` every frame so they keep animating. If the
+/// window is resized meanwhile, the outgoing screen is scaled to the new size.
+///
+/// # Examples
+///
+/// ```
+/// use barn::game::screen_transition::{Direction, ScreenTransition, TransitionEffect};
+/// use barn::graphics::renderer::Renderer;
+/// use barn::graphics::software_renderer::SoftwareRenderer;
+///
+/// let mut renderer = SoftwareRenderer::new(4, 4);
+/// renderer.set_clear_color(0.0, 0.0, 0.0, 1.0);
+/// renderer.begin_render_target("from");
+/// renderer.draw_rect(0, 0, 4, 4, [1.0, 0.0, 0.0, 1.0]);
+/// renderer.end_render_target();
+/// renderer.begin_render_target("to");
+/// renderer.draw_rect(0, 0, 4, 4, [0.0, 0.0, 1.0, 1.0]);
+/// renderer.end_render_target();
+///
+/// let mut transition = ScreenTransition::new(TransitionEffect::Wipe(Direction::Left), 1.0);
+/// transition.update(0.5);
+/// transition.draw(&mut renderer, "from", "to");
+/// let frame = renderer.present();
+/// // The incoming screen moves in from the right.
+/// assert_eq!(frame.get_pixel(0, 0).0, [255, 0, 0, 255]);
+/// assert_eq!(frame.get_pixel(3, 0).0, [0, 0, 255, 255]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScreenTransition {
+    pub effect: TransitionEffect,
+    /// Length of the transition in seconds.
+    pub duration: f32,
+    pub easing: Easing,
+    elapsed: f32,
+}
+
+impl ScreenTransition {
+    pub fn new(effect: TransitionEffect, duration: f32) -> ScreenTransition {
+        ScreenTransition {
+            effect,
+            duration,
+            easing: Easing::Linear,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> ScreenTransition {
+        self.easing = easing;
+        self
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.elapsed = (self.elapsed + dt.max(0.0)).min(self.duration.max(0.0));
+    }
+
+    /// Eased progress from 0.0 to 1.0.
+    pub fn progress(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+        self.easing.apply(self.elapsed / self.duration)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// Starts the transition over.
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+    }
+
+    /// Composes the screens in the `from` and `to` textures at the current progress.
+    /// Nothing is drawn for a texture that has not been rendered yet.
+    pub fn draw(&self, renderer: &mut dyn Renderer, from: &str, to: &str) {
+        let (width, height) = renderer.size();
+        let (w, h) = (width as f32, height as f32);
+        let full = (0.0, 0.0, w, h);
+        let t = self.progress();
+        match self.effect {
+            TransitionEffect::Fade(color) => {
+                let (screen, alpha) = if t < 0.5 { (from, t * 2.0) } else { (to, 2.0 - t * 2.0) };
+                draw_screen(renderer, screen, full, full, 1.0);
+                renderer.draw_rect(0, 0, width, height, [color[0], color[1], color[2], color[3] * alpha]);
+            },
+            TransitionEffect::Crossfade => {
+                draw_screen(renderer, from, full, full, 1.0);
+                draw_screen(renderer, to, full, full, t);
+            },
+            TransitionEffect::Wipe(direction) => {
+                draw_screen(renderer, from, full, full, 1.0);
+                let region = match direction {
+                    Direction::Left => (w * (1.0 - t), 0.0, w * t, h),
+                    Direction::Right => (0.0, 0.0, w * t, h),
+                    Direction::Up => (0.0, h * (1.0 - t), w, h * t),
+                    Direction::Down => (0.0, 0.0, w, h * t),
+                };
+                draw_screen(renderer, to, region, region, 1.0);
+            },
+            TransitionEffect::Slide(direction) => {
+                let [dx, dy] = direction.vector();
+                let from_dst = (dx * w * t, dy * h * t, w, h);
+                let to_dst = (-dx * w * (1.0 - t), -dy * h * (1.0 - t), w, h);
+                draw_screen(renderer, from, full, from_dst, 1.0);
+                draw_screen(renderer, to, full, to_dst, 1.0);
+            },
+            TransitionEffect::Iris => {
+                draw_screen(renderer, from, full, full, 1.0);
+                if renderer.texture_size(to).is_none() {
+                    return;
+                }
+                let radius = (w * w + h * h).sqrt() * 0.5 * t;
+                let circle = ClipMask::Circle { x: w * 0.5, y: h * 0.5, radius }.triangles().unwrap_or_default();
+                let vertices: Vec<MeshVertex> = circle.iter()
+                    .map(|&[x, y]| MeshVertex::new([x, y], [1.0, 1.0, 1.0, 1.0], [x / w, y / h]))
+                    .collect();
+                let indices: Vec<u32> = (0..vertices.len() as u32).collect();
+                renderer.draw_mesh(&vertices, &indices, Some(to));
+            },
+        }
+    }
+}
+
+/// Draws the `src` region of a screen texture at `dst`. `src` is in screen pixels and
+/// is scaled to the texture, so a screen captured before the window was resized
+/// still covers the whole screen.
+fn draw_screen(renderer: &mut dyn Renderer, texture: &str, src: Rect, dst: Rect, alpha: f32) {
+    let Some((tw, th)) = renderer.texture_size(texture) else {
+        return;
+    };
+    let (width, height) = renderer.size();
+    let (sx, sy) = (tw as f32 / width.max(1) as f32, th as f32 / height.max(1) as f32);
+    let src = (src.0 * sx, src.1 * sy, src.2 * sx, src.3 * sy);
+    renderer.draw_sprite(WgpuSprite::deferred(src, dst, [1.0, 1.0, 1.0, alpha], texture, (tw, th)));
+}
@@ -1,9 +1,59 @@
+use crate::game::screen_transition::ScreenTransition;
+
+/// A change to the game's state stack, returned from `State::update`.
+pub enum Transition<T> {
+    /// Keep the stack as it is.
+    None,
+    /// Pause the top state and put a new one above it, e.g. a pause menu.
+    Push(Box<dyn State<T>>),
+    /// Remove the state that returned it, resuming the one below if it was on top.
+    /// The game exits when the stack becomes empty.
+    Pop,
+    /// Swap the state that returned it for a new one.
+    Replace(Box<dyn State<T>>),
+    /// Remove every state and start over with a new one, e.g. back to the title screen.
+    ClearTo(Box<dyn State<T>>),
+    /// Make a change while animating from the old screen to the new one. Input is
+    /// blocked until the animation finishes.
+    Animated(Box<Transition<T>>, ScreenTransition),
+}
+
+impl<T> Transition<T> {
+    /// Animates this change with `effect`, e.g.
+    /// `Transition::Replace(next).animated(ScreenTransition::new(TransitionEffect::Crossfade, 0.5))`.
+    pub fn animated(self, effect: ScreenTransition) -> Transition<T> {
+        Transition::Animated(Box::new(self), effect)
+    }
+}
+
+/// Converts an optional next state, where `Some` replaces the current state.
+impl<T> From<Option<Box<dyn State<T>>>> for Transition<T> {
+    fn from(next: Option<Box<dyn State<T>>>) -> Transition<T> {
+        match next {
+            Some(state) => Transition::Replace(state),
+            None => Transition::None,
+        }
+    }
+}
+
 pub trait State<T> {
     // Update game logic.
-    fn update(&mut self, context: &mut T, dt: f32) -> Option<Box<dyn State<T>>>;
+    fn update(&mut self, context: &mut T, dt: f32) -> Transition<T>;
+
+    // Update the simulation by one fixed step. Only called when the game runs with a
+    // fixed timestep, in addition to `update` once per frame.
+    fn fixed_update(&mut self, _context: &mut T, _dt: f32) -> Transition<T> {
+        Transition::None
+    }
 
     // Render the game entities.
-    fn render(&mut self, context: &mut T, renderer: &mut crate::graphics::wgpu_renderer::WgpuRenderer);
+    fn render(&mut self, context: &mut T, renderer: &mut dyn crate::graphics::renderer::Renderer);
+
+    // Render with `alpha`, the fraction of a fixed step elapsed since the last
+    // `fixed_update`, for blending previous and current positions.
+    fn render_interpolated(&mut self, context: &mut T, renderer: &mut dyn crate::graphics::renderer::Renderer, _alpha: f32) {
+        self.render(context, renderer);
+    }
 
     // Perform any initialization here.
     fn on_enter(&mut self, context: &mut T);
@@ -11,6 +61,38 @@ pub trait State<T> {
     // Perform any cleanup before transitioning to the next state.
     fn on_exit(&mut self, context: &mut T);
 
+    // Called when another state is pushed on top of this one.
+    fn on_pause(&mut self, _context: &mut T) {}
+
+    // Called when the state above this one is popped.
+    fn on_resume(&mut self, _context: &mut T) {}
+
+    // Called when the window gains or loses keyboard focus.
+    fn on_focus_changed(&mut self, _context: &mut T, _focused: bool) {}
+
+    // Called when the window is minimized or hidden behind other windows, and when it
+    // becomes visible again.
+    fn on_visibility_changed(&mut self, _context: &mut T, _visible: bool) {}
+
+    // Called when the application is suspended, e.g. sent to the background on mobile.
+    fn on_app_suspended(&mut self, _context: &mut T) {}
+
+    // Called when the application returns from being suspended.
+    fn on_app_resumed(&mut self, _context: &mut T) {}
+
+    // Called when the window moves to a display with a different DPI scale.
+    fn on_scale_factor_changed(&mut self, _context: &mut T, _scale_factor: f64) {}
+
+    // Whether the states below this one are still rendered, e.g. for an overlay menu.
+    fn render_below(&self) -> bool {
+        false
+    }
+
+    // Whether the states below this one keep updating while it is on top.
+    fn update_below(&self) -> bool {
+        false
+    }
+
     // Get the state name.
     fn get_name(&mut self) -> String;
 }
@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+
+/// How one group of objects experiences time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TimeGroup {
+    scale: f32,
+    /// Whether the group ignores the global scale, pause and hit-stop.
+    independent: bool,
+}
+
+/// Global time scale, slow motion, hit-stop and pause.
+///
+/// The game advances it once per frame and passes the scaled `dt` to
+/// `State::update`, fixed updates and the scheduler, so animations ticked with
+/// that `dt` slow down too. While paused or frozen by a hit-stop the scaled `dt`
+/// is zero, but states are still updated and can read input. Screen transitions
+/// run on unscaled time.
+///
+/// Groups scale time for parts of the game, e.g. enemies slowed by a spell, and
+/// independent groups keep running at their own scale during slow motion and
+/// pauses, e.g. UI. Query them with `group_dt`.
+///
+/// # Examples
+///
+/// ```
+/// use barn::game::time::TimeController;
+///
+/// let mut time = TimeController::new();
+/// time.set_time_scale(0.5);
+/// time.set_group_scale("enemies", 0.5);
+/// time.set_group_independent("ui", true);
+/// assert_eq!(time.advance(0.1), 0.05);
+/// assert_eq!(time.group_dt("enemies"), 0.025);
+/// assert_eq!(time.group_dt("ui"), 0.1);
+///
+/// // A hit-stop freezes gameplay for two frames but not the UI.
+/// time.hit_stop(2);
+/// assert_eq!(time.advance(0.1), 0.0);
+/// assert_eq!(time.group_dt("ui"), 0.1);
+/// assert_eq!(time.advance(0.1), 0.0);
+/// assert_eq!(time.advance(0.1), 0.05);
+/// assert!((time.elapsed() - 0.1).abs() < 1e-6);
+/// assert!((time.unscaled_elapsed() - 0.4).abs() < 1e-6);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeController {
+    time_scale: f32,
+    groups: HashMap<String, TimeGroup>,
+    paused: bool,
+    /// Frames left in the current hit-stop.
+    frozen_frames: u32,
+    dt: f32,
+    unscaled_dt: f32,
+    elapsed: f64,
+    unscaled_elapsed: f64,
+    frame: u64,
+    /// Whether the audio speed last handed out by `next_audio_speed` was not 1.0.
+    audio_speed_changed: bool,
+    /// Whether audio playback speed, and with it pitch, follows the global time scale.
+    pub audio_follows_time_scale: bool,
+}
+
+impl Default for TimeController {
+    fn default() -> Self {
+        TimeController::new()
+    }
+}
+
+impl TimeController {
+    pub fn new() -> TimeController {
+        TimeController {
+            time_scale: 1.0,
+            groups: HashMap::new(),
+            paused: false,
+            frozen_frames: 0,
+            dt: 0.0,
+            unscaled_dt: 0.0,
+            elapsed: 0.0,
+            unscaled_elapsed: 0.0,
+            frame: 0,
+            audio_speed_changed: false,
+            audio_follows_time_scale: true,
+        }
+    }
+
+    /// Sets the global time scale, e.g. 0.25 for slow motion. Negative scales count as zero.
+    pub fn set_time_scale(&mut self, scale: f32) {
+        self.time_scale = scale.max(0.0);
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Scales the time of `group`. Dependent groups are scaled on top of the global scale.
+    pub fn set_group_scale(&mut self, group: &str, scale: f32) {
+        self.group_mut(group).scale = scale.max(0.0);
+    }
+
+    /// Lets `group` ignore the global time scale, pause and hit-stop.
+    pub fn set_group_independent(&mut self, group: &str, independent: bool) {
+        self.group_mut(group).independent = independent;
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Freezes scaled time for the next `frames` frames, e.g. to sell a heavy hit.
+    /// A longer hit-stop already running is kept.
+    pub fn hit_stop(&mut self, frames: u32) {
+        self.frozen_frames = self.frozen_frames.max(frames);
+    }
+
+    /// Whether scaled time is stopped by a pause or hit-stop.
+    pub fn is_frozen(&self) -> bool {
+        self.paused || self.frozen_frames > 0
+    }
+
+    /// Starts a frame of `unscaled_dt` real seconds and returns its scaled `dt`.
+    /// Called by the game once per frame.
+    pub fn advance(&mut self, unscaled_dt: f32) -> f32 {
+        self.frame += 1;
+        self.unscaled_dt = unscaled_dt;
+        self.unscaled_elapsed += unscaled_dt as f64;
+        self.dt = if self.is_frozen() { 0.0 } else { unscaled_dt * self.time_scale };
+        // A pause doesn't use up the hit-stop
+        if !self.paused {
+            self.frozen_frames = self.frozen_frames.saturating_sub(1);
+        }
+        self.elapsed += self.dt as f64;
+        self.dt
+    }
+
+    /// The scaled duration of the current frame.
+    pub fn dt(&self) -> f32 {
+        self.dt
+    }
+
+    /// The real duration of the current frame.
+    pub fn unscaled_dt(&self) -> f32 {
+        self.unscaled_dt
+    }
+
+    /// The current frame's duration for `group`. Unknown groups use the global `dt`.
+    pub fn group_dt(&self, group: &str) -> f32 {
+        match self.groups.get(group) {
+            Some(TimeGroup { scale, independent: true }) => self.unscaled_dt * scale,
+            Some(TimeGroup { scale, independent: false }) => self.dt * scale,
+            None => self.dt,
+        }
+    }
+
+    /// Scaled seconds since the game started.
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// Real seconds since the game started, excluding time the game spent paused
+    /// by losing focus.
+    pub fn unscaled_elapsed(&self) -> f64 {
+        self.unscaled_elapsed
+    }
+
+    /// Frames started since the game started.
+    pub fn frame_count(&self) -> u64 {
+        self.frame
+    }
+
+    /// The audio playback speed matching the time scale, if audio follows it.
+    /// Pauses and hit-stops leave audio alone.
+    pub fn audio_speed(&self) -> Option<f32> {
+        // Audio can't play at zero speed, so a stopped clock plays it very slowly
+        self.audio_follows_time_scale.then(|| self.time_scale.max(0.05))
+    }
+
+    /// The audio speed for the game to apply this frame: `audio_speed` while audio
+    /// follows the time scale, then 1.0 once after it stops following, so audio
+    /// doesn't stay slowed down.
+    pub fn next_audio_speed(&mut self) -> Option<f32> {
+        match self.audio_speed() {
+            Some(speed) => {
+                self.audio_speed_changed = speed != 1.0;
+                Some(speed)
+            },
+            None => std::mem::take(&mut self.audio_speed_changed).then_some(1.0),
+        }
+    }
+
+    fn group_mut(&mut self, group: &str) -> &mut TimeGroup {
+        self.groups.entry(group.to_string()).or_insert(TimeGroup { scale: 1.0, independent: false })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hit_stop_freezes_dependent_groups_only() {
+        let mut time = TimeController::new();
+        time.set_time_scale(0.5);
+        time.set_group_scale("enemies", 2.0);
+        time.set_group_scale("ui", 0.5);
+        time.set_group_independent("ui", true);
+        time.hit_stop(1);
+        assert_eq!(time.advance(0.1), 0.0);
+        assert_eq!(time.group_dt("enemies"), 0.0);
+        assert_eq!(time.group_dt("ui"), 0.05);
+        assert_eq!(time.advance(0.1), 0.05);
+        assert_eq!(time.group_dt("enemies"), 0.1);
+        assert_eq!(time.group_dt("ui"), 0.05);
+    }
+
+    #[test]
+    fn pausing_does_not_use_up_a_hit_stop() {
+        let mut time = TimeController::new();
+        time.hit_stop(1);
+        time.set_paused(true);
+        time.advance(0.1);
+        time.advance(0.1);
+        assert!(time.is_frozen());
+        time.set_paused(false);
+        assert!(time.is_frozen());
+        assert_eq!(time.advance(0.1), 0.0);
+        assert!(!time.is_frozen());
+        assert_eq!(time.advance(0.1), 0.1);
+    }
+
+    #[test]
+    fn shorter_hit_stops_do_not_cut_a_longer_one() {
+        let mut time = TimeController::new();
+        time.hit_stop(3);
+        time.advance(0.1);
+        time.hit_stop(1);
+        time.advance(0.1);
+        time.advance(0.1);
+        assert!(!time.is_frozen());
+        assert_eq!(time.advance(0.1), 0.1);
+    }
+
+    #[test]
+    fn pause_freezes_time_but_independent_groups_run() {
+        let mut time = TimeController::new();
+        time.set_group_independent("ui", true);
+        time.set_paused(true);
+        assert_eq!(time.advance(0.25), 0.0);
+        assert_eq!(time.group_dt("ui"), 0.25);
+        assert_eq!(time.unscaled_dt(), 0.25);
+        assert_eq!(time.elapsed(), 0.0);
+        assert_eq!(time.unscaled_elapsed(), 0.25);
+        assert_eq!(time.frame_count(), 1);
+    }
+
+    #[test]
+    fn negative_scales_count_as_zero() {
+        let mut time = TimeController::new();
+        time.set_time_scale(-1.0);
+        time.set_group_scale("enemies", -3.0);
+        assert_eq!(time.time_scale(), 0.0);
+        assert_eq!(time.advance(0.1), 0.0);
+        time.set_time_scale(1.0);
+        time.advance(0.1);
+        assert_eq!(time.group_dt("enemies"), 0.0);
+    }
+
+    #[test]
+    fn unknown_groups_use_the_global_dt() {
+        let mut time = TimeController::new();
+        time.set_time_scale(0.5);
+        time.advance(0.2);
+        assert_eq!(time.group_dt("anything"), 0.1);
+        assert_eq!(time.dt(), 0.1);
+    }
+
+    #[test]
+    fn audio_speed_follows_the_scale_with_a_floor() {
+        let mut time = TimeController::new();
+        time.set_time_scale(0.5);
+        assert_eq!(time.audio_speed(), Some(0.5));
+        time.set_time_scale(0.0);
+        assert_eq!(time.audio_speed(), Some(0.05));
+        // Hit-stops and pauses leave audio alone
+        time.set_time_scale(1.0);
+        time.hit_stop(5);
+        time.set_paused(true);
+        assert_eq!(time.audio_speed(), Some(1.0));
+        time.audio_follows_time_scale = false;
+        assert_eq!(time.audio_speed(), None);
+    }
+
+    #[test]
+    fn audio_speed_is_reset_once_when_it_stops_following() {
+        let mut time = TimeController::new();
+        time.set_time_scale(0.25);
+        assert_eq!(time.next_audio_speed(), Some(0.25));
+        time.audio_follows_time_scale = false;
+        assert_eq!(time.next_audio_speed(), Some(1.0));
+        assert_eq!(time.next_audio_speed(), None);
+        // Audio that was never slowed down is left alone
+        time.set_time_scale(1.0);
+        time.audio_follows_time_scale = true;
+        assert_eq!(time.next_audio_speed(), Some(1.0));
+        time.audio_follows_time_scale = false;
+        assert_eq!(time.next_audio_speed(), None);
+    }
+}
@@ -0,0 +1,142 @@
+/// Accumulates frame time and splits it into fixed simulation steps.
+///
+/// Frame times longer than `max_steps` steps are clamped, so a stall (window drag,
+/// breakpoint, slow frame) does not trigger a spiral of catch-up updates.
+///
+/// # Examples
+///
+/// ```
+/// use barn::game::timestep::FixedTimestep;
+///
+/// let mut timestep = FixedTimestep::new(50.0);
+/// assert_eq!(timestep.advance(0.05), 2);
+/// assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+/// // A one second hitch only runs the maximum number of steps.
+/// assert_eq!(timestep.advance(1.0), 5);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FixedTimestep {
+    step: f32,
+    max_steps: u32,
+    accumulator: f32,
+}
+
+impl FixedTimestep {
+    /// Creates a timestep running `ticks_per_second` fixed updates per second.
+    pub fn new(ticks_per_second: f32) -> FixedTimestep {
+        FixedTimestep {
+            step: 1.0 / ticks_per_second.max(f32::EPSILON),
+            max_steps: 5,
+            accumulator: 0.0,
+        }
+    }
+
+    /// Sets how many steps a single frame may run before the remaining time is dropped.
+    pub fn with_max_steps(mut self, max_steps: u32) -> FixedTimestep {
+        self.max_steps = max_steps.max(1);
+        self
+    }
+
+    /// Length of one fixed step in seconds.
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    pub fn max_steps(&self) -> u32 {
+        self.max_steps
+    }
+
+    /// Adds `dt` seconds of frame time and returns how many fixed steps to run.
+    pub fn advance(&mut self, dt: f32) -> u32 {
+        // A small tolerance keeps float error from turning 0.05 / 0.025 into one step.
+        self.accumulator += dt.max(0.0);
+        let steps = ((self.accumulator + self.step * 1e-4) / self.step).floor() as u32;
+        if steps > self.max_steps {
+            self.accumulator = 0.0;
+            return self.max_steps;
+        }
+        self.accumulator = (self.accumulator - steps as f32 * self.step).max(0.0);
+        steps
+    }
+
+    /// How far the simulation is between the last fixed step and the next one, from
+    /// 0.0 to 1.0. Rendering blends previous and current positions by this amount.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step).clamp(0.0, 1.0)
+    }
+
+    /// Drops any accumulated time, e.g. after loading or resuming from a pause.
+    pub fn reset(&mut self) {
+        self.accumulator = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_frames_accumulate_into_steps() {
+        let mut timestep = FixedTimestep::new(60.0);
+        assert_eq!(timestep.advance(0.01), 0);
+        assert!((timestep.alpha() - 0.6).abs() < 1e-4);
+        assert_eq!(timestep.advance(0.01), 1);
+        assert!((timestep.alpha() - 0.2).abs() < 1e-3);
+    }
+
+    #[test]
+    fn float_error_does_not_lose_a_step() {
+        let mut timestep = FixedTimestep::new(40.0);
+        assert_eq!(timestep.advance(0.05), 2);
+        let mut timestep = FixedTimestep::new(60.0);
+        let steps: u32 = (0..600).map(|_| timestep.advance(1.0 / 60.0)).sum();
+        assert_eq!(steps, 600);
+    }
+
+    #[test]
+    fn long_frames_are_clamped_to_max_steps() {
+        let mut timestep = FixedTimestep::new(10.0).with_max_steps(3);
+        assert_eq!(timestep.advance(0.45), 3);
+        // The rest of the stall is dropped rather than carried into the next frame
+        assert_eq!(timestep.alpha(), 0.0);
+        assert_eq!(timestep.advance(0.05), 0);
+    }
+
+    #[test]
+    fn running_exactly_max_steps_keeps_the_remainder() {
+        let mut timestep = FixedTimestep::new(10.0).with_max_steps(3);
+        assert_eq!(timestep.advance(0.35), 3);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn max_steps_is_at_least_one() {
+        let mut timestep = FixedTimestep::new(10.0).with_max_steps(0);
+        assert_eq!(timestep.max_steps(), 1);
+        assert_eq!(timestep.advance(1.0), 1);
+    }
+
+    #[test]
+    fn negative_frame_times_are_ignored() {
+        let mut timestep = FixedTimestep::new(10.0);
+        timestep.advance(0.05);
+        assert_eq!(timestep.advance(-1.0), 0);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn reset_drops_accumulated_time() {
+        let mut timestep = FixedTimestep::new(10.0);
+        timestep.advance(0.09);
+        timestep.reset();
+        assert_eq!(timestep.alpha(), 0.0);
+        assert_eq!(timestep.advance(0.02), 0);
+    }
+
+    #[test]
+    fn non_positive_rates_still_have_a_finite_step() {
+        let mut timestep = FixedTimestep::new(0.0);
+        assert!(timestep.step().is_finite());
+        assert_eq!(timestep.advance(1.0), 0);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::game::game_config::WindowMode;
+
+/// How the cursor is kept inside the window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CursorGrab {
+    None,
+    /// The cursor can move but not leave the window.
+    Confined,
+    /// The cursor stays in place, e.g. for mouse look. Platforms that support only
+    /// one of the two grab modes get the other one instead.
+    Locked,
+}
+
+/// How insistently `WindowCommand::RequestAttention` asks for the user's attention.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Attention {
+    /// Bounces the dock icon or flashes the taskbar entry until the window is focused.
+    Critical,
+    /// Flashes once or bounces briefly.
+    Informational,
+}
+
+/// A change to the game window, queued by a state and applied by `Game::run`
+/// after the frame's update.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WindowCommand {
+    /// Exits every state and closes the game.
+    Quit,
+    SetWindowMode(WindowMode),
+    SetTitle(String),
+    /// Resizes the window's inner area, in logical pixels.
+    SetSize(u32, u32),
+    SetCursorGrab(CursorGrab),
+    SetCursorVisible(bool),
+    RequestAttention(Attention),
+}
+
+/// A queue of window commands, kept by a context for `Context::take_window_commands`.
+///
+/// # Examples
+///
+/// ```
+/// use barn::game::window_command::{WindowCommand, WindowCommands};
+///
+/// let mut window = WindowCommands::new();
+/// window.set_title("Paused");
+/// window.quit();
+/// assert_eq!(window.take(), vec![WindowCommand::SetTitle("Paused".to_string()), WindowCommand::Quit]);
+/// assert!(window.take().is_empty());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WindowCommands {
+    commands: Vec<WindowCommand>,
+}
+
+impl WindowCommands {
+    pub fn new() -> WindowCommands {
+        WindowCommands { commands: Vec::new() }
+    }
+
+    pub fn push(&mut self, command: WindowCommand) {
+        self.commands.push(command);
+    }
+
+    pub fn quit(&mut self) {
+        self.push(WindowCommand::Quit);
+    }
+
+    pub fn set_window_mode(&mut self, mode: WindowMode) {
+        self.push(WindowCommand::SetWindowMode(mode));
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        self.push(WindowCommand::SetTitle(title.to_string()));
+    }
+
+    pub fn set_size(&mut self, width: u32, height: u32) {
+        self.push(WindowCommand::SetSize(width, height));
+    }
+
+    pub fn set_cursor_grab(&mut self, grab: CursorGrab) {
+        self.push(WindowCommand::SetCursorGrab(grab));
+    }
+
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.push(WindowCommand::SetCursorVisible(visible));
+    }
+
+    pub fn request_attention(&mut self, attention: Attention) {
+        self.push(WindowCommand::RequestAttention(attention));
+    }
+
+    /// Removes and returns the queued commands in the order they were pushed.
+    pub fn take(&mut self) -> Vec<WindowCommand> {
+        std::mem::take(&mut self.commands)
+    }
+}
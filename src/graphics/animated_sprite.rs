@@ -0,0 +1,577 @@
+use std::collections::HashMap;
+
+use crate::graphics::sprite_sheet::{SpriteSheet, TagDirection};
+use crate::graphics::wgpu_sprite::{WgpuSprite, WgpuSpriteFrame};
+use crate::graphics::Rect;
+
+/// How a clip continues after its last frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlayMode {
+    /// Stop on the last frame and report the clip as finished.
+    Once,
+    Loop,
+    /// Play forwards, then backwards, forever.
+    PingPong,
+}
+
+/// A named sequence of frames played by an `AnimatedSprite`.
+#[derive(Clone)]
+pub struct AnimationClip {
+    pub frames: Vec<WgpuSpriteFrame>,
+    pub mode: PlayMode,
+    /// Playback rate multiplier, e.g. 2.0 plays twice as fast.
+    pub speed: f32,
+    /// Named events fired when a frame is entered, keyed by frame index.
+    events: Vec<(usize, String)>,
+}
+
+impl AnimationClip {
+    pub fn new(frames: Vec<WgpuSpriteFrame>, mode: PlayMode) -> AnimationClip {
+        AnimationClip { frames, mode, speed: 1.0, events: Vec::new() }
+    }
+
+    /// Builds a clip from a sprite sheet tag. The tag direction picks the frame order
+    /// and mode; tags that repeat a fixed number of times play once. Frame
+    /// destinations are relative to the sprite position, with the untrimmed frame
+    /// scaled to `size`.
+    pub fn from_sheet(sheet: &SpriteSheet, tag: &str, size: (f32, f32)) -> Option<AnimationClip> {
+        let tag = sheet.tag(tag)?;
+        let dst = (0.0, 0.0, size.0, size.1);
+        let (indices, mode): (Vec<usize>, PlayMode) = match tag.direction {
+            TagDirection::PingPong => ((tag.from..=tag.to).collect(), PlayMode::PingPong),
+            TagDirection::PingPongReverse => ((tag.from..=tag.to).rev().collect(), PlayMode::PingPong),
+            _ if tag.repeat.is_some() => (sheet.tag_frames(tag), PlayMode::Once),
+            _ => (sheet.tag_frames(tag), PlayMode::Loop),
+        };
+        let frames = indices.into_iter().map(|i| sheet.sprite_frame(i, dst)).collect();
+        Some(AnimationClip::new(frames, mode))
+    }
+
+    pub fn with_mode(mut self, mode: PlayMode) -> AnimationClip {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> AnimationClip {
+        self.speed = speed;
+        self
+    }
+
+    /// Fires `name` whenever `frame` is entered, e.g. a "footstep" on contact frames.
+    pub fn with_event(mut self, frame: usize, name: &str) -> AnimationClip {
+        self.events.push((frame, name.to_string()));
+        self
+    }
+}
+
+/// A frame event reported by `AnimatedSprite::update`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationEvent {
+    pub clip: String,
+    pub frame: usize,
+    pub name: String,
+}
+
+/// A condition on the sprite's parameters that allows a transition.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    Always,
+    /// The current clip has finished playing (only `PlayMode::Once` clips finish).
+    Finished,
+    Bool(String, bool),
+    /// Holds when the parameter is set and above the threshold; unset parameters never hold.
+    FloatAbove(String, f32),
+    /// Holds when the parameter is set and below the threshold; unset parameters never hold.
+    FloatBelow(String, f32),
+    /// A trigger set with `AnimatedSprite::trigger`, consumed by the transition.
+    Trigger(String),
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+}
+
+/// An edge of the animation graph.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationTransition {
+    /// Clip the transition leaves from; `None` applies to every clip.
+    pub from: Option<String>,
+    pub to: String,
+    pub condition: Condition,
+}
+
+/// A sprite with named animation clips and a graph of conditional transitions.
+///
+/// Game states drive the graph by setting parameters, and the sprite switches clips
+/// on its own during `update`.
+///
+/// # Examples
+///
+/// ```
+/// use barn::graphics::animated_sprite::{AnimatedSprite, AnimationClip, Condition, PlayMode};
+/// use barn::graphics::wgpu_sprite::WgpuSpriteFrame;
+///
+/// let frame = |x: f32| WgpuSpriteFrame { src: (x, 0.0, 9.0, 15.0), dst: (0.0, 0.0, 18.0, 30.0), duration: 0.1 };
+/// let mut sprite = AnimatedSprite::new("hero.png", (36, 15));
+/// sprite.add_clip("idle", AnimationClip::new(vec![frame(0.0)], PlayMode::Loop));
+/// sprite.add_clip("run", AnimationClip::new(vec![frame(9.0), frame(18.0)], PlayMode::Loop).with_event(1, "footstep"));
+/// sprite.add_transition(Some("idle"), "run", Condition::FloatAbove("speed".to_string(), 0.1));
+/// sprite.add_transition(Some("run"), "idle", Condition::FloatBelow("speed".to_string(), 0.1));
+/// sprite.play("idle");
+///
+/// sprite.set_float("speed", 3.0);
+/// sprite.update(0.0);
+/// assert_eq!(sprite.current_clip(), Some("run"));
+/// let events = sprite.update(0.1);
+/// assert_eq!(events[0].name, "footstep");
+/// ```
+pub struct AnimatedSprite {
+    pub texture_path: String,
+    pub texture_size: (u32, u32),
+    /// Offset added to every frame destination.
+    pub position: (f32, f32),
+    pub color: [f32; 4],
+    /// Playback rate multiplier applied on top of each clip's speed.
+    pub speed: f32,
+    clips: HashMap<String, AnimationClip>,
+    transitions: Vec<AnimationTransition>,
+    bools: HashMap<String, bool>,
+    floats: HashMap<String, f32>,
+    triggers: Vec<String>,
+    current: Option<String>,
+    frame: usize,
+    frame_time: f32,
+    forward: bool,
+    finished: bool,
+    paused: bool,
+    entered: bool,
+}
+
+impl AnimatedSprite {
+    pub fn new(texture_path: &str, texture_size: (u32, u32)) -> AnimatedSprite {
+        AnimatedSprite {
+            texture_path: texture_path.to_string(),
+            texture_size,
+            position: (0.0, 0.0),
+            color: [1.0, 1.0, 1.0, 1.0],
+            speed: 1.0,
+            clips: HashMap::new(),
+            transitions: Vec::new(),
+            bools: HashMap::new(),
+            floats: HashMap::new(),
+            triggers: Vec::new(),
+            current: None,
+            frame: 0,
+            frame_time: 0.0,
+            forward: true,
+            finished: false,
+            paused: false,
+            entered: false,
+        }
+    }
+
+    /// Creates a sprite with a clip for every tag of a sprite sheet.
+    pub fn from_sheet(sheet: &SpriteSheet, size: (f32, f32)) -> AnimatedSprite {
+        let mut sprite = AnimatedSprite::new(&sheet.image, sheet.size);
+        for tag in sheet.tags.iter() {
+            if let Some(clip) = AnimationClip::from_sheet(sheet, &tag.name, size) {
+                sprite.add_clip(&tag.name, clip);
+            }
+        }
+        sprite
+    }
+
+    pub fn add_clip(&mut self, name: &str, clip: AnimationClip) {
+        self.clips.insert(name.to_string(), clip);
+    }
+
+    pub fn clip_mut(&mut self, name: &str) -> Option<&mut AnimationClip> {
+        self.clips.get_mut(name)
+    }
+
+    /// Adds a transition. Transitions are checked in the order they were added, and
+    /// transitions to clips that have not been added are skipped.
+    pub fn add_transition(&mut self, from: Option<&str>, to: &str, condition: Condition) {
+        self.transitions.push(AnimationTransition {
+            from: from.map(|name| name.to_string()),
+            to: to.to_string(),
+            condition,
+        });
+    }
+
+    /// Switches to a clip and restarts it. Playing the current clip again does nothing.
+    pub fn play(&mut self, name: &str) {
+        if self.current.as_deref() == Some(name) || !self.clips.contains_key(name) {
+            return;
+        }
+        self.current = Some(name.to_string());
+        self.restart();
+    }
+
+    /// Restarts the current clip from its first frame.
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.frame_time = 0.0;
+        self.forward = true;
+        self.finished = false;
+        self.entered = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn current_clip(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    /// Index of the current frame within the current clip.
+    pub fn frame_index(&self) -> usize {
+        self.frame
+    }
+
+    /// Returns true once a `PlayMode::Once` clip has shown its last frame for its full duration.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.bools.insert(name.to_string(), value);
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.floats.insert(name.to_string(), value);
+    }
+
+    /// Sets a one-shot parameter that stays set until a transition consumes it.
+    pub fn trigger(&mut self, name: &str) {
+        if !self.triggers.iter().any(|t| t == name) {
+            self.triggers.push(name.to_string());
+        }
+    }
+
+    pub fn reset_trigger(&mut self, name: &str) {
+        self.triggers.retain(|t| t != name);
+    }
+
+    /// Follows any transition whose condition holds, then advances the current clip.
+    /// Returns the frame events fired during this update in order.
+    pub fn update(&mut self, dt: f32) -> Vec<AnimationEvent> {
+        let mut events = Vec::new();
+        self.follow_transitions(&mut events);
+
+        let name = match self.current.clone() {
+            Some(name) => name,
+            None => return events,
+        };
+        if self.entered {
+            self.entered = false;
+            self.fire_events(&name, &mut events);
+        }
+        let clip = &self.clips[&name];
+        let count = clip.frames.len();
+        let total: f32 = clip.frames.iter().map(|f| f.duration).sum();
+        if self.paused || self.finished || count == 0 || total <= 0.0 {
+            return events;
+        }
+
+        self.frame_time += dt * (clip.speed * self.speed).max(0.0);
+        while self.frame_time >= self.clips[&name].frames[self.frame].duration {
+            self.frame_time -= self.clips[&name].frames[self.frame].duration;
+            match self.clips[&name].mode {
+                PlayMode::Loop => self.frame = (self.frame + 1) % count,
+                PlayMode::Once if self.frame + 1 < count => self.frame += 1,
+                PlayMode::Once => {
+                    self.finished = true;
+                    self.frame_time = 0.0;
+                    break;
+                },
+                PlayMode::PingPong if count == 1 => {},
+                PlayMode::PingPong => {
+                    if (self.forward && self.frame + 1 == count) || (!self.forward && self.frame == 0) {
+                        self.forward = !self.forward;
+                    }
+                    self.frame = if self.forward { self.frame + 1 } else { self.frame - 1 };
+                },
+            }
+            self.fire_events(&name, &mut events);
+        }
+
+        // A finished clip may unlock a transition straight away.
+        if self.finished {
+            self.follow_transitions(&mut events);
+        }
+        events
+    }
+
+    /// Returns the current frame with its destination offset by `position`.
+    pub fn current_frame(&self) -> Option<WgpuSpriteFrame> {
+        let clip = self.clips.get(self.current.as_ref()?)?;
+        let mut frame = *clip.frames.get(self.frame)?;
+        frame.dst.0 += self.position.0;
+        frame.dst.1 += self.position.1;
+        Some(frame)
+    }
+
+    /// Returns a sprite for the current frame, ready for `WgpuRenderer::draw_sprite`.
+    pub fn sprite(&self) -> Option<WgpuSprite> {
+        let frame = self.current_frame()?;
+        Some(WgpuSprite::deferred(frame.src, frame.dst, self.color, &self.texture_path, self.texture_size))
+    }
+
+    /// Destination rectangle of the current frame, useful for collision boxes.
+    pub fn bounds(&self) -> Option<Rect> {
+        self.current_frame().map(|frame| frame.dst)
+    }
+
+    fn follow_transitions(&mut self, events: &mut Vec<AnimationEvent>) {
+        let current = match self.current.clone() {
+            Some(current) => current,
+            None => return,
+        };
+        let next = self.transitions.iter()
+            .filter(|t| t.to != current && t.from.as_ref().is_none_or(|from| *from == current))
+            .filter(|t| self.clips.contains_key(&t.to))
+            .find(|t| self.holds(&t.condition))
+            .cloned();
+        if let Some(transition) = next {
+            self.consume_triggers(&transition.condition);
+            self.play(&transition.to);
+            // Fire the first frame's events now, since the clip may not advance this update.
+            if self.entered {
+                self.entered = false;
+                self.fire_events(&transition.to, events);
+            }
+        }
+    }
+
+    fn holds(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::Always => true,
+            Condition::Finished => self.finished,
+            Condition::Bool(name, value) => self.bools.get(name).copied().unwrap_or(false) == *value,
+            Condition::FloatAbove(name, threshold) => self.floats.get(name).is_some_and(|v| v > threshold),
+            Condition::FloatBelow(name, threshold) => self.floats.get(name).is_some_and(|v| v < threshold),
+            Condition::Trigger(name) => self.triggers.iter().any(|t| t == name),
+            Condition::All(conditions) => conditions.iter().all(|c| self.holds(c)),
+            Condition::Any(conditions) => conditions.iter().any(|c| self.holds(c)),
+        }
+    }
+
+    fn consume_triggers(&mut self, condition: &Condition) {
+        let mut used = Vec::new();
+        self.held_triggers(condition, &mut used);
+        for name in used.iter() {
+            self.reset_trigger(name);
+        }
+    }
+
+    /// Collects the triggers that contributed to `condition` holding.
+    fn held_triggers(&self, condition: &Condition, used: &mut Vec<String>) {
+        match condition {
+            Condition::Trigger(name) => used.push(name.clone()),
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                for c in conditions.iter().filter(|c| self.holds(c)) {
+                    self.held_triggers(c, used);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn fire_events(&self, clip: &str, events: &mut Vec<AnimationEvent>) {
+        for (frame, name) in self.clips[clip].events.iter() {
+            if *frame == self.frame {
+                events.push(AnimationEvent { clip: clip.to_string(), frame: *frame, name: name.clone() });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(count: usize) -> Vec<WgpuSpriteFrame> {
+        (0..count)
+            .map(|i| WgpuSpriteFrame { src: (i as f32 * 8.0, 0.0, 8.0, 8.0), dst: (0.0, 0.0, 8.0, 8.0), duration: 1.0 })
+            .collect()
+    }
+
+    fn sprite(clips: &[(&str, usize, PlayMode)]) -> AnimatedSprite {
+        let mut sprite = AnimatedSprite::new("sheet.png", (64, 8));
+        for (name, count, mode) in clips.iter() {
+            sprite.add_clip(name, AnimationClip::new(frames(*count), *mode));
+        }
+        sprite.play(clips[0].0);
+        sprite
+    }
+
+    fn frame_sequence(sprite: &mut AnimatedSprite, updates: usize) -> Vec<usize> {
+        (0..updates)
+            .map(|_| {
+                sprite.update(1.0);
+                sprite.frame_index()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loop_wraps_to_first_frame() {
+        let mut sprite = sprite(&[("walk", 3, PlayMode::Loop)]);
+        assert_eq!(frame_sequence(&mut sprite, 4), vec![1, 2, 0, 1]);
+        assert!(!sprite.is_finished());
+    }
+
+    #[test]
+    fn ping_pong_reverses_at_both_ends() {
+        let mut sprite = sprite(&[("bob", 3, PlayMode::PingPong)]);
+        assert_eq!(frame_sequence(&mut sprite, 6), vec![1, 2, 1, 0, 1, 2]);
+        assert!(!sprite.is_finished());
+    }
+
+    #[test]
+    fn once_finishes_after_last_frame_duration() {
+        let mut sprite = sprite(&[("attack", 2, PlayMode::Once)]);
+        sprite.update(1.0);
+        assert_eq!(sprite.frame_index(), 1);
+        assert!(!sprite.is_finished());
+        sprite.update(0.5);
+        assert!(!sprite.is_finished());
+        sprite.update(0.5);
+        assert!(sprite.is_finished());
+        sprite.update(5.0);
+        assert_eq!(sprite.frame_index(), 1);
+    }
+
+    #[test]
+    fn finished_transition_follows_in_same_update() {
+        let mut sprite = sprite(&[("attack", 2, PlayMode::Once), ("idle", 1, PlayMode::Loop)]);
+        sprite.add_transition(Some("attack"), "idle", Condition::Finished);
+        sprite.update(1.0);
+        assert_eq!(sprite.current_clip(), Some("attack"));
+        sprite.update(1.0);
+        assert_eq!(sprite.current_clip(), Some("idle"));
+        assert!(!sprite.is_finished());
+        assert_eq!(sprite.frame_index(), 0);
+    }
+
+    #[test]
+    fn trigger_is_consumed_exactly_once() {
+        let mut sprite = sprite(&[("idle", 1, PlayMode::Loop), ("hit", 1, PlayMode::Loop)]);
+        sprite.add_transition(None, "hit", Condition::Trigger("hit".to_string()));
+        sprite.add_transition(Some("hit"), "idle", Condition::Bool("recovered".to_string(), true));
+
+        sprite.trigger("hit");
+        sprite.trigger("hit");
+        sprite.update(0.0);
+        assert_eq!(sprite.current_clip(), Some("hit"));
+
+        sprite.set_bool("recovered", true);
+        sprite.update(0.0);
+        assert_eq!(sprite.current_clip(), Some("idle"));
+        sprite.set_bool("recovered", false);
+        sprite.update(0.0);
+        assert_eq!(sprite.current_clip(), Some("idle"));
+    }
+
+    #[test]
+    fn reset_trigger_clears_pending_trigger() {
+        let mut sprite = sprite(&[("idle", 1, PlayMode::Loop), ("jump", 1, PlayMode::Loop)]);
+        sprite.add_transition(Some("idle"), "jump", Condition::Trigger("jump".to_string()));
+        sprite.trigger("jump");
+        sprite.reset_trigger("jump");
+        sprite.update(0.0);
+        assert_eq!(sprite.current_clip(), Some("idle"));
+    }
+
+    #[test]
+    fn transition_to_unknown_clip_keeps_trigger() {
+        let mut sprite = sprite(&[("idle", 1, PlayMode::Loop)]);
+        sprite.add_transition(Some("idle"), "jump", Condition::Trigger("jump".to_string()));
+        sprite.trigger("jump");
+        sprite.update(0.0);
+        assert_eq!(sprite.current_clip(), Some("idle"));
+
+        sprite.add_clip("jump", AnimationClip::new(frames(1), PlayMode::Loop));
+        sprite.update(0.0);
+        assert_eq!(sprite.current_clip(), Some("jump"));
+    }
+
+    #[test]
+    fn all_requires_every_condition() {
+        let mut sprite = sprite(&[("idle", 1, PlayMode::Loop), ("run", 1, PlayMode::Loop)]);
+        let condition = Condition::All(vec![
+            Condition::Bool("grounded".to_string(), true),
+            Condition::FloatAbove("speed".to_string(), 1.0),
+        ]);
+        sprite.add_transition(Some("idle"), "run", condition);
+
+        sprite.set_float("speed", 2.0);
+        sprite.update(0.0);
+        assert_eq!(sprite.current_clip(), Some("idle"));
+        sprite.set_bool("grounded", true);
+        sprite.update(0.0);
+        assert_eq!(sprite.current_clip(), Some("run"));
+    }
+
+    #[test]
+    fn any_holds_when_one_branch_holds() {
+        let mut sprite = sprite(&[("idle", 1, PlayMode::Loop), ("fall", 1, PlayMode::Loop)]);
+        let condition = Condition::Any(vec![
+            Condition::Trigger("hurt".to_string()),
+            Condition::Bool("dead".to_string(), true),
+        ]);
+        sprite.add_transition(Some("idle"), "fall", condition);
+        sprite.set_bool("dead", true);
+        sprite.update(0.0);
+        assert_eq!(sprite.current_clip(), Some("fall"));
+    }
+
+    #[test]
+    fn trigger_survives_until_its_transition_is_taken() {
+        let mut sprite = sprite(&[("idle", 1, PlayMode::Loop), ("fall", 1, PlayMode::Loop)]);
+        let condition = Condition::All(vec![
+            Condition::Trigger("hurt".to_string()),
+            Condition::Bool("dead".to_string(), true),
+        ]);
+        sprite.add_transition(Some("idle"), "fall", condition);
+        sprite.trigger("hurt");
+        sprite.update(0.0);
+        assert_eq!(sprite.current_clip(), Some("idle"));
+        sprite.set_bool("dead", true);
+        sprite.update(0.0);
+        assert_eq!(sprite.current_clip(), Some("fall"));
+    }
+
+    #[test]
+    fn unset_floats_never_hold() {
+        let mut sprite = sprite(&[("idle", 1, PlayMode::Loop), ("run", 1, PlayMode::Loop), ("crouch", 1, PlayMode::Loop)]);
+        sprite.add_transition(Some("idle"), "run", Condition::FloatAbove("speed".to_string(), -1.0));
+        sprite.add_transition(Some("idle"), "crouch", Condition::FloatBelow("height".to_string(), 1.0));
+        sprite.update(0.0);
+        assert_eq!(sprite.current_clip(), Some("idle"));
+
+        sprite.set_float("height", 0.0);
+        sprite.update(0.0);
+        assert_eq!(sprite.current_clip(), Some("crouch"));
+    }
+
+    #[test]
+    fn entering_clip_fires_first_frame_events_once() {
+        let mut sprite = sprite(&[("idle", 1, PlayMode::Loop)]);
+        sprite.add_clip("run", AnimationClip::new(frames(2), PlayMode::Loop).with_event(0, "start").with_event(1, "step"));
+        sprite.add_transition(Some("idle"), "run", Condition::Bool("moving".to_string(), true));
+        sprite.update(0.0);
+
+        sprite.set_bool("moving", true);
+        let events = sprite.update(0.0);
+        assert_eq!(events, vec![AnimationEvent { clip: "run".to_string(), frame: 0, name: "start".to_string() }]);
+        assert!(sprite.update(0.0).is_empty());
+
+        let names: Vec<String> = sprite.update(2.0).into_iter().map(|e| e.name).collect();
+        assert_eq!(names, vec!["step".to_string(), "start".to_string()]);
+    }
+}
@@ -0,0 +1,531 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use crate::graphics::wgpu_sprite::WgpuSprite;
+use crate::graphics::Rect;
+
+/// A named sub-image inside one page of a `TextureAtlas`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtlasRegion {
+    pub page: usize,
+    /// Source rectangle in page pixels, excluding padding and extrusion.
+    pub rect: Rect,
+}
+
+/// One packed texture. `key` is the name the page is registered under in the
+/// renderer's texture map and is used as the `texture_path` of atlas sprites.
+pub struct AtlasPage {
+    pub key: String,
+    pub image: RgbaImage,
+}
+
+/// The JSON file written by `TextureAtlas::save`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AtlasManifest {
+    #[serde(default)]
+    pub name: String,
+    pub pages: Vec<ManifestPage>,
+    /// Sorted by name so baked manifests are stable between runs.
+    pub regions: BTreeMap<String, ManifestRegion>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ManifestPage {
+    /// Image file, relative to the manifest.
+    pub image: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ManifestRegion {
+    pub page: usize,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Images packed into one or more pages, addressable by name.
+pub struct TextureAtlas {
+    pub name: String,
+    pub pages: Vec<AtlasPage>,
+    regions: HashMap<String, AtlasRegion>,
+}
+
+/// Collects images and packs them into a `TextureAtlas`.
+///
+/// # Examples
+///
+/// ```
+/// use barn::graphics::atlas::TextureAtlasBuilder;
+/// use image::RgbaImage;
+///
+/// let mut builder = TextureAtlasBuilder::new().with_max_size(64).with_padding(2).with_extrude(1);
+/// builder.add_image("player", RgbaImage::new(16, 24));
+/// builder.add_image("coin", RgbaImage::new(8, 8));
+/// let atlas = builder.build("sprites").unwrap();
+///
+/// assert_eq!(atlas.pages.len(), 1);
+/// let coin = atlas.region("coin").unwrap();
+/// assert_eq!((coin.rect.2, coin.rect.3), (8.0, 8.0));
+/// ```
+pub struct TextureAtlasBuilder {
+    max_size: u32,
+    padding: u32,
+    extrude: u32,
+    power_of_two: bool,
+    images: Vec<(String, RgbaImage)>,
+}
+
+impl TextureAtlasBuilder {
+    /// Returns a builder producing pages of at most 2048x2048 pixels, with 2 pixels of
+    /// padding between regions and 1 pixel of edge extrusion.
+    pub fn new() -> TextureAtlasBuilder {
+        TextureAtlasBuilder {
+            max_size: 2048,
+            padding: 2,
+            extrude: 1,
+            power_of_two: false,
+            images: Vec::new(),
+        }
+    }
+
+    pub fn with_max_size(mut self, max_size: u32) -> TextureAtlasBuilder {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Sets the number of transparent pixels left between regions.
+    pub fn with_padding(mut self, padding: u32) -> TextureAtlasBuilder {
+        self.padding = padding;
+        self
+    }
+
+    /// Sets how many times the border pixels of each image are repeated outwards,
+    /// which prevents neighbouring regions from bleeding in when filtering.
+    pub fn with_extrude(mut self, extrude: u32) -> TextureAtlasBuilder {
+        self.extrude = extrude;
+        self
+    }
+
+    /// Rounds page dimensions up to powers of two.
+    pub fn with_power_of_two(mut self, power_of_two: bool) -> TextureAtlasBuilder {
+        self.power_of_two = power_of_two;
+        self
+    }
+
+    pub fn add_image(&mut self, name: &str, image: RgbaImage) {
+        self.images.push((name.to_string(), image));
+    }
+
+    pub fn add_file(&mut self, name: &str, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let image = image::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        self.add_image(name, image.into_rgba8());
+        Ok(())
+    }
+
+    /// Adds an encoded image (PNG, JPEG, ...) from memory.
+    pub fn add_bytes(&mut self, name: &str, bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        self.add_image(name, image::load_from_memory(bytes)?.into_rgba8());
+        Ok(())
+    }
+
+    /// Packs every added image. Pages are keyed `"{name}#{index}"`.
+    pub fn build(&self, name: &str) -> Result<TextureAtlas, Box<dyn std::error::Error>> {
+        let border = self.extrude * 2 + self.padding;
+
+        // Place large images first; it gives the packer far fewer dead ends.
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&i| {
+            let (w, h) = self.images[i].1.dimensions();
+            (std::cmp::Reverse(w.max(h)), std::cmp::Reverse(w * h))
+        });
+
+        // Trailing padding may hang over the page edge, so bins are padded by that much.
+        let mut bins: Vec<MaxRectsBin> = Vec::new();
+        let mut placements = HashMap::new();
+        for index in order {
+            let (region_name, image) = &self.images[index];
+            if placements.contains_key(region_name) {
+                return Err(format!("duplicate atlas region '{}'", region_name).into());
+            }
+            let (w, h) = (image.width() + border, image.height() + border);
+            let placed = bins.iter_mut().enumerate().find_map(|(page, bin)| bin.insert(w, h).map(|cell| (page, cell)));
+            let (page, cell) = match placed {
+                Some(placed) => placed,
+                None => {
+                    let mut bin = MaxRectsBin::new(self.max_size + self.padding, self.max_size + self.padding);
+                    let cell = bin.insert(w, h).ok_or_else(|| format!(
+                        "image '{}' ({}x{}) does not fit in a {}x{} atlas page",
+                        region_name, image.width(), image.height(), self.max_size, self.max_size
+                    ))?;
+                    bins.push(bin);
+                    (bins.len() - 1, cell)
+                }
+            };
+            placements.insert(region_name.clone(), (index, page, cell));
+        }
+
+        // Shrink every page to the area actually used.
+        let mut page_sizes = vec![(1u32, 1u32); bins.len()];
+        for (_, page, cell) in placements.values() {
+            let size = &mut page_sizes[*page];
+            size.0 = size.0.max(cell.x + cell.w - self.padding);
+            size.1 = size.1.max(cell.y + cell.h - self.padding);
+        }
+        let mut pages: Vec<AtlasPage> = page_sizes.iter().enumerate().map(|(i, (w, h))| {
+            let (w, h) = if self.power_of_two { (w.next_power_of_two(), h.next_power_of_two()) } else { (*w, *h) };
+            AtlasPage { key: format!("{}#{}", name, i), image: RgbaImage::new(w, h) }
+        }).collect();
+
+        let mut regions = HashMap::new();
+        for (region_name, (index, page, cell)) in placements {
+            let image = &self.images[index].1;
+            let x = cell.x + self.extrude;
+            let y = cell.y + self.extrude;
+            blit_extruded(&mut pages[page].image, image, x, y, self.extrude);
+            regions.insert(region_name, AtlasRegion {
+                page,
+                rect: (x as f32, y as f32, image.width() as f32, image.height() as f32),
+            });
+        }
+
+        Ok(TextureAtlas { name: name.to_string(), pages, regions })
+    }
+}
+
+impl Default for TextureAtlasBuilder {
+    fn default() -> Self {
+        TextureAtlasBuilder::new()
+    }
+}
+
+impl TextureAtlas {
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.get(name)
+    }
+
+    pub fn regions(&self) -> impl Iterator<Item = (&String, &AtlasRegion)> {
+        self.regions.iter()
+    }
+
+    pub fn page_size(&self, page: usize) -> (u32, u32) {
+        self.pages[page].image.dimensions()
+    }
+
+    /// Returns a sprite drawing the named region at `dst`. Upload the pages with
+    /// `Renderer::load_atlas` first.
+    pub fn sprite(&self, name: &str, dst: Rect, color: [f32; 4]) -> Option<WgpuSprite> {
+        let region = self.region(name)?;
+        let page = &self.pages[region.page];
+        Some(WgpuSprite::deferred(region.rect, dst, color, &page.key, page.image.dimensions()))
+    }
+
+    /// Describes the pages and regions. Page images are referenced as `{name}_{index}.png`.
+    pub fn manifest(&self) -> AtlasManifest {
+        let pages = self.pages.iter().enumerate().map(|(i, page)| ManifestPage {
+            image: format!("{}_{}.png", self.name, i),
+            width: page.image.width(),
+            height: page.image.height(),
+        }).collect();
+        let regions = self.regions.iter().map(|(name, region)| {
+            let (x, y, width, height) = region.rect;
+            (name.clone(), ManifestRegion { page: region.page, x, y, width, height })
+        }).collect();
+        AtlasManifest { name: self.name.clone(), pages, regions }
+    }
+
+    /// Writes the page images and `{name}.json` into `directory`, returning the manifest path.
+    pub fn save(&self, directory: &str) -> Result<String, Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(directory)?;
+        for (i, page) in self.pages.iter().enumerate() {
+            let path = Path::new(directory).join(format!("{}_{}.png", self.name, i));
+            page.image.save(&path)?;
+        }
+        let manifest_path = Path::new(directory).join(format!("{}.json", self.name));
+        std::fs::write(&manifest_path, serde_json::to_string(&self.manifest())?)?;
+        Ok(manifest_path.to_string_lossy().into_owned())
+    }
+
+    /// Loads an atlas baked by `save` or the `barn_atlas` tool. Pages are keyed by
+    /// the path of their image file.
+    pub fn from_manifest(path: &str) -> Result<TextureAtlas, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let manifest: AtlasManifest = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+        let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+
+        let mut pages = Vec::new();
+        for page in manifest.pages.iter() {
+            let key = directory.join(&page.image).to_string_lossy().into_owned();
+            let image = image::open(&key).map_err(|e| format!("{}: {}", key, e))?.into_rgba8();
+            pages.push(AtlasPage { key, image });
+        }
+
+        let mut regions = HashMap::new();
+        for (region_name, region) in manifest.regions {
+            if region.page >= pages.len() {
+                return Err(format!("atlas region '{}' refers to missing page {}", region_name, region.page).into());
+            }
+            regions.insert(region_name, AtlasRegion {
+                page: region.page,
+                rect: (region.x, region.y, region.width, region.height),
+            });
+        }
+
+        let name = manifest.name;
+        Ok(TextureAtlas { name, pages, regions })
+    }
+}
+
+/// Copies `image` to (x, y) and repeats its border pixels `extrude` times outwards.
+fn blit_extruded(page: &mut RgbaImage, image: &RgbaImage, x: u32, y: u32, extrude: u32) {
+    let (w, h) = image.dimensions();
+    if w == 0 || h == 0 {
+        return;
+    }
+    for py in (y - extrude)..(y + h + extrude) {
+        for px in (x - extrude)..(x + w + extrude) {
+            if px >= page.width() || py >= page.height() {
+                continue;
+            }
+            let sx = px.saturating_sub(x).min(w - 1);
+            let sy = py.saturating_sub(y).min(h - 1);
+            page.put_pixel(px, py, *image.get_pixel(sx, sy));
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct PackRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+impl PackRect {
+    fn intersects(&self, other: &PackRect) -> bool {
+        self.x < other.x + other.w && other.x < self.x + self.w && self.y < other.y + other.h && other.y < self.y + self.h
+    }
+
+    fn contains(&self, other: &PackRect) -> bool {
+        other.x >= self.x && other.y >= self.y && other.x + other.w <= self.x + self.w && other.y + other.h <= self.y + self.h
+    }
+}
+
+/// MaxRects bin packer using the best-short-side-fit heuristic.
+struct MaxRectsBin {
+    free: Vec<PackRect>,
+}
+
+impl MaxRectsBin {
+    fn new(width: u32, height: u32) -> MaxRectsBin {
+        MaxRectsBin { free: vec![PackRect { x: 0, y: 0, w: width, h: height }] }
+    }
+
+    fn insert(&mut self, w: u32, h: u32) -> Option<PackRect> {
+        let placed = self.free.iter()
+            .filter(|f| w <= f.w && h <= f.h)
+            .min_by_key(|f| {
+                let (dw, dh) = (f.w - w, f.h - h);
+                (dw.min(dh), dw.max(dh), f.y, f.x)
+            })
+            .map(|f| PackRect { x: f.x, y: f.y, w, h })?;
+
+        // Split every free rectangle the placement overlaps into up to four maximal pieces.
+        let mut free = Vec::with_capacity(self.free.len() + 4);
+        for f in self.free.drain(..) {
+            if !f.intersects(&placed) {
+                free.push(f);
+                continue;
+            }
+            if placed.x > f.x {
+                free.push(PackRect { x: f.x, y: f.y, w: placed.x - f.x, h: f.h });
+            }
+            if placed.x + placed.w < f.x + f.w {
+                free.push(PackRect { x: placed.x + placed.w, y: f.y, w: f.x + f.w - placed.x - placed.w, h: f.h });
+            }
+            if placed.y > f.y {
+                free.push(PackRect { x: f.x, y: f.y, w: f.w, h: placed.y - f.y });
+            }
+            if placed.y + placed.h < f.y + f.h {
+                free.push(PackRect { x: f.x, y: placed.y + placed.h, w: f.w, h: f.y + f.h - placed.y - placed.h });
+            }
+        }
+
+        // Drop free rectangles contained in another one.
+        let mut i = 0;
+        while i < free.len() {
+            let contained = (0..free.len()).any(|j| j != i && free[j].contains(&free[i]) && (!free[i].contains(&free[j]) || j < i));
+            if contained {
+                free.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        self.free = free;
+        Some(placed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::renderer::Renderer;
+    use crate::graphics::software_renderer::SoftwareRenderer;
+    use image::Rgba;
+
+    fn solid(w: u32, h: u32, value: u8) -> RgbaImage {
+        RgbaImage::from_pixel(w, h, Rgba([value, value, value, 255]))
+    }
+
+    fn pack_rect(region: &AtlasRegion) -> PackRect {
+        let (x, y, w, h) = region.rect;
+        PackRect { x: x as u32, y: y as u32, w: w as u32, h: h as u32 }
+    }
+
+    #[test]
+    fn full_pages_overflow_into_new_ones() {
+        let mut builder = TextureAtlasBuilder::new().with_max_size(32).with_padding(0).with_extrude(0);
+        builder.add_image("a", solid(24, 24, 1));
+        builder.add_image("b", solid(24, 24, 2));
+        builder.add_image("c", solid(8, 8, 3));
+        let atlas = builder.build("overflow").unwrap();
+
+        assert_eq!(atlas.pages.len(), 2);
+        assert_eq!(atlas.pages[1].key, "overflow#1");
+        assert_ne!(atlas.region("a").unwrap().page, atlas.region("b").unwrap().page);
+        // The small image fills the gap on the first page instead of opening a third
+        assert_eq!(atlas.region("c").unwrap().page, 0);
+        for page in 0..atlas.pages.len() {
+            let (w, h) = atlas.page_size(page);
+            assert!(w <= 32 && h <= 32);
+        }
+    }
+
+    #[test]
+    fn regions_on_a_page_never_overlap() {
+        let mut builder = TextureAtlasBuilder::new().with_max_size(64).with_padding(1).with_extrude(1);
+        for i in 0..12 {
+            builder.add_image(&i.to_string(), solid(5 + i, 13 - i, i as u8));
+        }
+        let atlas = builder.build("packed").unwrap();
+        let regions: Vec<&AtlasRegion> = atlas.regions().map(|(_, region)| region).collect();
+        for (i, a) in regions.iter().enumerate() {
+            let (w, h) = atlas.page_size(a.page);
+            assert!(a.rect.0 + a.rect.2 <= w as f32 && a.rect.1 + a.rect.3 <= h as f32);
+            for b in regions.iter().skip(i + 1).filter(|b| b.page == a.page) {
+                assert!(!pack_rect(a).intersects(&pack_rect(b)));
+            }
+        }
+    }
+
+    #[test]
+    fn duplicate_names_are_rejected() {
+        let mut builder = TextureAtlasBuilder::new();
+        builder.add_image("coin", solid(4, 4, 0));
+        builder.add_image("coin", solid(8, 8, 0));
+        let error = builder.build("dupes").err().unwrap();
+        assert!(error.to_string().contains("duplicate atlas region 'coin'"));
+    }
+
+    #[test]
+    fn images_larger_than_a_page_are_rejected() {
+        let mut builder = TextureAtlasBuilder::new().with_max_size(16).with_padding(0).with_extrude(1);
+        // Fits exactly without extrusion, but not with it
+        builder.add_image("wide", solid(16, 4, 0));
+        let error = builder.build("small").err().unwrap();
+        assert!(error.to_string().contains("does not fit in a 16x16 atlas page"));
+
+        let mut builder = TextureAtlasBuilder::new().with_max_size(16).with_padding(4).with_extrude(0);
+        builder.add_image("exact", solid(16, 16, 0));
+        assert_eq!(builder.build("exact").unwrap().page_size(0), (16, 16));
+    }
+
+    #[test]
+    fn border_pixels_are_extruded() {
+        let mut image = RgbaImage::new(2, 2);
+        image.put_pixel(0, 0, Rgba([1, 0, 0, 255]));
+        image.put_pixel(1, 1, Rgba([2, 0, 0, 255]));
+        let mut builder = TextureAtlasBuilder::new().with_padding(0).with_extrude(1);
+        builder.add_image("tile", image);
+        let atlas = builder.build("extruded").unwrap();
+
+        assert_eq!(atlas.region("tile").unwrap().rect, (1.0, 1.0, 2.0, 2.0));
+        let page = &atlas.pages[0].image;
+        assert_eq!(page.dimensions(), (4, 4));
+        assert_eq!(page.get_pixel(0, 0), &Rgba([1, 0, 0, 255]));
+        assert_eq!(page.get_pixel(3, 3), &Rgba([2, 0, 0, 255]));
+    }
+
+    #[test]
+    fn power_of_two_rounds_pages_up() {
+        let mut builder = TextureAtlasBuilder::new().with_padding(0).with_extrude(0).with_power_of_two(true);
+        builder.add_image("odd", solid(17, 5, 0));
+        assert_eq!(builder.build("pot").unwrap().page_size(0), (32, 8));
+    }
+
+    #[test]
+    fn manifest_round_trips_through_save() {
+        let directory = std::env::temp_dir().join(format!("barn_atlas_{}", std::process::id()));
+        let mut builder = TextureAtlasBuilder::new().with_max_size(16).with_padding(0).with_extrude(0);
+        builder.add_image("a", solid(12, 12, 10));
+        builder.add_image("b", solid(12, 12, 20));
+        let atlas = builder.build("saved").unwrap();
+        let path = atlas.save(directory.to_str().unwrap()).unwrap();
+
+        let loaded = TextureAtlas::from_manifest(&path).unwrap();
+        assert_eq!(loaded.name, "saved");
+        assert_eq!(loaded.pages.len(), 2);
+        for name in ["a", "b"] {
+            assert_eq!(loaded.region(name), atlas.region(name));
+        }
+        let b = loaded.region("b").unwrap();
+        assert_eq!(loaded.pages[b.page].image.get_pixel(0, 0), &Rgba([20, 20, 20, 255]));
+    }
+
+    #[test]
+    fn manifests_with_missing_pages_are_rejected() {
+        let directory = std::env::temp_dir().join(format!("barn_atlas_bad_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("bad.json");
+        std::fs::write(&path, r#"{"name": "bad", "pages": [], "regions": {"a": {"page": 0, "x": 0, "y": 0, "width": 1, "height": 1}}}"#).unwrap();
+        let error = TextureAtlas::from_manifest(path.to_str().unwrap()).err().unwrap();
+        assert!(error.to_string().contains("refers to missing page 0"));
+        std::fs::write(&path, r#"{"name": "bad", "pages": []}"#).unwrap();
+        let error = TextureAtlas::from_manifest(path.to_str().unwrap()).err().unwrap();
+        assert!(error.to_string().contains("regions"));
+    }
+
+    #[test]
+    fn manifest_regions_are_sorted_by_name() {
+        let mut builder = TextureAtlasBuilder::new().with_padding(0).with_extrude(0);
+        for name in ["zebra", "apple", "mango"] {
+            builder.add_image(name, solid(4, 4, 0));
+        }
+        let manifest = builder.build("fruit").unwrap().manifest();
+        let json = serde_json::to_string(&manifest).unwrap();
+        let position = |name: &str| json.find(&format!("\"{}\"", name)).unwrap();
+        assert!(position("apple") < position("mango") && position("mango") < position("zebra"));
+        assert_eq!(manifest.pages[0].image, "fruit_0.png");
+    }
+
+    #[test]
+    fn loaded_atlas_sprites_draw_their_region() {
+        let mut builder = TextureAtlasBuilder::new().with_padding(1).with_extrude(0);
+        builder.add_image("dark", solid(2, 2, 0));
+        builder.add_image("light", solid(2, 2, 255));
+        let atlas = builder.build("tiles").unwrap();
+        let mut renderer = SoftwareRenderer::new(4, 4);
+        renderer.load_atlas(&atlas);
+        assert_eq!(renderer.texture_size(&atlas.pages[0].key), Some(atlas.page_size(0)));
+        renderer.draw_sprite(atlas.sprite("light", (0.0, 0.0, 4.0, 4.0), [1.0, 1.0, 1.0, 1.0]).unwrap());
+        let frame = renderer.present();
+        assert_eq!(frame.get_pixel(0, 0).0, [255, 255, 255, 255]);
+        assert_eq!(frame.get_pixel(3, 3).0, [255, 255, 255, 255]);
+    }
+}
@@ -10,12 +10,11 @@ impl<'a> BarnGFX<'a> {
     }
     
     pub fn render(&mut self, surface: &mut wgpu::Surface) -> Result<(), wgpu::SurfaceError> {
-        self.renderer.render(surface);
-        Ok(())
+        self.renderer.render(surface)
     }
     
-    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>, surface: &mut wgpu::Surface, config: &mut wgpu::SurfaceConfiguration) {
-        self.renderer.resize(new_size, surface, config);
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>, surface: &mut wgpu::Surface) {
+        self.renderer.resize(new_size, surface);
     }
     
     pub fn load_texture(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
@@ -0,0 +1,570 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+
+/// Where a frame recording is written.
+pub enum RecordingFormat {
+    /// An animated GIF at the given path. Frame delays follow the real frame times.
+    Gif(String),
+    /// Numbered PNG files, e.g. `captures/frame_` produces `captures/frame_00000.png`.
+    PngSequence(String),
+}
+
+/// Writes presented frames to disk while a recording is active.
+pub struct FrameRecorder {
+    format: RecordingFormat,
+    gif: Option<GifEncoder<BufWriter<File>>>,
+    // GIF frames are written one frame late, once their display time is known.
+    pending: Option<(RgbaImage, Instant)>,
+    last_delay: Duration,
+    frame_count: u32,
+}
+
+impl FrameRecorder {
+    pub fn new(format: RecordingFormat) -> Result<FrameRecorder, Box<dyn std::error::Error>> {
+        let gif = match &format {
+            RecordingFormat::Gif(path) => {
+                let mut encoder = GifEncoder::new_with_speed(BufWriter::new(File::create(path)?), 10);
+                encoder.set_repeat(Repeat::Infinite)?;
+                Some(encoder)
+            }
+            RecordingFormat::PngSequence(_) => None,
+        };
+        Ok(FrameRecorder {
+            format,
+            gif,
+            pending: None,
+            last_delay: Duration::from_millis(16),
+            frame_count: 0,
+        })
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    pub fn add_frame(&mut self, image: RgbaImage) -> Result<(), Box<dyn std::error::Error>> {
+        self.add_frame_at(image, Instant::now())
+    }
+
+    /// Adds a frame that was presented at `now`, which sets the GIF delay of the frame before it.
+    pub fn add_frame_at(&mut self, image: RgbaImage, now: Instant) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.format {
+            RecordingFormat::PngSequence(prefix) => {
+                image.save(format!("{}{:05}.png", prefix, self.frame_count))?;
+            }
+            RecordingFormat::Gif(_) => {
+                if let Some((previous, time)) = self.pending.take() {
+                    self.last_delay = now.duration_since(time);
+                    self.write_gif_frame(previous)?;
+                }
+                self.pending = Some((image, now));
+            }
+        }
+        self.frame_count += 1;
+        Ok(())
+    }
+
+    /// Flushes any buffered frame and closes the output.
+    pub fn finish(mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some((image, _)) = self.pending.take() {
+            self.write_gif_frame(image)?;
+        }
+        Ok(())
+    }
+
+    fn write_gif_frame(&mut self, image: RgbaImage) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(encoder) = self.gif.as_mut() {
+            let delay = Delay::from_saturating_duration(self.last_delay);
+            encoder.encode_frame(Frame::from_parts(image, 0, 0, delay))?;
+        }
+        Ok(())
+    }
+}
+
+/// The requests a copied frame answers, taken from `FrameCapture` when the copy
+/// is recorded so a request is never answered by an earlier frame.
+#[derive(Default)]
+pub(crate) struct CaptureRequest {
+    keep: bool,
+    paths: Vec<String>,
+    /// When the frame was presented, if it belongs to a recording.
+    record: Option<Instant>,
+}
+
+/// How many read-back frames can wait for the encoder thread before delivering blocks.
+const ENCODE_QUEUE: usize = 8;
+
+/// Work for the encoder thread, done in the order it was sent.
+enum EncodeJob {
+    Frame { image: RgbaImage, paths: Vec<String>, record: Option<Instant> },
+    StartRecording(FrameRecorder),
+    StopRecording(Sender<Result<(), String>>),
+    Flush(Sender<()>),
+}
+
+/// A thread that writes screenshots and recordings, so PNG and GIF encoding
+/// doesn't hold up presenting frames.
+struct FrameEncoder {
+    jobs: Option<SyncSender<EncodeJob>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FrameEncoder {
+    fn spawn() -> FrameEncoder {
+        let (jobs, receiver) = mpsc::sync_channel(ENCODE_QUEUE);
+        let thread = thread::Builder::new()
+            .name("barn-capture".to_string())
+            .spawn(move || encode_frames(receiver))
+            .expect("failed to spawn the capture thread");
+        FrameEncoder { jobs: Some(jobs), thread: Some(thread) }
+    }
+
+    fn send(&self, job: EncodeJob) -> Result<(), Box<dyn std::error::Error>> {
+        let jobs = self.jobs.as_ref().ok_or("capture thread has stopped")?;
+        jobs.send(job).map_err(|_| "capture thread has stopped".into())
+    }
+}
+
+impl Drop for FrameEncoder {
+    /// Finishes the queued writes and any active recording.
+    fn drop(&mut self) {
+        self.jobs = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn encode_frames(jobs: Receiver<EncodeJob>) {
+    let mut recorder: Option<FrameRecorder> = None;
+    for job in jobs {
+        match job {
+            EncodeJob::Frame { image, paths, record } => {
+                for path in paths.iter() {
+                    if let Err(e) = image.save(path) {
+                        log::error!("Failed to save screenshot {}: {}", path, e);
+                    }
+                }
+                if let (Some(recorder), Some(time)) = (recorder.as_mut(), record) {
+                    if let Err(e) = recorder.add_frame_at(image, time) {
+                        log::error!("Failed to record frame: {}", e);
+                    }
+                }
+            }
+            EncodeJob::StartRecording(new) => recorder = Some(new),
+            EncodeJob::StopRecording(reply) => {
+                let result = recorder.take().map_or(Ok(()), |recorder| recorder.finish());
+                let _ = reply.send(result.map_err(|e| e.to_string()));
+            }
+            EncodeJob::Flush(reply) => {
+                let _ = reply.send(());
+            }
+        }
+    }
+    if let Some(Err(e)) = recorder.map(|recorder| recorder.finish()) {
+        log::error!("Failed to finish recording: {}", e);
+    }
+}
+
+/// Pending screenshot and recording requests for the renderer.
+///
+/// Frames are read back asynchronously and written on a separate thread, so
+/// requests are answered a frame or two after the frame they capture was
+/// presented. Use `flush` to wait until the files are written.
+#[derive(Default)]
+pub struct FrameCapture {
+    capture_next: bool,
+    screenshot_paths: Vec<String>,
+    captured: Option<RgbaImage>,
+    recording: bool,
+    encoder: Option<FrameEncoder>,
+}
+
+impl FrameCapture {
+    /// Returns true if the next presented frame needs to be read back from the GPU.
+    pub fn wants_frame(&self) -> bool {
+        self.capture_next || !self.screenshot_paths.is_empty() || self.recording
+    }
+
+    pub fn capture_next_frame(&mut self) {
+        self.capture_next = true;
+    }
+
+    pub fn save_next_frame(&mut self, path: &str) {
+        self.screenshot_paths.push(path.to_string());
+    }
+
+    pub fn take_captured_frame(&mut self) -> Option<RgbaImage> {
+        self.captured.take()
+    }
+
+    /// Starts a recording. The output file is created straight away, so errors
+    /// opening it are returned here.
+    pub fn start_recording(&mut self, format: RecordingFormat) -> Result<(), Box<dyn std::error::Error>> {
+        self.stop_recording()?;
+        let recorder = FrameRecorder::new(format)?;
+        self.encoder().send(EncodeJob::StartRecording(recorder))?;
+        self.recording = true;
+        Ok(())
+    }
+
+    /// Stops the recording and waits until its queued frames are written.
+    pub fn stop_recording(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if !std::mem::take(&mut self.recording) {
+            return Ok(());
+        }
+        let (reply, result) = mpsc::channel();
+        self.encoder().send(EncodeJob::StopRecording(reply))?;
+        result.recv().map_err(|_| "capture thread has stopped")??;
+        Ok(())
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// Blocks until every screenshot and recorded frame delivered so far is written.
+    pub fn flush(&mut self) {
+        if let Some(encoder) = self.encoder.as_ref() {
+            let (reply, done) = mpsc::channel();
+            if encoder.send(EncodeJob::Flush(reply)).is_ok() {
+                let _ = done.recv();
+            }
+        }
+    }
+
+    /// Takes the pending requests for the frame being copied now.
+    pub(crate) fn take_request(&mut self) -> CaptureRequest {
+        CaptureRequest {
+            keep: std::mem::take(&mut self.capture_next),
+            paths: std::mem::take(&mut self.screenshot_paths),
+            record: self.recording.then(Instant::now),
+        }
+    }
+
+    /// Hands a read-back frame to the requests taken when it was copied. Files are
+    /// written on the encoder thread. Frames copied for a recording that has since
+    /// stopped are dropped.
+    pub(crate) fn deliver(&mut self, image: RgbaImage, request: CaptureRequest) -> Result<(), Box<dyn std::error::Error>> {
+        let record = request.record.filter(|_| self.recording);
+        if request.keep {
+            self.captured = Some(image.clone());
+        }
+        if !request.paths.is_empty() || record.is_some() {
+            self.encoder().send(EncodeJob::Frame { image, paths: request.paths, record })?;
+        }
+        Ok(())
+    }
+
+    fn encoder(&mut self) -> &FrameEncoder {
+        self.encoder.get_or_insert_with(FrameEncoder::spawn)
+    }
+}
+
+/// How many copied frames can wait for the GPU before capturing has to block.
+const READBACK_BUFFERS: usize = 3;
+
+/// A buffer the size of one frame that the GPU copies into and the CPU maps.
+struct ReadbackBuffer {
+    buffer: wgpu::Buffer,
+    padded_bytes_per_row: u32,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+}
+
+impl ReadbackBuffer {
+    fn new(device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32) -> ReadbackBuffer {
+        // Buffer rows must be aligned for texture-to-buffer copies.
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (width * 4).div_ceil(align) * align;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        ReadbackBuffer {
+            buffer,
+            padded_bytes_per_row,
+            width,
+            height,
+            format,
+        }
+    }
+
+    fn fits(&self, texture: &wgpu::Texture) -> bool {
+        (self.width, self.height, self.format) == (texture.width(), texture.height(), texture.format())
+    }
+
+    fn copy_from(&self, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &self.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_bytes_per_row),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Converts the mapped buffer to an image and unmaps it.
+    fn read(&self) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+        let image = frame_to_image(&self.buffer.slice(..).get_mapped_range(), self.width, self.height, self.padded_bytes_per_row, self.format);
+        self.buffer.unmap();
+        image
+    }
+}
+
+/// Returns an error unless frames of `format` can be captured. Only 8-bit RGBA
+/// and BGRA surfaces are supported.
+pub(crate) fn check_format(format: wgpu::TextureFormat) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm
+        | wgpu::TextureFormat::Rgba8UnormSrgb
+        | wgpu::TextureFormat::Bgra8Unorm
+        | wgpu::TextureFormat::Bgra8UnormSrgb => Ok(()),
+        other => Err(format!("can't capture frames of format {:?}", other).into()),
+    }
+}
+
+/// Strips the row padding from a copied frame and swaps BGRA surfaces to RGBA.
+fn frame_to_image(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    format: wgpu::TextureFormat,
+) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+    check_format(format)?;
+    let swap_red_blue = matches!(format, wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb);
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for row in data.chunks(padded_bytes_per_row as usize).take(height as usize) {
+        let row = row.get(..(width * 4) as usize).ok_or("captured frame has a short row")?;
+        for pixel in row.chunks_exact(4) {
+            if swap_red_blue {
+                pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+            } else {
+                pixels.extend_from_slice(pixel);
+            }
+        }
+    }
+    RgbaImage::from_raw(width, height, pixels).ok_or_else(|| "captured frame has an unexpected size".into())
+}
+
+/// A read-back frame, or why it couldn't be read, and the requests it answers.
+pub(crate) type CapturedFrame = (Result<RgbaImage, Box<dyn std::error::Error>>, CaptureRequest);
+
+/// A frame copied into a readback buffer, waiting for the GPU.
+struct PendingFrame {
+    buffer: ReadbackBuffer,
+    request: CaptureRequest,
+    /// Receives the result of mapping the buffer, once its copy has been submitted.
+    mapped: Option<Receiver<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+/// A small ring of readback buffers, so copying a frame for a screenshot or
+/// recording doesn't stall `present` until the GPU has finished it.
+#[derive(Default)]
+pub(crate) struct FrameReadback {
+    idle: Vec<ReadbackBuffer>,
+    /// Copied frames, oldest first.
+    pending: VecDeque<PendingFrame>,
+}
+
+impl FrameReadback {
+    /// Whether every buffer holds a frame the GPU hasn't finished copying.
+    pub fn is_full(&self) -> bool {
+        self.pending.len() >= READBACK_BUFFERS
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Records a copy of `texture` into a free buffer, for the requests in `request`.
+    pub fn copy(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        request: CaptureRequest,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        check_format(texture.format())?;
+        let buffer = match self.idle.pop() {
+            Some(buffer) if buffer.fits(texture) => buffer,
+            // Buffers sized for the window before a resize are dropped
+            _ => ReadbackBuffer::new(device, texture.format(), texture.width(), texture.height()),
+        };
+        buffer.copy_from(encoder, texture);
+        self.pending.push_back(PendingFrame { buffer, request, mapped: None });
+        Ok(())
+    }
+
+    /// Starts mapping the buffers copied into since the last submit. Call it after
+    /// submitting the encoder passed to `copy`.
+    pub fn map_submitted(&mut self) {
+        for frame in self.pending.iter_mut().filter(|frame| frame.mapped.is_none()) {
+            let (sender, receiver) = mpsc::channel();
+            frame.buffer.buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+            frame.mapped = Some(receiver);
+        }
+    }
+
+    /// Returns the frames the GPU has finished copying, oldest first, with the
+    /// requests they answer. With `wait` it blocks until every submitted copy is done.
+    pub fn collect(&mut self, device: &wgpu::Device, wait: bool) -> Vec<CapturedFrame> {
+        device.poll(if wait { wgpu::Maintain::Wait } else { wgpu::Maintain::Poll });
+        let mut frames = Vec::new();
+        while let Some(frame) = self.pending.front() {
+            let mapped = match frame.mapped.as_ref().map(|receiver| receiver.try_recv()) {
+                Some(Ok(result)) => result.map_err(|e| e.into()),
+                Some(Err(TryRecvError::Disconnected)) => Err("capture buffer was never mapped".into()),
+                // Keep frames in order behind one the GPU is still copying
+                Some(Err(TryRecvError::Empty)) | None => break,
+            };
+            let frame = self.pending.pop_front().unwrap();
+            let image = mapped.and_then(|_| frame.buffer.read());
+            frames.push((image, frame.request));
+            self.idle.push(frame.buffer);
+        }
+        frames
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("barn_capture_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn frame_to_image_strips_row_padding() {
+        // 2x2 frame with rows padded to 12 bytes
+        let data = [
+            1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0, 0,
+            9, 10, 11, 12, 13, 14, 15, 16, 0, 0, 0, 0,
+        ];
+        let image = frame_to_image(&data, 2, 2, 12, wgpu::TextureFormat::Rgba8UnormSrgb).unwrap();
+        assert_eq!(image.get_pixel(1, 0), &Rgba([5, 6, 7, 8]));
+        assert_eq!(image.get_pixel(0, 1), &Rgba([9, 10, 11, 12]));
+    }
+
+    #[test]
+    fn frame_to_image_swaps_bgra_surfaces() {
+        let image = frame_to_image(&[10, 20, 30, 255], 1, 1, 4, wgpu::TextureFormat::Bgra8UnormSrgb).unwrap();
+        assert_eq!(image.get_pixel(0, 0), &Rgba([30, 20, 10, 255]));
+    }
+
+    #[test]
+    fn frame_to_image_rejects_short_data() {
+        assert!(frame_to_image(&[0; 12], 2, 2, 8, wgpu::TextureFormat::Rgba8Unorm).is_err());
+        assert!(frame_to_image(&[0; 6], 2, 1, 6, wgpu::TextureFormat::Rgba8Unorm).is_err());
+    }
+
+    #[test]
+    fn requests_are_taken_once() {
+        let mut capture = FrameCapture::default();
+        assert!(!capture.wants_frame());
+        capture.capture_next_frame();
+        assert!(capture.wants_frame());
+        let request = capture.take_request();
+        assert!(!capture.wants_frame());
+
+        // A frame copied later doesn't answer the earlier request
+        capture.deliver(RgbaImage::new(1, 1), CaptureRequest::default()).unwrap();
+        assert!(capture.take_captured_frame().is_none());
+        capture.deliver(RgbaImage::new(2, 2), request).unwrap();
+        assert_eq!(capture.take_captured_frame().unwrap().dimensions(), (2, 2));
+        assert!(capture.take_captured_frame().is_none());
+    }
+
+    #[test]
+    fn screenshots_are_saved_when_delivered() {
+        let path = temp_dir("screenshot").join("shot.png");
+        let mut capture = FrameCapture::default();
+        capture.save_next_frame(path.to_str().unwrap());
+        let request = capture.take_request();
+        assert!(!path.exists());
+        capture.deliver(RgbaImage::new(3, 2), request).unwrap();
+        capture.flush();
+        assert_eq!(image::open(&path).unwrap().to_rgba8().dimensions(), (3, 2));
+    }
+
+    #[test]
+    fn recording_writes_numbered_pngs_and_drops_frames_after_stop() {
+        let dir = temp_dir("recording");
+        let prefix = dir.join("frame_");
+        let mut capture = FrameCapture::default();
+        capture.start_recording(RecordingFormat::PngSequence(prefix.to_str().unwrap().to_string())).unwrap();
+        assert!(capture.wants_frame());
+        let first = capture.take_request();
+        // Recordings keep wanting frames
+        assert!(capture.wants_frame());
+        let second = capture.take_request();
+        capture.deliver(RgbaImage::new(1, 1), first).unwrap();
+        capture.deliver(RgbaImage::new(1, 1), second).unwrap();
+        capture.flush();
+        assert!(dir.join("frame_00000.png").exists());
+        assert!(dir.join("frame_00001.png").exists());
+
+        let late = capture.take_request();
+        capture.stop_recording().unwrap();
+        assert!(!capture.wants_frame());
+        capture.deliver(RgbaImage::new(1, 1), late).unwrap();
+        capture.flush();
+        assert!(!dir.join("frame_00002.png").exists());
+    }
+
+    #[test]
+    fn dropping_the_capture_finishes_a_gif_recording() {
+        use image::AnimationDecoder;
+
+        let path = temp_dir("gif").join("clip.gif");
+        let mut capture = FrameCapture::default();
+        capture.start_recording(RecordingFormat::Gif(path.to_str().unwrap().to_string())).unwrap();
+        for _ in 0..3 {
+            let request = capture.take_request();
+            capture.deliver(RgbaImage::new(2, 2), request).unwrap();
+        }
+        drop(capture);
+        let decoder = image::codecs::gif::GifDecoder::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(decoder.into_frames().count(), 3);
+    }
+
+    #[test]
+    fn frame_to_image_rejects_unsupported_formats() {
+        for format in [wgpu::TextureFormat::Rgba16Float, wgpu::TextureFormat::Rgb10a2Unorm] {
+            let error = frame_to_image(&[0; 16], 1, 1, 16, format).err().unwrap();
+            assert!(error.to_string().contains(&format!("{:?}", format)));
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::graphics::wgpu_sprite::WgpuSprite;
+use crate::graphics::Rect;
+use crate::math::vector2::Vector2;
+
+/// A shape passed to `Renderer::push_mask`.
+/// Drawing is limited to the union of the shapes in a mask.
+#[derive(Clone)]
+pub enum ClipMask {
+    Rect(Rect),
+    Circle { x: f32, y: f32, radius: f32 },
+    /// A convex polygon in screen pixels.
+    Polygon(Vec<Vector2>),
+    /// The opaque pixels (alpha of at least one half) of a sprite.
+    Sprite(WgpuSprite),
+}
+
+impl ClipMask {
+
+    /// Triangulates primitive shapes into a triangle list in screen pixels.
+    /// Returns `None` for sprites, which are drawn through the sprite pipeline.
+    pub fn triangles(&self) -> Option<Vec<[f32; 2]>> {
+        match self {
+            ClipMask::Rect((x, y, w, h)) => Some(vec![
+                [*x, *y], [x + w, *y], [x + w, y + h],
+                [*x, *y], [x + w, y + h], [*x, y + h],
+            ]),
+            ClipMask::Circle { x, y, radius } => {
+                let segments = (radius * 0.5).clamp(12.0, 96.0) as usize;
+                let mut triangles = Vec::with_capacity(segments * 3);
+                for i in 0..segments {
+                    let a0 = i as f32 / segments as f32 * std::f32::consts::TAU;
+                    let a1 = (i + 1) as f32 / segments as f32 * std::f32::consts::TAU;
+                    triangles.push([*x, *y]);
+                    triangles.push([x + radius * a0.cos(), y + radius * a0.sin()]);
+                    triangles.push([x + radius * a1.cos(), y + radius * a1.sin()]);
+                }
+                Some(triangles)
+            }
+            ClipMask::Polygon(points) => {
+                let mut triangles = Vec::new();
+                for i in 1..points.len().saturating_sub(1) {
+                    triangles.push([points[0].x, points[0].y]);
+                    triangles.push([points[i].x, points[i].y]);
+                    triangles.push([points[i + 1].x, points[i + 1].y]);
+                }
+                Some(triangles)
+            }
+            ClipMask::Sprite(_) => None,
+        }
+    }
+}
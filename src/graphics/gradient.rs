@@ -0,0 +1,273 @@
+use crate::graphics::mesh::{fan_indices, MeshVertex};
+use crate::graphics::Rect;
+
+/// Largest triangle edge, in pixels, left after subdividing shapes for radial gradients.
+const RADIAL_STEP: f32 = 12.0;
+
+/// A color at a position along a gradient, where 0.0 is the start and 1.0 the end.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GradientStop {
+    pub offset: f32,
+    pub color: [f32; 4],
+}
+
+/// A color ramp in screen pixels. Shapes filled with a gradient are tessellated so
+/// the per-vertex colors reproduce it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Gradient {
+    /// Colors change along the line from `start` to `end` and are constant across it.
+    Linear { start: [f32; 2], end: [f32; 2], stops: Vec<GradientStop> },
+    /// Colors change with the distance from `center`; offset 1.0 lies at `radius`.
+    Radial { center: [f32; 2], radius: f32, stops: Vec<GradientStop> },
+}
+
+impl Gradient {
+
+    /// Returns a linear gradient. Stops are `(offset, color)` pairs in any order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use barn::graphics::gradient::Gradient;
+    /// let sky = Gradient::linear([0.0, 0.0], [0.0, 100.0], &[(0.0, [0.0, 0.0, 0.5, 1.0]), (1.0, [1.0, 0.5, 0.0, 1.0])]);
+    /// assert_eq!(sky.color_at([30.0, 50.0]), [0.5, 0.25, 0.25, 1.0]);
+    /// ```
+    pub fn linear(start: [f32; 2], end: [f32; 2], stops: &[(f32, [f32; 4])]) -> Gradient {
+        Gradient::Linear { start, end, stops: sorted_stops(stops) }
+    }
+
+    pub fn radial(center: [f32; 2], radius: f32, stops: &[(f32, [f32; 4])]) -> Gradient {
+        Gradient::Radial { center, radius, stops: sorted_stops(stops) }
+    }
+
+    /// Returns a two-color gradient running from the top to the bottom of `rect`.
+    pub fn vertical(rect: Rect, top: [f32; 4], bottom: [f32; 4]) -> Gradient {
+        Gradient::linear([rect.0, rect.1], [rect.0, rect.1 + rect.3], &[(0.0, top), (1.0, bottom)])
+    }
+
+    /// Returns a two-color gradient running from the left to the right of `rect`.
+    pub fn horizontal(rect: Rect, left: [f32; 4], right: [f32; 4]) -> Gradient {
+        Gradient::linear([rect.0, rect.1], [rect.0 + rect.2, rect.1], &[(0.0, left), (1.0, right)])
+    }
+
+    pub fn stops(&self) -> &[GradientStop] {
+        match self {
+            Gradient::Linear { stops, .. } | Gradient::Radial { stops, .. } => stops,
+        }
+    }
+
+    /// Returns the unclamped gradient offset of a point.
+    pub fn offset_at(&self, point: [f32; 2]) -> f32 {
+        match self {
+            Gradient::Linear { start, end, .. } => {
+                let axis = [end[0] - start[0], end[1] - start[1]];
+                let length_squared = axis[0] * axis[0] + axis[1] * axis[1];
+                if length_squared <= f32::EPSILON {
+                    return 0.0;
+                }
+                ((point[0] - start[0]) * axis[0] + (point[1] - start[1]) * axis[1]) / length_squared
+            }
+            Gradient::Radial { center, radius, .. } => {
+                let distance = ((point[0] - center[0]).powi(2) + (point[1] - center[1]).powi(2)).sqrt();
+                if *radius <= f32::EPSILON { 0.0 } else { distance / radius }
+            }
+        }
+    }
+
+    pub fn color_at(&self, point: [f32; 2]) -> [f32; 4] {
+        let stops = self.stops();
+        let offset = self.offset_at(point);
+        match stops.iter().position(|stop| stop.offset >= offset) {
+            None => stops.last().map_or([0.0; 4], |stop| stop.color),
+            Some(0) => stops[0].color,
+            Some(i) => {
+                let (a, b) = (stops[i - 1], stops[i]);
+                let t = (offset - a.offset) / (b.offset - a.offset);
+                std::array::from_fn(|c| a.color[c] + (b.color[c] - a.color[c]) * t)
+            }
+        }
+    }
+
+    /// Tessellates a convex polygon filled with this gradient into an indexed triangle list.
+    ///
+    /// Linear gradients are split into bands at every stop, which makes the result
+    /// exact. Radial gradients are subdivided into small triangles and approximated.
+    pub fn mesh(&self, polygon: &[[f32; 2]]) -> (Vec<MeshVertex>, Vec<u32>) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        if polygon.len() < 3 {
+            return (vertices, indices);
+        }
+        match self {
+            Gradient::Linear { .. } => {
+                let offsets: Vec<f32> = polygon.iter().map(|p| self.offset_at(*p)).collect();
+                let min = offsets.iter().copied().fold(f32::MAX, f32::min);
+                let max = offsets.iter().copied().fold(f32::MIN, f32::max);
+                let mut cuts = vec![min];
+                cuts.extend(self.stops().iter().map(|stop| stop.offset).filter(|offset| *offset > min && *offset < max));
+                cuts.push(max);
+                for band in cuts.windows(2) {
+                    let clipped = clip(polygon, |p| self.offset_at(p) - band[0]);
+                    let clipped = clip(&clipped, |p| band[1] - self.offset_at(p));
+                    if clipped.len() < 3 {
+                        continue;
+                    }
+                    indices.extend(fan_indices(vertices.len() as u32, clipped.len() as u32));
+                    vertices.extend(clipped.iter().map(|p| MeshVertex::colored(*p, self.color_at(*p))));
+                }
+            }
+            Gradient::Radial { .. } => {
+                for i in 1..polygon.len() - 1 {
+                    self.subdivide(polygon[0], polygon[i], polygon[i + 1], &mut vertices, &mut indices);
+                }
+            }
+        }
+        (vertices, indices)
+    }
+
+    /// Splits a triangle into a grid of smaller triangles with colors at every corner.
+    fn subdivide(&self, a: [f32; 2], b: [f32; 2], c: [f32; 2], vertices: &mut Vec<MeshVertex>, indices: &mut Vec<u32>) {
+        let edge = |p: [f32; 2], q: [f32; 2]| ((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2)).sqrt();
+        let longest = edge(a, b).max(edge(b, c)).max(edge(c, a));
+        let n = ((longest / RADIAL_STEP).ceil() as u32).clamp(1, 64);
+
+        let first = vertices.len() as u32;
+        for i in 0..=n {
+            for j in 0..=(n - i) {
+                let (u, v) = (i as f32 / n as f32, j as f32 / n as f32);
+                let p = [a[0] + (b[0] - a[0]) * u + (c[0] - a[0]) * v, a[1] + (b[1] - a[1]) * u + (c[1] - a[1]) * v];
+                vertices.push(MeshVertex::colored(p, self.color_at(p)));
+            }
+        }
+        // Row i of the grid holds n - i + 1 vertices.
+        let index = |i: u32, j: u32| first + (0..i).map(|k| n - k + 1).sum::<u32>() + j;
+        for i in 0..n {
+            for j in 0..(n - i) {
+                indices.extend_from_slice(&[index(i, j), index(i + 1, j), index(i, j + 1)]);
+                if j + 1 < n - i {
+                    indices.extend_from_slice(&[index(i + 1, j), index(i + 1, j + 1), index(i, j + 1)]);
+                }
+            }
+        }
+    }
+}
+
+fn sorted_stops(stops: &[(f32, [f32; 4])]) -> Vec<GradientStop> {
+    let mut stops: Vec<GradientStop> = stops.iter().map(|(offset, color)| GradientStop { offset: *offset, color: *color }).collect();
+    stops.sort_by(|a, b| a.offset.total_cmp(&b.offset));
+    stops
+}
+
+/// Keeps the part of a convex polygon where `inside` is not negative.
+fn clip(polygon: &[[f32; 2]], inside: impl Fn([f32; 2]) -> f32) -> Vec<[f32; 2]> {
+    let mut result = Vec::with_capacity(polygon.len() + 1);
+    for i in 0..polygon.len() {
+        let (p, q) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+        let (dp, dq) = (inside(p), inside(q));
+        if dp >= 0.0 {
+            result.push(p);
+        }
+        if (dp >= 0.0) != (dq >= 0.0) {
+            let t = dp / (dp - dq);
+            result.push([p[0] + (q[0] - p[0]) * t, p[1] + (q[1] - p[1]) * t]);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+    const GREEN: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+    const BLUE: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+    const SQUARE: [[f32; 2]; 4] = [[0.0, 0.0], [100.0, 0.0], [100.0, 100.0], [0.0, 100.0]];
+
+    fn area(vertices: &[MeshVertex], indices: &[u32]) -> f32 {
+        indices.chunks(3).map(|t| {
+            let [a, b, c] = [0, 1, 2].map(|k| vertices[t[k] as usize].position);
+            ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])).abs() / 2.0
+        }).sum()
+    }
+
+    fn assert_close(a: [f32; 4], b: [f32; 4]) {
+        assert!(a.iter().zip(b.iter()).all(|(x, y)| (x - y).abs() < 1e-4), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn colors_clamp_to_the_end_stops() {
+        let gradient = Gradient::horizontal((0.0, 0.0, 100.0, 10.0), RED, BLUE);
+        assert_eq!(gradient.color_at([-50.0, 0.0]), RED);
+        assert_eq!(gradient.color_at([150.0, 0.0]), BLUE);
+        assert_close(gradient.color_at([25.0, 5.0]), [0.75, 0.0, 0.25, 1.0]);
+    }
+
+    #[test]
+    fn stops_are_sorted_by_offset() {
+        let gradient = Gradient::linear([0.0, 0.0], [10.0, 0.0], &[(1.0, BLUE), (0.0, RED), (0.5, GREEN)]);
+        let offsets: Vec<f32> = gradient.stops().iter().map(|stop| stop.offset).collect();
+        assert_eq!(offsets, vec![0.0, 0.5, 1.0]);
+        assert_eq!(gradient.color_at([5.0, 0.0]), GREEN);
+    }
+
+    #[test]
+    fn coincident_stops_make_a_hard_edge() {
+        let gradient = Gradient::linear([0.0, 0.0], [10.0, 0.0], &[(0.0, RED), (0.5, RED), (0.5, BLUE), (1.0, BLUE)]);
+        assert_eq!(gradient.color_at([5.0, 0.0]), RED);
+        assert_eq!(gradient.color_at([5.01, 0.0]), BLUE);
+    }
+
+    #[test]
+    fn degenerate_gradients_use_the_first_stop() {
+        let linear = Gradient::linear([5.0, 5.0], [5.0, 5.0], &[(0.0, RED), (1.0, BLUE)]);
+        assert_eq!(linear.color_at([50.0, 50.0]), RED);
+        let radial = Gradient::radial([5.0, 5.0], 0.0, &[(0.0, RED), (1.0, BLUE)]);
+        assert_eq!(radial.color_at([50.0, 50.0]), RED);
+        let empty = Gradient::linear([0.0, 0.0], [1.0, 0.0], &[]);
+        assert_eq!(empty.color_at([0.5, 0.0]), [0.0; 4]);
+    }
+
+    #[test]
+    fn linear_meshes_are_cut_at_every_stop() {
+        let gradient = Gradient::linear([0.0, 0.0], [100.0, 0.0], &[(0.0, RED), (0.25, GREEN), (0.75, GREEN), (1.0, BLUE)]);
+        let (vertices, indices) = gradient.mesh(&SQUARE);
+        // Three quad bands of two triangles each
+        assert_eq!(indices.len(), 3 * 2 * 3);
+        assert!((area(&vertices, &indices) - 10000.0).abs() < 0.1);
+        for vertex in vertices.iter() {
+            assert_eq!(vertex.color, gradient.color_at(vertex.position));
+        }
+        assert!(vertices.iter().any(|vertex| vertex.position[0] == 25.0 && vertex.color == GREEN));
+    }
+
+    #[test]
+    fn linear_meshes_skip_stops_outside_the_shape() {
+        let gradient = Gradient::linear([0.0, 0.0], [1000.0, 0.0], &[(0.0, RED), (0.5, GREEN), (1.0, BLUE)]);
+        let (vertices, indices) = gradient.mesh(&SQUARE);
+        assert_eq!(indices.len(), 6);
+        assert!((area(&vertices, &indices) - 10000.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn radial_meshes_are_subdivided() {
+        let gradient = Gradient::radial([50.0, 50.0], 50.0, &[(0.0, RED), (1.0, BLUE)]);
+        let (vertices, indices) = gradient.mesh(&SQUARE);
+        assert!(indices.iter().all(|&index| (index as usize) < vertices.len()));
+        assert!((area(&vertices, &indices) - 10000.0).abs() < 0.1);
+        let edge = |p: [f32; 2], q: [f32; 2]| ((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2)).sqrt();
+        for t in indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| vertices[t[k] as usize].position);
+            assert!(edge(a, b).max(edge(b, c)).max(edge(c, a)) <= RADIAL_STEP + 1e-3);
+        }
+        let center = vertices.iter().find(|vertex| vertex.position == [50.0, 50.0]).unwrap();
+        assert_eq!(center.color, RED);
+    }
+
+    #[test]
+    fn shapes_with_fewer_than_three_points_are_empty() {
+        let gradient = Gradient::radial([0.0, 0.0], 10.0, &[(0.0, RED), (1.0, BLUE)]);
+        let (vertices, indices) = gradient.mesh(&SQUARE[..2]);
+        assert!(vertices.is_empty() && indices.is_empty());
+    }
+}
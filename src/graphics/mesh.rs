@@ -0,0 +1,31 @@
+/// A vertex of a mesh drawn with `Renderer::draw_mesh`.
+///
+/// Unlike `TextVertex`, the position is in screen pixels; the renderer converts it
+/// to normalized device coordinates when the frame is presented.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeshVertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+    /// Normalized texture coordinates. Ignored for untextured meshes.
+    pub tex_coords: [f32; 2],
+}
+
+impl MeshVertex {
+    pub fn new(position: [f32; 2], color: [f32; 4], tex_coords: [f32; 2]) -> MeshVertex {
+        MeshVertex { position, color, tex_coords }
+    }
+
+    /// Returns an untextured vertex.
+    pub fn colored(position: [f32; 2], color: [f32; 4]) -> MeshVertex {
+        MeshVertex { position, color, tex_coords: [0.0, 0.0] }
+    }
+}
+
+/// Returns triangle list indices for a convex polygon of `count` vertices starting at `first`.
+pub fn fan_indices(first: u32, count: u32) -> Vec<u32> {
+    let mut indices = Vec::with_capacity(count.saturating_sub(2) as usize * 3);
+    for i in 1..count.saturating_sub(1) {
+        indices.extend_from_slice(&[first, first + i, first + i + 1]);
+    }
+    indices
+}
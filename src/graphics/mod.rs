@@ -1,6 +1,21 @@
+pub mod animated_sprite;
+pub mod atlas;
 pub mod barn_gfx;
+pub mod capture;
+pub mod clip;
 pub mod color;
 pub mod fill_type;
+pub mod gradient;
+pub mod mesh;
+pub mod nine_slice;
+pub mod parallax;
+pub mod render_settings;
+pub mod renderer;
+pub mod render_stats;
+pub mod software_renderer;
+pub mod sprite_instance;
+pub mod sprite_sheet;
+pub mod trail;
 pub mod wgpu_renderer;
 pub mod wgpu_sprite;
 pub mod texture;
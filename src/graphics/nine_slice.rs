@@ -0,0 +1,270 @@
+use crate::graphics::wgpu_sprite::WgpuSprite;
+use crate::graphics::Rect;
+
+/// How a slice fills the space between the fixed corners.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SliceFill {
+    /// Scale the source region to cover the whole area.
+    Stretch,
+    /// Repeat the source region at its native size, cropping the last repetition.
+    Tile,
+}
+
+/// A texture region split into fixed corners, edges and a center so it can be
+/// drawn at any size without distorting the corners. Used for UI panels and buttons.
+#[derive(Clone)]
+pub struct NineSlice {
+    pub texture_path: String,
+    pub texture_size: (u32, u32),
+    /// Region of the texture containing the whole panel.
+    pub src: Rect,
+    /// Border insets in source pixels.
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+    pub edge_fill: SliceFill,
+    pub center_fill: SliceFill,
+    /// Scale applied to the borders and tiles, e.g. 2.0 for doubled pixel art.
+    pub scale: f32,
+    pub color: [f32; 4],
+}
+
+impl NineSlice {
+
+    /// Returns a nine-slice with the given border insets, in source pixels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use barn::graphics::nine_slice::NineSlice;
+    /// let panel = NineSlice::new("ui/panel.png", (48, 48), (0.0, 0.0, 48.0, 48.0), 16.0, 16.0, 16.0, 16.0);
+    /// // Corners, edges and center of a 200x100 dialog.
+    /// assert_eq!(panel.slices((10.0, 10.0, 200.0, 100.0)).len(), 9);
+    /// ```
+    pub fn new(texture_path: &str, texture_size: (u32, u32), src: Rect, left: f32, right: f32, top: f32, bottom: f32) -> NineSlice {
+        NineSlice {
+            texture_path: texture_path.to_string(),
+            texture_size,
+            src,
+            left,
+            right,
+            top,
+            bottom,
+            edge_fill: SliceFill::Stretch,
+            center_fill: SliceFill::Stretch,
+            scale: 1.0,
+            color: [1.0, 1.0, 1.0, 1.0],
+        }
+    }
+
+    /// Returns a three-slice that only keeps its left and right caps fixed.
+    pub fn horizontal(texture_path: &str, texture_size: (u32, u32), src: Rect, left: f32, right: f32) -> NineSlice {
+        NineSlice::new(texture_path, texture_size, src, left, right, 0.0, 0.0)
+    }
+
+    /// Returns a three-slice that only keeps its top and bottom caps fixed.
+    pub fn vertical(texture_path: &str, texture_size: (u32, u32), src: Rect, top: f32, bottom: f32) -> NineSlice {
+        NineSlice::new(texture_path, texture_size, src, 0.0, 0.0, top, bottom)
+    }
+
+    pub fn with_edge_fill(mut self, fill: SliceFill) -> NineSlice {
+        self.edge_fill = fill;
+        self
+    }
+
+    pub fn with_center_fill(mut self, fill: SliceFill) -> NineSlice {
+        self.center_fill = fill;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> NineSlice {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> NineSlice {
+        self.color = color;
+        self
+    }
+
+    /// Splits the panel into sprites covering `dst`. The renderer creates their
+    /// vertex buffers when they are drawn.
+    pub fn slices(&self, dst: Rect) -> Vec<WgpuSprite> {
+        let (sx, sy, sw, sh) = self.src;
+        let (dx, dy, dw, dh) = dst;
+
+        // Shrink the borders proportionally when the destination is too small to fit them.
+        let mut left = self.left * self.scale;
+        let mut right = self.right * self.scale;
+        let mut top = self.top * self.scale;
+        let mut bottom = self.bottom * self.scale;
+        if left + right > dw && left + right > 0.0 {
+            let fit = dw / (left + right);
+            left *= fit;
+            right *= fit;
+        }
+        if top + bottom > dh && top + bottom > 0.0 {
+            let fit = dh / (top + bottom);
+            top *= fit;
+            bottom *= fit;
+        }
+
+        let src_columns = [(sx, self.left), (sx + self.left, sw - self.left - self.right), (sx + sw - self.right, self.right)];
+        let src_rows = [(sy, self.top), (sy + self.top, sh - self.top - self.bottom), (sy + sh - self.bottom, self.bottom)];
+        let dst_columns = [(dx, left), (dx + left, dw - left - right), (dx + dw - right, right)];
+        let dst_rows = [(dy, top), (dy + top, dh - top - bottom), (dy + dh - bottom, bottom)];
+
+        // Three-slices have no borders on one axis, so nothing repeats along it.
+        let has_columns = self.left > 0.0 || self.right > 0.0;
+        let has_rows = self.top > 0.0 || self.bottom > 0.0;
+
+        let mut sprites = Vec::new();
+        for row in 0..3 {
+            for column in 0..3 {
+                let src = (src_columns[column].0, src_rows[row].0, src_columns[column].1, src_rows[row].1);
+                let dst = (dst_columns[column].0, dst_rows[row].0, dst_columns[column].1, dst_rows[row].1);
+                if src.2 <= 0.0 || src.3 <= 0.0 || dst.2 <= 0.0 || dst.3 <= 0.0 {
+                    continue;
+                }
+                let fill = match (row == 1, column == 1) {
+                    (true, true) => self.center_fill,
+                    (false, false) => SliceFill::Stretch,
+                    _ => self.edge_fill,
+                };
+                match fill {
+                    SliceFill::Stretch => sprites.push(self.sprite(src, dst)),
+                    // Edges only repeat along their length; corners are never tiled.
+                    SliceFill::Tile => self.tile(src, dst, column == 1 && has_columns, row == 1 && has_rows, &mut sprites),
+                }
+            }
+        }
+        sprites
+    }
+
+    fn sprite(&self, src: Rect, dst: Rect) -> WgpuSprite {
+        WgpuSprite::deferred(src, dst, self.color, &self.texture_path, self.texture_size)
+    }
+
+    fn tile(&self, src: Rect, dst: Rect, repeat_x: bool, repeat_y: bool, sprites: &mut Vec<WgpuSprite>) {
+        let tile_w = if repeat_x { src.2 * self.scale } else { dst.2 };
+        let tile_h = if repeat_y { src.3 * self.scale } else { dst.3 };
+        if tile_w <= 0.0 || tile_h <= 0.0 {
+            sprites.push(self.sprite(src, dst));
+            return;
+        }
+        let mut y = 0.0;
+        while y < dst.3 {
+            let h = tile_h.min(dst.3 - y);
+            let mut x = 0.0;
+            while x < dst.2 {
+                let w = tile_w.min(dst.2 - x);
+                // Crop the source so partial tiles are not squashed.
+                let src_w = if repeat_x { w / self.scale } else { src.2 };
+                let src_h = if repeat_y { h / self.scale } else { src.3 };
+                sprites.push(self.sprite((src.0, src.1, src_w, src_h), (dst.0 + x, dst.1 + y, w, h)));
+                x += tile_w;
+            }
+            y += tile_h;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn panel() -> NineSlice {
+        NineSlice::new("panel.png", (48, 48), (0.0, 0.0, 48.0, 48.0), 16.0, 16.0, 16.0, 16.0)
+    }
+
+    fn rects(sprites: &[WgpuSprite]) -> Vec<(Rect, Rect)> {
+        sprites.iter().map(|sprite| (sprite.src, sprite.dst)).collect()
+    }
+
+    #[test]
+    fn stretch_keeps_corners_and_scales_the_rest() {
+        let slices = rects(&panel().slices((10.0, 10.0, 200.0, 100.0)));
+        assert_eq!(slices, vec![
+            ((0.0, 0.0, 16.0, 16.0), (10.0, 10.0, 16.0, 16.0)),
+            ((16.0, 0.0, 16.0, 16.0), (26.0, 10.0, 168.0, 16.0)),
+            ((32.0, 0.0, 16.0, 16.0), (194.0, 10.0, 16.0, 16.0)),
+            ((0.0, 16.0, 16.0, 16.0), (10.0, 26.0, 16.0, 68.0)),
+            ((16.0, 16.0, 16.0, 16.0), (26.0, 26.0, 168.0, 68.0)),
+            ((32.0, 16.0, 16.0, 16.0), (194.0, 26.0, 16.0, 68.0)),
+            ((0.0, 32.0, 16.0, 16.0), (10.0, 94.0, 16.0, 16.0)),
+            ((16.0, 32.0, 16.0, 16.0), (26.0, 94.0, 168.0, 16.0)),
+            ((32.0, 32.0, 16.0, 16.0), (194.0, 94.0, 16.0, 16.0)),
+        ]);
+    }
+
+    #[test]
+    fn scale_grows_the_borders() {
+        let slices = rects(&panel().with_scale(2.0).slices((0.0, 0.0, 100.0, 100.0)));
+        assert_eq!(slices[0], ((0.0, 0.0, 16.0, 16.0), (0.0, 0.0, 32.0, 32.0)));
+        assert_eq!(slices[4], ((16.0, 16.0, 16.0, 16.0), (32.0, 32.0, 36.0, 36.0)));
+        assert_eq!(slices[8], ((32.0, 32.0, 16.0, 16.0), (68.0, 68.0, 32.0, 32.0)));
+    }
+
+    #[test]
+    fn tile_repeats_edges_along_their_length_and_crops_the_last_tile() {
+        let slices = rects(&panel().with_edge_fill(SliceFill::Tile).slices((0.0, 0.0, 72.0, 48.0)));
+        let top: Vec<(Rect, Rect)> = slices.iter().copied().filter(|(src, dst)| src.1 == 0.0 && dst.0 >= 16.0 && dst.0 < 56.0).collect();
+        assert_eq!(top, vec![
+            ((16.0, 0.0, 16.0, 16.0), (16.0, 0.0, 16.0, 16.0)),
+            ((16.0, 0.0, 16.0, 16.0), (32.0, 0.0, 16.0, 16.0)),
+            ((16.0, 0.0, 8.0, 16.0), (48.0, 0.0, 8.0, 16.0)),
+        ]);
+        // The side edges are only 16 pixels tall here, so they fit in one tile.
+        assert!(slices.contains(&((0.0, 16.0, 16.0, 16.0), (0.0, 16.0, 16.0, 16.0))));
+        // The center still stretches.
+        assert!(slices.contains(&((16.0, 16.0, 16.0, 16.0), (16.0, 16.0, 40.0, 16.0))));
+        assert_eq!(slices.len(), 4 + 3 + 3 + 1 + 1 + 1);
+    }
+
+    #[test]
+    fn tiled_center_repeats_on_both_axes_at_scale() {
+        let panel = panel().with_center_fill(SliceFill::Tile).with_scale(0.5);
+        let center: Vec<(Rect, Rect)> = rects(&panel.slices((0.0, 0.0, 36.0, 28.0)))
+            .into_iter()
+            .filter(|(src, _)| src.0 == 16.0 && src.1 == 16.0)
+            .collect();
+        // The 20x12 center is covered by 8x8 tiles, cropped on the right and bottom.
+        assert_eq!(center.len(), 3 * 2);
+        assert_eq!(center[0], ((16.0, 16.0, 16.0, 16.0), (8.0, 8.0, 8.0, 8.0)));
+        assert_eq!(center[2], ((16.0, 16.0, 8.0, 16.0), (24.0, 8.0, 4.0, 8.0)));
+        assert_eq!(center[5], ((16.0, 16.0, 8.0, 8.0), (24.0, 16.0, 4.0, 4.0)));
+    }
+
+    #[test]
+    fn undersized_destination_shrinks_the_borders() {
+        let slices = rects(&panel().slices((0.0, 0.0, 20.0, 8.0)));
+        // Only the corners remain, squeezed to fit; the edges and center have no room.
+        assert_eq!(slices, vec![
+            ((0.0, 0.0, 16.0, 16.0), (0.0, 0.0, 10.0, 4.0)),
+            ((32.0, 0.0, 16.0, 16.0), (10.0, 0.0, 10.0, 4.0)),
+            ((0.0, 32.0, 16.0, 16.0), (0.0, 4.0, 10.0, 4.0)),
+            ((32.0, 32.0, 16.0, 16.0), (10.0, 4.0, 10.0, 4.0)),
+        ]);
+    }
+
+    #[test]
+    fn horizontal_three_slice_stretches_caps_vertically() {
+        let bar = NineSlice::horizontal("bar.png", (30, 10), (0.0, 0.0, 30.0, 10.0), 10.0, 10.0);
+        assert_eq!(rects(&bar.slices((0.0, 0.0, 100.0, 20.0))), vec![
+            ((0.0, 0.0, 10.0, 10.0), (0.0, 0.0, 10.0, 20.0)),
+            ((10.0, 0.0, 10.0, 10.0), (10.0, 0.0, 80.0, 20.0)),
+            ((20.0, 0.0, 10.0, 10.0), (90.0, 0.0, 10.0, 20.0)),
+        ]);
+    }
+
+    #[test]
+    fn three_slice_tiles_only_along_its_length() {
+        let bar = NineSlice::vertical("bar.png", (10, 30), (0.0, 0.0, 10.0, 30.0), 10.0, 10.0)
+            .with_center_fill(SliceFill::Tile);
+        let slices = rects(&bar.slices((0.0, 0.0, 40.0, 45.0)));
+        let middle: Vec<Rect> = slices.iter().filter(|(src, _)| src.1 == 10.0).map(|(_, dst)| *dst).collect();
+        assert_eq!(middle, vec![(0.0, 10.0, 40.0, 10.0), (0.0, 20.0, 40.0, 10.0), (0.0, 30.0, 40.0, 5.0)]);
+        assert_eq!(slices.len(), 5);
+    }
+}
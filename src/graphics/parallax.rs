@@ -0,0 +1,255 @@
+use crate::graphics::renderer::Renderer;
+use crate::graphics::wgpu_sprite::WgpuSprite;
+use crate::graphics::Rect;
+
+/// Upper bound on tiles drawn per layer, in case a layer is tiny compared to the screen.
+const MAX_TILES: usize = 4096;
+
+/// One scrolling image of a `ParallaxBackground`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParallaxLayer {
+    pub texture_path: String,
+    /// Region of the texture to draw.
+    pub src: Rect,
+    /// Size of one copy of the image in world pixels. Defaults to the size of `src`.
+    pub size: (f32, f32),
+    /// How much the layer follows the camera on each axis: 0.0 stays fixed on screen,
+    /// 1.0 moves with the world and values in between appear further away.
+    pub scroll_factor: [f32; 2],
+    /// Automatic scrolling in pixels per second, e.g. for drifting clouds.
+    pub velocity: [f32; 2],
+    pub repeat_x: bool,
+    pub repeat_y: bool,
+    /// Position of the layer's first copy in world pixels.
+    pub offset: [f32; 2],
+    pub color: [f32; 4],
+    scroll: [f32; 2],
+}
+
+impl ParallaxLayer {
+    pub fn new(texture_path: &str, src: Rect) -> ParallaxLayer {
+        ParallaxLayer {
+            texture_path: texture_path.to_string(),
+            src,
+            size: (src.2, src.3),
+            scroll_factor: [1.0, 1.0],
+            velocity: [0.0, 0.0],
+            repeat_x: false,
+            repeat_y: false,
+            offset: [0.0, 0.0],
+            color: [1.0, 1.0, 1.0, 1.0],
+            scroll: [0.0, 0.0],
+        }
+    }
+
+    pub fn with_size(mut self, width: f32, height: f32) -> ParallaxLayer {
+        self.size = (width, height);
+        self
+    }
+
+    pub fn with_scroll_factor(mut self, x: f32, y: f32) -> ParallaxLayer {
+        self.scroll_factor = [x, y];
+        self
+    }
+
+    pub fn with_velocity(mut self, x: f32, y: f32) -> ParallaxLayer {
+        self.velocity = [x, y];
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat_x: bool, repeat_y: bool) -> ParallaxLayer {
+        self.repeat_x = repeat_x;
+        self.repeat_y = repeat_y;
+        self
+    }
+
+    pub fn with_offset(mut self, x: f32, y: f32) -> ParallaxLayer {
+        self.offset = [x, y];
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> ParallaxLayer {
+        self.color = color;
+        self
+    }
+
+    /// Advances the automatic scrolling. Repeating axes wrap so the offset stays small.
+    pub fn update(&mut self, dt: f32) {
+        let size = [self.size.0, self.size.1];
+        let repeat = [self.repeat_x, self.repeat_y];
+        for axis in 0..2 {
+            self.scroll[axis] += self.velocity[axis] * dt;
+            if repeat[axis] && size[axis] > 0.0 {
+                self.scroll[axis] = self.scroll[axis].rem_euclid(size[axis]);
+            }
+        }
+    }
+
+    /// Returns the screen rectangles covered by copies of the layer for a camera
+    /// centered on `camera` at `zoom`, on a screen of `screen` pixels.
+    ///
+    /// The layer sees the camera moved `scroll_factor` of the way from the screen
+    /// center to `camera`, and is scaled by `1 + (zoom - 1) * scroll_factor[0]` so
+    /// distant layers zoom less.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use barn::graphics::parallax::ParallaxLayer;
+    ///
+    /// let hills = ParallaxLayer::new("hills.png", (0.0, 0.0, 100.0, 50.0))
+    ///     .with_scroll_factor(0.5, 0.0)
+    ///     .with_repeat(true, false);
+    /// // Moving the camera 40 pixels right of the screen center scrolls the hills 20 pixels left.
+    /// let tiles = hills.tiles([140.0, 100.0], 1.0, (200.0, 200.0));
+    /// assert_eq!(tiles[0], (-20.0, 0.0, 100.0, 50.0));
+    /// assert_eq!(tiles.len(), 3);
+    /// ```
+    pub fn tiles(&self, camera: [f32; 2], zoom: f32, screen: (f32, f32)) -> Vec<Rect> {
+        let half = [screen.0 * 0.5, screen.1 * 0.5];
+        let scale = 1.0 + (zoom - 1.0) * self.scroll_factor[0];
+        let (width, height) = (self.size.0 * scale, self.size.1 * scale);
+        if width <= 0.0 || height <= 0.0 {
+            return Vec::new();
+        }
+        let origin: [f32; 2] = std::array::from_fn(|axis| {
+            let layer_camera = half[axis] + (camera[axis] - half[axis]) * self.scroll_factor[axis];
+            half[axis] + (self.offset[axis] + self.scroll[axis] - layer_camera) * scale
+        });
+        let span = |origin: f32, size: f32, screen: f32, repeat: bool| -> Vec<f32> {
+            if !repeat {
+                return if origin < screen && origin + size > 0.0 { vec![origin] } else { Vec::new() };
+            }
+            let first = origin - (origin / size).ceil() * size;
+            (0..).map(|i| first + i as f32 * size).take_while(|start| *start < screen).take(MAX_TILES).collect()
+        };
+        let columns = span(origin[0], width, screen.0, self.repeat_x);
+        let rows = span(origin[1], height, screen.1, self.repeat_y);
+        rows.iter()
+            .flat_map(|y| columns.iter().map(move |x| (*x, *y, width, height)))
+            .take(MAX_TILES)
+            .collect()
+    }
+}
+
+/// A stack of parallax layers drawn back to front.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParallaxBackground {
+    pub layers: Vec<ParallaxLayer>,
+}
+
+impl ParallaxBackground {
+    pub fn new() -> ParallaxBackground {
+        ParallaxBackground { layers: Vec::new() }
+    }
+
+    /// Adds a layer in front of the existing ones.
+    pub fn with_layer(mut self, layer: ParallaxLayer) -> ParallaxBackground {
+        self.layers.push(layer);
+        self
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for layer in self.layers.iter_mut() {
+            layer.update(dt);
+        }
+    }
+
+    /// Draws every layer for a camera centered on the world position `camera`. A
+    /// background that never moves with a camera can pass the screen center and a
+    /// zoom of 1.0. Layers whose texture is not loaded are skipped.
+    pub fn draw(&self, renderer: &mut dyn Renderer, camera: [f32; 2], zoom: f32) {
+        let (width, height) = renderer.size();
+        for layer in self.layers.iter() {
+            let Some(texture_size) = renderer.texture_size(&layer.texture_path) else {
+                continue;
+            };
+            for dst in layer.tiles(camera, zoom, (width as f32, height as f32)) {
+                renderer.draw_sprite(WgpuSprite::deferred(layer.src, dst, layer.color, &layer.texture_path, texture_size));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: (f32, f32) = (200.0, 200.0);
+    const CENTER: [f32; 2] = [100.0, 100.0];
+
+    fn layer() -> ParallaxLayer {
+        ParallaxLayer::new("layer.png", (0.0, 0.0, 100.0, 50.0))
+    }
+
+    #[test]
+    fn scroll_factor_scales_camera_movement() {
+        let camera = [150.0, 140.0];
+        assert_eq!(layer().with_scroll_factor(0.0, 0.0).tiles(camera, 1.0, SCREEN), vec![(0.0, 0.0, 100.0, 50.0)]);
+        assert_eq!(layer().tiles(camera, 1.0, SCREEN), vec![(-50.0, -40.0, 100.0, 50.0)]);
+        assert_eq!(layer().with_scroll_factor(0.5, 0.0).tiles(camera, 1.0, SCREEN), vec![(-25.0, 0.0, 100.0, 50.0)]);
+    }
+
+    #[test]
+    fn offset_places_the_first_copy() {
+        let tiles = layer().with_offset(30.0, 60.0).tiles(CENTER, 1.0, SCREEN);
+        assert_eq!(tiles, vec![(30.0, 60.0, 100.0, 50.0)]);
+    }
+
+    #[test]
+    fn zoom_scales_around_the_screen_center_by_scroll_factor() {
+        let square = layer().with_size(100.0, 100.0);
+        assert_eq!(square.tiles(CENTER, 2.0, SCREEN), vec![(-100.0, -100.0, 200.0, 200.0)]);
+        let far = square.clone().with_scroll_factor(0.5, 0.5);
+        assert_eq!(far.tiles(CENTER, 2.0, SCREEN), vec![(-50.0, -50.0, 150.0, 150.0)]);
+        // Layers fixed on screen ignore the zoom
+        let fixed = square.with_scroll_factor(0.0, 0.0);
+        assert_eq!(fixed.tiles(CENTER, 2.0, SCREEN), vec![(0.0, 0.0, 100.0, 100.0)]);
+    }
+
+    #[test]
+    fn non_repeating_layer_disappears_off_screen() {
+        assert!(layer().tiles([1000.0, 100.0], 1.0, SCREEN).is_empty());
+        assert!(layer().tiles([100.0, 300.0], 1.0, SCREEN).is_empty());
+        // Partly visible copies are kept
+        assert_eq!(layer().tiles([180.0, 100.0], 1.0, SCREEN), vec![(-80.0, 0.0, 100.0, 50.0)]);
+    }
+
+    #[test]
+    fn repeating_layer_covers_the_screen() {
+        let tiles = layer().with_repeat(true, false).tiles([1000.0, 100.0], 1.0, SCREEN);
+        assert_eq!(tiles, vec![(0.0, 0.0, 100.0, 50.0), (100.0, 0.0, 100.0, 50.0)]);
+
+        let tiles = layer().with_repeat(true, true).tiles([120.0, 110.0], 1.0, SCREEN);
+        let columns: Vec<f32> = tiles.iter().filter(|tile| tile.1 == tiles[0].1).map(|tile| tile.0).collect();
+        let rows: Vec<f32> = tiles.iter().filter(|tile| tile.0 == tiles[0].0).map(|tile| tile.1).collect();
+        assert_eq!(columns, vec![-20.0, 80.0, 180.0]);
+        assert_eq!(rows, vec![-10.0, 40.0, 90.0, 140.0, 190.0]);
+        assert_eq!(tiles.len(), 15);
+    }
+
+    #[test]
+    fn negative_camera_positions_wrap_the_first_tile() {
+        // The layer origin lands right of the screen edge, so the copies before it fill the screen.
+        let tiles = layer().with_repeat(true, false).tiles([-130.0, 100.0], 1.0, SCREEN);
+        assert_eq!(tiles, vec![(-70.0, 0.0, 100.0, 50.0), (30.0, 0.0, 100.0, 50.0), (130.0, 0.0, 100.0, 50.0)]);
+        assert!(layer().tiles([-130.0, 100.0], 1.0, SCREEN).is_empty());
+
+        let tiles = layer().with_repeat(false, true).tiles([100.0, -25.0], 1.0, SCREEN);
+        assert_eq!(tiles[0], (0.0, -25.0, 100.0, 50.0));
+        assert_eq!(tiles.len(), 5);
+    }
+
+    #[test]
+    fn velocity_scrolls_and_wraps_repeating_axes() {
+        let mut clouds = layer().with_velocity(-30.0, 10.0).with_repeat(true, false).with_scroll_factor(0.0, 0.0);
+        clouds.update(1.0);
+        let tiles = clouds.tiles(CENTER, 1.0, SCREEN);
+        assert_eq!(tiles[0], (-30.0, 10.0, 100.0, 50.0));
+        for _ in 0..10 {
+            clouds.update(1.0);
+        }
+        // Ten more seconds wrap the horizontal scroll back into one image width
+        assert_eq!(clouds.tiles(CENTER, 1.0, SCREEN)[0], (-30.0, 110.0, 100.0, 50.0));
+    }
+}
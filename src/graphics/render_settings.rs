@@ -0,0 +1,145 @@
+use serde::Deserialize;
+
+/// How presentation is synchronized with the display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VSync {
+    /// Wait for vertical blank. Never tears and is supported everywhere.
+    On,
+    /// Present as soon as a frame is ready without tearing, replacing queued frames.
+    /// Falls back to relaxed vsync, then to `On`.
+    Adaptive,
+    /// Present immediately, allowing tearing. Falls back to `Adaptive`, then to `On`.
+    Off,
+}
+
+impl VSync {
+    /// Present modes to try, most preferred first. `Fifo` is always available.
+    fn preferred_modes(self) -> &'static [wgpu::PresentMode] {
+        use wgpu::PresentMode::*;
+        match self {
+            VSync::On => &[Fifo],
+            VSync::Adaptive => &[Mailbox, FifoRelaxed, Fifo],
+            VSync::Off => &[Immediate, Mailbox, FifoRelaxed, Fifo],
+        }
+    }
+}
+
+/// Which GPU to prefer on systems with more than one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerProfile {
+    Default,
+    LowPower,
+    HighPerformance,
+}
+
+impl From<PowerProfile> for wgpu::PowerPreference {
+    fn from(profile: PowerProfile) -> wgpu::PowerPreference {
+        match profile {
+            PowerProfile::Default => wgpu::PowerPreference::None,
+            PowerProfile::LowPower => wgpu::PowerPreference::LowPower,
+            PowerProfile::HighPerformance => wgpu::PowerPreference::HighPerformance,
+        }
+    }
+}
+
+/// The graphics API used by the renderer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphicsBackend {
+    /// Let wgpu pick from every backend available on the platform.
+    Auto,
+    Vulkan,
+    Metal,
+    Dx12,
+    /// OpenGL or OpenGL ES.
+    Gl,
+}
+
+impl From<GraphicsBackend> for wgpu::Backends {
+    fn from(backend: GraphicsBackend) -> wgpu::Backends {
+        match backend {
+            GraphicsBackend::Auto => wgpu::Backends::all(),
+            GraphicsBackend::Vulkan => wgpu::Backends::VULKAN,
+            GraphicsBackend::Metal => wgpu::Backends::METAL,
+            GraphicsBackend::Dx12 => wgpu::Backends::DX12,
+            GraphicsBackend::Gl => wgpu::Backends::GL,
+        }
+    }
+}
+
+/// Renderer options chosen at startup.
+///
+/// `vsync` and `msaa_samples` can also be changed on a running renderer with
+/// `Renderer::apply_settings`, e.g. from a state's settings menu; the power profile
+/// and backend select the GPU and only take effect when the renderer is created.
+///
+/// # Examples
+///
+/// ```
+/// use barn::graphics::render_settings::{GraphicsBackend, RenderSettings, VSync};
+///
+/// let settings = RenderSettings::default()
+///     .with_vsync(VSync::Off)
+///     .with_msaa(4)
+///     .with_backend(GraphicsBackend::Vulkan);
+/// assert_eq!(settings.msaa_samples, 4);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RenderSettings {
+    pub vsync: VSync,
+    /// Samples per pixel. 1 disables multisampling; unsupported counts are lowered
+    /// to the nearest count the GPU supports.
+    pub msaa_samples: u32,
+    pub power_profile: PowerProfile,
+    pub backend: GraphicsBackend,
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings {
+            vsync: VSync::On,
+            msaa_samples: 1,
+            power_profile: PowerProfile::Default,
+            backend: GraphicsBackend::Auto,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn with_vsync(mut self, vsync: VSync) -> RenderSettings {
+        self.vsync = vsync;
+        self
+    }
+
+    pub fn with_msaa(mut self, samples: u32) -> RenderSettings {
+        self.msaa_samples = samples;
+        self
+    }
+
+    pub fn with_power_profile(mut self, power_profile: PowerProfile) -> RenderSettings {
+        self.power_profile = power_profile;
+        self
+    }
+
+    pub fn with_backend(mut self, backend: GraphicsBackend) -> RenderSettings {
+        self.backend = backend;
+        self
+    }
+
+    /// Picks the first present mode for `vsync` that the surface supports.
+    pub fn present_mode(&self, available: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        self.vsync.preferred_modes().iter()
+            .copied()
+            .find(|mode| available.contains(mode))
+            .unwrap_or(wgpu::PresentMode::Fifo)
+    }
+
+    /// Picks the highest supported sample count that does not exceed `msaa_samples`.
+    pub fn sample_count(&self, supported: &[u32]) -> u32 {
+        supported.iter()
+            .copied()
+            .filter(|count| *count <= self.msaa_samples.max(1))
+            .max()
+            .unwrap_or(1)
+    }
+}
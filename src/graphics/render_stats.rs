@@ -0,0 +1,182 @@
+use std::ops::AddAssign;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+/// Counters describing the cost of one presented frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    pub pipeline_switches: u32,
+    pub bind_group_switches: u32,
+    pub vertices: u32,
+    /// Estimated bytes of all loaded textures, including the font atlas.
+    pub texture_memory: u64,
+    /// Vertex buffers created on the GPU while building the frame.
+    pub buffer_allocations: u32,
+    pub buffer_bytes: u64,
+    /// GPU time in milliseconds from the start of the frame's first render pass,
+    /// including render targets, to the end of its last. `None` when the adapter does
+    /// not support timestamp queries. Lags a frame or two behind the other counters
+    /// because it is read back without stalling.
+    pub gpu_time_ms: Option<f32>,
+}
+
+impl RenderStats {
+    /// Returns a single-line summary suitable for an on-screen debug overlay.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use barn::graphics::render_stats::RenderStats;
+    /// let stats = RenderStats { draw_calls: 12, vertices: 72, ..Default::default() };
+    /// assert!(stats.summary().starts_with("DRAWS 12"));
+    /// ```
+    pub fn summary(&self) -> String {
+        let gpu = match self.gpu_time_ms {
+            Some(ms) => format!("{:.2}MS", ms),
+            None => "N/A".to_string(),
+        };
+        format!(
+            "DRAWS {} PIPES {} BINDS {} VERTS {} BUFS {} TEX {}KB GPU {}",
+            self.draw_calls,
+            self.pipeline_switches,
+            self.bind_group_switches,
+            self.vertices,
+            self.buffer_allocations,
+            self.texture_memory / 1024,
+            gpu
+        )
+    }
+
+    pub(crate) fn record_buffer(&mut self, bytes: u64) {
+        self.buffer_allocations += 1;
+        self.buffer_bytes += bytes;
+    }
+}
+
+impl AddAssign for RenderStats {
+    fn add_assign(&mut self, other: RenderStats) {
+        self.draw_calls += other.draw_calls;
+        self.pipeline_switches += other.pipeline_switches;
+        self.bind_group_switches += other.bind_group_switches;
+        self.vertices += other.vertices;
+        self.texture_memory += other.texture_memory;
+        self.buffer_allocations += other.buffer_allocations;
+        self.buffer_bytes += other.buffer_bytes;
+        self.gpu_time_ms = match (self.gpu_time_ms, other.gpu_time_ms) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+/// Measures the GPU time of a frame's render passes with timestamp queries. Results
+/// are copied into a staging buffer and collected on a later frame once the buffer
+/// has been mapped.
+pub(crate) struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    map_state: Arc<AtomicU8>,
+    map_pending: bool,
+    copied: bool,
+    period: f32,
+}
+
+impl GpuTimer {
+    const QUERY_BYTES: u64 = 2 * std::mem::size_of::<u64>() as u64;
+    const MAP_WAITING: u8 = 0;
+    const MAP_DONE: u8 = 1;
+    const MAP_FAILED: u8 = 2;
+
+    /// Returns `None` if the device was created without `Features::TIMESTAMP_QUERY`.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<GpuTimer> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Pass Timestamps"),
+            ty: wgpu::QueryType::Timestamp,
+            count: 2,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Resolve Buffer"),
+            size: Self::QUERY_BYTES,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Readback Buffer"),
+            size: Self::QUERY_BYTES,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Some(GpuTimer {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            map_state: Arc::new(AtomicU8::new(Self::MAP_WAITING)),
+            map_pending: false,
+            copied: false,
+            period: queue.get_timestamp_period(),
+        })
+    }
+
+    /// Returns the timestamp writes for a pass that is the frame's first pass, its
+    /// last pass or both, so the measurement spans every pass in between.
+    pub fn timestamp_writes(&self, first: bool, last: bool) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        (first || last).then(|| wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: first.then_some(0),
+            end_of_pass_write_index: last.then_some(1),
+        })
+    }
+
+    /// Resolves the queries written by the pass. The results are only copied for
+    /// readback when the previous measurement has been collected.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.resolve_query_set(&self.query_set, 0..2, &self.resolve_buffer, 0);
+        self.copied = !self.map_pending;
+        if self.copied {
+            encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, Self::QUERY_BYTES);
+        }
+    }
+
+    /// Starts mapping the results copied this frame. Call after the encoder was submitted.
+    pub fn after_submit(&mut self) {
+        if !self.copied {
+            return;
+        }
+        self.copied = false;
+        self.map_pending = true;
+        let map_state = self.map_state.clone();
+        self.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let state = if result.is_ok() { Self::MAP_DONE } else { Self::MAP_FAILED };
+            map_state.store(state, Ordering::Release);
+        });
+    }
+
+    /// Returns the most recent pass duration in milliseconds if a measurement finished.
+    pub fn collect(&mut self, device: &wgpu::Device) -> Option<f32> {
+        if !self.map_pending {
+            return None;
+        }
+        device.poll(wgpu::Maintain::Poll);
+        match self.map_state.swap(Self::MAP_WAITING, Ordering::Acquire) {
+            Self::MAP_DONE => {},
+            Self::MAP_FAILED => {
+                self.map_pending = false;
+                return None;
+            },
+            _ => return None,
+        }
+        let elapsed = {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&data);
+            timestamps[1].saturating_sub(timestamps[0])
+        };
+        self.readback_buffer.unmap();
+        self.map_pending = false;
+        Some(elapsed as f32 * self.period / 1_000_000.0)
+    }
+}
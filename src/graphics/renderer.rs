@@ -0,0 +1,173 @@
+use image::RgbaImage;
+
+use crate::graphics::atlas::TextureAtlas;
+use crate::graphics::capture::RecordingFormat;
+use crate::graphics::clip::ClipMask;
+use crate::graphics::gradient::Gradient;
+use crate::graphics::mesh::MeshVertex;
+use crate::graphics::nine_slice::NineSlice;
+use crate::graphics::render_settings::RenderSettings;
+use crate::graphics::render_stats::RenderStats;
+use crate::graphics::sprite_instance::SpriteInstance;
+use crate::graphics::trail::Trail;
+use crate::graphics::wgpu_sprite::WgpuSprite;
+use crate::graphics::Rect;
+use crate::math::vector2::Vector2;
+
+/// The drawing API available to states, implemented by the GPU renderer and by the
+/// CPU `SoftwareRenderer`.
+///
+/// Draw calls are recorded during `State::render` and drawn in order when the frame
+/// is presented. Positions are in pixels with the origin at the top left.
+pub trait Renderer {
+    /// Returns the width and height of the render target in pixels.
+    fn size(&self) -> (u32, u32);
+
+    fn set_clear_color(&mut self, r: f64, g: f64, b: f64, a: f64);
+
+    /// Loads an image so sprites and meshes can refer to it by `path`.
+    fn load_texture(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Registers an image that is already in memory under `path`, replacing any
+    /// texture loaded under that name.
+    fn add_texture(&mut self, path: &str, image: RgbaImage);
+
+    /// Registers every page of an atlas under its key so atlas sprites can be drawn.
+    fn load_atlas(&mut self, atlas: &TextureAtlas) {
+        for page in atlas.pages.iter() {
+            self.add_texture(&page.key, page.image.clone());
+        }
+    }
+
+    /// Returns the size of a loaded texture.
+    fn texture_size(&self, path: &str) -> Option<(u32, u32)>;
+
+    /// Draws a sprite. Sprites whose texture is not loaded are skipped.
+    fn draw_sprite(&mut self, sprite: WgpuSprite);
+
+    fn draw_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: [f32; 4]);
+
+    /// Draws a line of text with its baseline at `y`.
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, font_size: f32, color: [f32; 4]);
+
+    /// Draws an indexed triangle list, optionally textured.
+    fn draw_mesh(&mut self, vertices: &[MeshVertex], indices: &[u32], texture_path: Option<&str>);
+
+    /// Draws everything up to the matching `end_render_target` into a screen-sized
+    /// texture named `name` instead of the frame. The texture is cleared to the clear
+    /// color and can then be drawn like a loaded texture, e.g. to compose screen
+    /// transitions. Draw into a target before drawing with it.
+    fn begin_render_target(&mut self, name: &str);
+
+    fn end_render_target(&mut self);
+
+    /// Drops everything drawn since the last frame was presented.
+    fn clear_draw_commands(&mut self);
+
+    /// Restricts subsequent drawing to `rect`, intersected with any active clip.
+    fn push_scissor(&mut self, rect: Rect);
+
+    /// Restricts subsequent drawing to the union of `shapes`, intersected with any
+    /// active clip. Useful for circular minimaps and reveal effects. Scissors and
+    /// masks clip every kind of drawing, text included, on every renderer.
+    fn push_mask(&mut self, shapes: Vec<ClipMask>);
+
+    /// Removes the most recently pushed scissor or mask.
+    fn pop_clip(&mut self);
+
+    /// Keeps a copy of the next presented frame for `take_captured_frame`.
+    fn capture_next_frame(&mut self);
+
+    /// Returns the frame captured after `capture_next_frame`, once it is available.
+    fn take_captured_frame(&mut self) -> Option<RgbaImage>;
+
+    /// Saves the next presented frame as an image. The format follows the file extension.
+    fn save_screenshot(&mut self, path: &str);
+
+    /// Starts writing every presented frame to disk, replacing any active recording.
+    fn start_recording(&mut self, format: RecordingFormat) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Writes the frames still pending, then closes the recording.
+    fn stop_recording(&mut self) -> Result<(), Box<dyn std::error::Error>>;
+
+    fn is_recording(&self) -> bool;
+
+    /// Returns the counters of the last presented frame.
+    fn stats(&self) -> RenderStats;
+
+    /// Returns the renderer options currently in effect.
+    fn settings(&self) -> RenderSettings;
+
+    /// Applies new renderer options, e.g. from a settings menu.
+    fn apply_settings(&mut self, settings: RenderSettings);
+
+    /// Draws a one-line summary of the last frame's stats.
+    fn draw_stats_overlay(&mut self, x: f32, y: f32, font_size: f32, color: [f32; 4]) {
+        let summary = self.stats().summary();
+        self.draw_text(&summary, x, y, font_size, color);
+    }
+
+    /// Draws copies of one texture region, each with its own position, size,
+    /// rotation and tint. Instances whose texture is not loaded are skipped.
+    fn draw_sprite_instances(&mut self, texture_path: &str, src: Rect, instances: &[SpriteInstance]) {
+        let Some((tw, th)) = self.texture_size(texture_path) else {
+            return;
+        };
+        let (tw, th) = (tw as f32, th as f32);
+        let (u0, v0, u1, v1) = (src.0 / tw, src.1 / th, (src.0 + src.2) / tw, (src.1 + src.3) / th);
+        let mut vertices = Vec::with_capacity(instances.len() * 4);
+        let mut indices = Vec::with_capacity(instances.len() * 6);
+        for instance in instances.iter() {
+            // Half axes of the rotated quad; rotation is clockwise with y pointing down.
+            let (sin, cos) = instance.rotation.sin_cos();
+            let [w, h] = [instance.size[0] * 0.5, instance.size[1] * 0.5];
+            let (ax, ay) = ([w * cos, w * sin], [-h * sin, h * cos]);
+            let [cx, cy] = instance.position;
+            let base = vertices.len() as u32;
+            vertices.extend_from_slice(&[
+                MeshVertex::new([cx - ax[0] - ay[0], cy - ax[1] - ay[1]], instance.color, [u0, v0]),
+                MeshVertex::new([cx + ax[0] - ay[0], cy + ax[1] - ay[1]], instance.color, [u1, v0]),
+                MeshVertex::new([cx + ax[0] + ay[0], cy + ax[1] + ay[1]], instance.color, [u1, v1]),
+                MeshVertex::new([cx - ax[0] + ay[0], cy - ax[1] + ay[1]], instance.color, [u0, v1]),
+            ]);
+            indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+        self.draw_mesh(&vertices, &indices, Some(texture_path));
+    }
+
+    /// Draws a nine-slice (or three-slice) panel stretched over `dst`.
+    fn draw_nine_slice(&mut self, slice: &NineSlice, dst: Rect) {
+        for sprite in slice.slices(dst) {
+            self.draw_sprite(sprite);
+        }
+    }
+
+    fn draw_gradient_rect(&mut self, rect: Rect, gradient: &Gradient) {
+        let (x, y, w, h) = rect;
+        let (vertices, indices) = gradient.mesh(&[[x, y], [x + w, y], [x + w, y + h], [x, y + h]]);
+        self.draw_mesh(&vertices, &indices, None);
+    }
+
+    fn draw_gradient_circle(&mut self, x: f32, y: f32, radius: f32, gradient: &Gradient) {
+        let circle = ClipMask::Circle { x, y, radius }.triangles().unwrap_or_default();
+        // The circle is a fan around its center; every third vertex is on the rim.
+        let rim: Vec<[f32; 2]> = circle.chunks_exact(3).map(|triangle| triangle[1]).collect();
+        let (vertices, indices) = gradient.mesh(&rim);
+        self.draw_mesh(&vertices, &indices, None);
+    }
+
+    /// Fills a convex polygon with a gradient.
+    fn draw_gradient_polygon(&mut self, points: &[Vector2], gradient: &Gradient) {
+        let points: Vec<[f32; 2]> = points.iter().map(|p| [p.x, p.y]).collect();
+        let (vertices, indices) = gradient.mesh(&points);
+        self.draw_mesh(&vertices, &indices, None);
+    }
+
+    /// Draws a trail as a triangle strip, textured when the trail has a texture.
+    fn draw_trail(&mut self, trail: &Trail) {
+        let (vertices, indices) = trail.mesh();
+        if !indices.is_empty() {
+            self.draw_mesh(&vertices, &indices, trail.texture_path.as_deref());
+        }
+    }
+}
@@ -0,0 +1,39 @@
+@group(0) @binding(0) var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1) var s_diffuse: sampler;
+
+// One corner of the shared unit quad, centered on the origin.
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) tex_coords: vec2<f32>,
+}
+
+// Per-instance transform in normalized device coordinates.
+struct InstanceInput {
+    @location(3) center: vec2<f32>,
+    @location(4) axis_x: vec2<f32>,
+    @location(5) axis_y: vec2<f32>,
+    @location(6) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput, instance: InstanceInput) -> VertexOutput {
+    var out: VertexOutput;
+    let position = instance.center + in.position.x * instance.axis_x + in.position.y * instance.axis_y;
+    out.position = vec4<f32>(position, 0.0, 1.0);
+    out.color = in.color * instance.color;
+    out.tex_coords = in.tex_coords;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let tex = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return tex * in.color;
+}
@@ -26,4 +26,14 @@ fn vs_main(in: VertexInput) -> VertexOutput {
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let tex = textureSample(t_diffuse, s_diffuse, in.tex_coords);
     return tex * in.color;
-} 
\ No newline at end of file
+} 
+
+// Used when drawing sprites into the stencil buffer as clipping masks.
+@fragment
+fn fs_mask(in: VertexOutput) -> @location(0) vec4<f32> {
+    let tex = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    if (tex.a * in.color.a < 0.5) {
+        discard;
+    }
+    return tex * in.color;
+}
@@ -0,0 +1,705 @@
+use std::collections::HashMap;
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont, point};
+use image::RgbaImage;
+
+use crate::graphics::capture::{FrameCapture, RecordingFormat};
+use crate::graphics::clip::ClipMask;
+use crate::graphics::mesh::MeshVertex;
+use crate::graphics::render_settings::RenderSettings;
+use crate::graphics::render_stats::RenderStats;
+use crate::graphics::renderer::Renderer;
+use crate::graphics::wgpu_sprite::WgpuSprite;
+use crate::graphics::Rect;
+
+enum SoftwareCommand {
+    Sprite(WgpuSprite),
+    Mesh { vertices: Vec<MeshVertex>, indices: Vec<u32>, texture_path: Option<String> },
+    Text { text: String, x: f32, y: f32, font_size: f32, color: [f32; 4] },
+    PushScissor(Rect),
+    PushMask(Vec<ClipMask>),
+    PopClip,
+    BeginTarget(String),
+    EndTarget,
+}
+
+/// The frame or a render target being drawn during `present`.
+struct Layer {
+    name: Option<String>,
+    pixels: Vec<[f32; 4]>,
+    /// Which pixels may be drawn, one entry per pushed scissor or mask.
+    clips: Vec<Vec<bool>>,
+}
+
+/// A renderer that rasterizes on the CPU into an `RgbaImage`.
+///
+/// It needs no GPU or window, so states can be rendered in unit tests and offline
+/// tools. Blending happens in linear space and the frame is stored as sRGB, like the
+/// GPU renderer drawing to an sRGB surface. Textures are sampled with nearest
+/// filtering and text is drawn at the requested `font_size` once a font is loaded.
+///
+/// # Examples
+///
+/// ```
+/// use barn::graphics::renderer::Renderer;
+/// use barn::graphics::software_renderer::SoftwareRenderer;
+///
+/// let mut renderer = SoftwareRenderer::new(8, 8);
+/// renderer.set_clear_color(0.0, 0.0, 0.0, 1.0);
+/// renderer.draw_rect(2, 2, 4, 4, [1.0, 0.0, 0.0, 1.0]);
+/// let frame = renderer.present();
+/// assert_eq!(frame.get_pixel(3, 3).0, [255, 0, 0, 255]);
+/// assert_eq!(frame.get_pixel(0, 0).0, [0, 0, 0, 255]);
+/// ```
+pub struct SoftwareRenderer {
+    width: u32,
+    height: u32,
+    clear_color: [f32; 4],
+    textures: HashMap<String, RgbaImage>,
+    font: Option<FontArc>,
+    commands: Vec<SoftwareCommand>,
+    frame: RgbaImage,
+    capture: FrameCapture,
+    stats: RenderStats,
+    settings: RenderSettings,
+}
+
+impl SoftwareRenderer {
+    pub fn new(width: u32, height: u32) -> SoftwareRenderer {
+        SoftwareRenderer {
+            width,
+            height,
+            clear_color: [0.1, 0.2, 0.3, 1.0],
+            textures: HashMap::new(),
+            font: None,
+            commands: Vec::new(),
+            frame: RgbaImage::new(width, height),
+            capture: FrameCapture::default(),
+            stats: RenderStats::default(),
+            settings: RenderSettings::default(),
+        }
+    }
+
+    /// Loads the font used by `draw_text`. Without one, text is not drawn.
+    pub fn load_font(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let data = std::fs::read(path).map_err(|e| format!("Failed to read font file {}: {}", path, e))?;
+        self.font = Some(FontArc::try_from_vec(data).map_err(|e| format!("Failed to load font {}: {}", path, e))?);
+        Ok(())
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.frame = RgbaImage::new(width, height);
+    }
+
+    /// Draws the recorded commands into the frame and returns it.
+    pub fn present(&mut self) -> &RgbaImage {
+        let clear = vec![self.clear_color; (self.width * self.height) as usize];
+        let layer = |name: Option<String>| Layer { name, pixels: clear.clone(), clips: Vec::new() };
+        // The frame is at the bottom; each open render target is drawn into its own layer.
+        let mut layers = vec![layer(None)];
+        let mut stats = RenderStats::default();
+        let commands = std::mem::take(&mut self.commands);
+        for command in commands.iter() {
+            match command {
+                SoftwareCommand::BeginTarget(name) => {
+                    layers.push(layer(Some(name.clone())));
+                    continue;
+                },
+                SoftwareCommand::EndTarget => {
+                    if layers.len() > 1 {
+                        self.store_target(layers.pop().unwrap());
+                    }
+                    continue;
+                },
+                _ => {},
+            }
+            let Layer { pixels, clips, .. } = layers.last_mut().unwrap();
+            let clip = clips.last().map(Vec::as_slice);
+            match command {
+                SoftwareCommand::Sprite(sprite) => {
+                    if self.fill_sprite(pixels, sprite, clip) {
+                        stats.draw_calls += 1;
+                        stats.vertices += 6;
+                    }
+                },
+                SoftwareCommand::Mesh { vertices, indices, texture_path } => {
+                    let texture = match texture_path {
+                        Some(path) => match self.textures.get(path) {
+                            Some(texture) => Some(texture),
+                            None => continue,
+                        },
+                        None => None,
+                    };
+                    self.fill_triangles(pixels, vertices, indices, texture, clip);
+                    stats.draw_calls += 1;
+                    stats.vertices += indices.len() as u32;
+                },
+                SoftwareCommand::Text { text, x, y, font_size, color } => {
+                    if self.font.is_some() {
+                        self.fill_text(pixels, text, *x, *y, *font_size, *color, clip);
+                        stats.draw_calls += 1;
+                        stats.vertices += 6 * text.chars().filter(|c| !c.is_whitespace()).count() as u32;
+                    }
+                },
+                SoftwareCommand::PushScissor(rect) => {
+                    let clip = self.scissor_clip(*rect, clips.last());
+                    clips.push(clip);
+                },
+                SoftwareCommand::PushMask(shapes) => {
+                    let clip = self.mask_clip(shapes, clips.last());
+                    clips.push(clip);
+                },
+                SoftwareCommand::PopClip => {
+                    clips.pop();
+                },
+                SoftwareCommand::BeginTarget(_) | SoftwareCommand::EndTarget => {},
+            }
+        }
+        while layers.len() > 1 {
+            self.store_target(layers.pop().unwrap());
+        }
+
+        let frame = layers.pop().unwrap();
+        self.frame = encode_image(self.width, self.height, &frame.pixels);
+        stats.texture_memory = self.textures.values().map(|texture| texture.as_raw().len() as u64).sum();
+        self.stats = stats;
+        if self.capture.wants_frame() {
+            let request = self.capture.take_request();
+            if let Err(e) = self.capture.deliver(self.frame.clone(), request) {
+                log::error!("Failed to capture frame: {}", e);
+            }
+        }
+        &self.frame
+    }
+
+    /// Keeps a finished render target layer as a texture.
+    fn store_target(&mut self, layer: Layer) {
+        if let Some(name) = layer.name {
+            self.textures.insert(name, encode_image(self.width, self.height, &layer.pixels));
+        }
+    }
+
+    /// Returns the pixels inside `rect`, snapped to whole pixels like a GPU scissor,
+    /// that are also inside `current`.
+    fn scissor_clip(&self, rect: Rect, current: Option<&Vec<bool>>) -> Vec<bool> {
+        let x1 = rect.0.max(0.0) as u32;
+        let y1 = rect.1.max(0.0) as u32;
+        let x2 = (rect.0 + rect.2).max(0.0) as u32;
+        let y2 = (rect.1 + rect.3).max(0.0) as u32;
+        (0..self.width * self.height)
+            .map(|i| {
+                let (x, y) = (i % self.width, i / self.width);
+                x >= x1 && x < x2 && y >= y1 && y < y2 && current.is_none_or(|clip| clip[i as usize])
+            })
+            .collect()
+    }
+
+    /// Returns the pixels covered by any of `shapes` that are also inside `current`.
+    /// Sprite shapes cover their pixels with an alpha of at least one half.
+    fn mask_clip(&self, shapes: &[ClipMask], current: Option<&Vec<bool>>) -> Vec<bool> {
+        let mut coverage = vec![[0.0; 4]; (self.width * self.height) as usize];
+        let white = [1.0, 1.0, 1.0, 1.0];
+        for shape in shapes.iter() {
+            match (shape, shape.triangles()) {
+                (_, Some(triangles)) => {
+                    let vertices: Vec<MeshVertex> = triangles.iter().map(|point| MeshVertex::colored(*point, white)).collect();
+                    let indices: Vec<u32> = (0..vertices.len() as u32).collect();
+                    self.fill_triangles(&mut coverage, &vertices, &indices, None, None);
+                },
+                (ClipMask::Sprite(sprite), None) => {
+                    let mut layer = vec![[0.0; 4]; coverage.len()];
+                    let mut sprite = sprite.clone();
+                    sprite.color = white;
+                    self.fill_sprite(&mut layer, &sprite, None);
+                    for (covered, pixel) in coverage.iter_mut().zip(layer.iter()) {
+                        if pixel[3] >= 0.5 {
+                            covered[3] = 1.0;
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+        coverage.iter()
+            .enumerate()
+            .map(|(i, pixel)| pixel[3] >= 0.5 && current.is_none_or(|clip| clip[i]))
+            .collect()
+    }
+
+    /// Returns the last presented frame.
+    pub fn frame(&self) -> &RgbaImage {
+        &self.frame
+    }
+
+    /// Draws a sprite, returning false when its texture is not loaded.
+    fn fill_sprite(&self, pixels: &mut [[f32; 4]], sprite: &WgpuSprite, clip: Option<&[bool]>) -> bool {
+        let Some(texture) = sprite.get_texture_path().and_then(|path| self.textures.get(path)) else {
+            return false;
+        };
+        let (tw, th) = (texture.width() as f32, texture.height() as f32);
+        let ((sx, sy, sw, sh), (x, y, w, h)) = (sprite.src, sprite.dst);
+        let (u0, v0, u1, v1) = (sx / tw, sy / th, (sx + sw) / tw, (sy + sh) / th);
+        let quad = [
+            MeshVertex::new([x, y], sprite.color, [u0, v0]),
+            MeshVertex::new([x + w, y], sprite.color, [u1, v0]),
+            MeshVertex::new([x + w, y + h], sprite.color, [u1, v1]),
+            MeshVertex::new([x, y + h], sprite.color, [u0, v1]),
+        ];
+        self.fill_triangles(pixels, &quad, &[0, 1, 2, 0, 2, 3], Some(texture), clip);
+        true
+    }
+
+    /// Draws a triangle list. Only pixels marked visible in `clip` are written.
+    fn fill_triangles(&self, pixels: &mut [[f32; 4]], vertices: &[MeshVertex], indices: &[u32], texture: Option<&RgbaImage>, clip: Option<&[bool]>) {
+        for triangle in indices.chunks_exact(3) {
+            let corners = [triangle[0], triangle[1], triangle[2]].map(|i| vertices.get(i as usize));
+            if let [Some(a), Some(b), Some(c)] = corners {
+                self.fill_triangle(pixels, [a, b, c], texture, clip);
+            }
+        }
+    }
+
+    fn fill_triangle(&self, pixels: &mut [[f32; 4]], mut corners: [&MeshVertex; 3], texture: Option<&RgbaImage>, clip: Option<&[bool]>) {
+        let edge = |a: [f32; 2], b: [f32; 2], p: [f32; 2]| (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0]);
+        let mut area = edge(corners[0].position, corners[1].position, corners[2].position);
+        if area.abs() <= f32::EPSILON {
+            return;
+        }
+        if area < 0.0 {
+            corners.swap(1, 2);
+            area = -area;
+        }
+        let [a, b, c] = corners.map(|vertex| vertex.position);
+        // Pixels exactly on an edge belong to one of the two triangles sharing it.
+        let owns = |from: [f32; 2], to: [f32; 2]| {
+            let (dx, dy) = (to[0] - from[0], to[1] - from[1]);
+            dy < 0.0 || (dy == 0.0 && dx > 0.0)
+        };
+        let edges = [(b, c, owns(b, c)), (c, a, owns(c, a)), (a, b, owns(a, b))];
+
+        let min_x = a[0].min(b[0]).min(c[0]).floor().max(0.0) as u32;
+        let min_y = a[1].min(b[1]).min(c[1]).floor().max(0.0) as u32;
+        let max_x = (a[0].max(b[0]).max(c[0]).ceil().max(0.0) as u32).min(self.width);
+        let max_y = (a[1].max(b[1]).max(c[1]).ceil().max(0.0) as u32).min(self.height);
+        for py in min_y..max_y {
+            for px in min_x..max_x {
+                let index = (py * self.width + px) as usize;
+                if clip.is_some_and(|clip| !clip[index]) {
+                    continue;
+                }
+                let p = [px as f32 + 0.5, py as f32 + 0.5];
+                let mut weights = [0.0; 3];
+                let mut inside = true;
+                for (i, (from, to, owned)) in edges.iter().enumerate() {
+                    let w = edge(*from, *to, p);
+                    if w < 0.0 || (w == 0.0 && !owned) {
+                        inside = false;
+                        break;
+                    }
+                    weights[i] = w / area;
+                }
+                if !inside {
+                    continue;
+                }
+                let mut color: [f32; 4] = std::array::from_fn(|i| {
+                    corners[0].color[i] * weights[0] + corners[1].color[i] * weights[1] + corners[2].color[i] * weights[2]
+                });
+                if let Some(texture) = texture {
+                    let u = corners[0].tex_coords[0] * weights[0] + corners[1].tex_coords[0] * weights[1] + corners[2].tex_coords[0] * weights[2];
+                    let v = corners[0].tex_coords[1] * weights[0] + corners[1].tex_coords[1] * weights[1] + corners[2].tex_coords[1] * weights[2];
+                    let texel = sample(texture, u, v);
+                    for i in 0..4 {
+                        color[i] *= texel[i];
+                    }
+                }
+                blend(&mut pixels[index], color);
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn fill_text(&self, pixels: &mut [[f32; 4]], text: &str, x: f32, y: f32, font_size: f32, color: [f32; 4], clip: Option<&[bool]>) {
+        let Some(font) = self.font.as_ref() else {
+            return;
+        };
+        let scaled = font.as_scaled(PxScale::from(font_size));
+        let mut caret = x;
+        let mut previous = None;
+        for c in text.chars() {
+            let id = font.glyph_id(c);
+            if let Some(previous) = previous {
+                caret += scaled.kern(previous, id);
+            }
+            let glyph = id.with_scale_and_position(font_size, point(caret, y));
+            if let Some(outlined) = font.outline_glyph(glyph) {
+                let bounds = outlined.px_bounds();
+                outlined.draw(|gx, gy, coverage| {
+                    let (px, py) = (bounds.min.x as i32 + gx as i32, bounds.min.y as i32 + gy as i32);
+                    if px >= 0 && py >= 0 && (px as u32) < self.width && (py as u32) < self.height {
+                        let index = (py as u32 * self.width + px as u32) as usize;
+                        if clip.is_none_or(|clip| clip[index]) {
+                            let src = [color[0], color[1], color[2], color[3] * coverage];
+                            blend(&mut pixels[index], src);
+                        }
+                    }
+                });
+            }
+            caret += scaled.h_advance(id);
+            previous = Some(id);
+        }
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn set_clear_color(&mut self, r: f64, g: f64, b: f64, a: f64) {
+        self.clear_color = [r as f32, g as f32, b as f32, a as f32];
+    }
+
+    fn load_texture(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let image = image::open(path).map_err(|e| format!("Failed to load texture {}: {}", path, e))?;
+        self.textures.insert(path.to_string(), image.into_rgba8());
+        Ok(())
+    }
+
+    fn add_texture(&mut self, path: &str, image: RgbaImage) {
+        self.textures.insert(path.to_string(), image);
+    }
+
+    fn texture_size(&self, path: &str) -> Option<(u32, u32)> {
+        self.textures.get(path).map(|texture| texture.dimensions())
+    }
+
+    fn draw_sprite(&mut self, sprite: WgpuSprite) {
+        self.commands.push(SoftwareCommand::Sprite(sprite));
+    }
+
+    fn draw_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: [f32; 4]) {
+        let (x1, y1) = (x as f32, y as f32);
+        let (x2, y2) = (x1 + width as f32, y1 + height as f32);
+        self.commands.push(SoftwareCommand::Mesh {
+            vertices: vec![
+                MeshVertex::colored([x1, y1], color),
+                MeshVertex::colored([x2, y1], color),
+                MeshVertex::colored([x2, y2], color),
+                MeshVertex::colored([x1, y2], color),
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3],
+            texture_path: None,
+        });
+    }
+
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, font_size: f32, color: [f32; 4]) {
+        self.commands.push(SoftwareCommand::Text { text: text.to_string(), x, y, font_size, color });
+    }
+
+    fn draw_mesh(&mut self, vertices: &[MeshVertex], indices: &[u32], texture_path: Option<&str>) {
+        self.commands.push(SoftwareCommand::Mesh {
+            vertices: vertices.to_vec(),
+            indices: indices.to_vec(),
+            texture_path: texture_path.map(|path| path.to_string()),
+        });
+    }
+
+    fn begin_render_target(&mut self, name: &str) {
+        // Keep the target's size known before its first present, like the GPU renderer does.
+        if self.texture_size(name) != Some((self.width, self.height)) {
+            self.textures.insert(name.to_string(), RgbaImage::new(self.width, self.height));
+        }
+        self.commands.push(SoftwareCommand::BeginTarget(name.to_string()));
+    }
+
+    fn end_render_target(&mut self) {
+        self.commands.push(SoftwareCommand::EndTarget);
+    }
+
+    fn clear_draw_commands(&mut self) {
+        self.commands.clear();
+    }
+
+    fn push_scissor(&mut self, rect: Rect) {
+        self.commands.push(SoftwareCommand::PushScissor(rect));
+    }
+
+    fn push_mask(&mut self, shapes: Vec<ClipMask>) {
+        self.commands.push(SoftwareCommand::PushMask(shapes));
+    }
+
+    fn pop_clip(&mut self) {
+        self.commands.push(SoftwareCommand::PopClip);
+    }
+
+    fn capture_next_frame(&mut self) {
+        self.capture.capture_next_frame();
+    }
+
+    /// Returns the frame captured after `capture_next_frame`, as soon as it is presented.
+    fn take_captured_frame(&mut self) -> Option<RgbaImage> {
+        self.capture.take_captured_frame()
+    }
+
+    fn save_screenshot(&mut self, path: &str) {
+        self.capture.save_next_frame(path);
+    }
+
+    fn start_recording(&mut self, format: RecordingFormat) -> Result<(), Box<dyn std::error::Error>> {
+        self.capture.start_recording(format)
+    }
+
+    fn stop_recording(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.capture.stop_recording()
+    }
+
+    fn is_recording(&self) -> bool {
+        self.capture.is_recording()
+    }
+
+    /// Returns the draw calls, vertices and texture memory of the last presented frame.
+    fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    fn settings(&self) -> RenderSettings {
+        self.settings
+    }
+
+    /// Only remembers the settings; frames are drawn the same way whatever they are.
+    fn apply_settings(&mut self, settings: RenderSettings) {
+        self.settings = settings;
+    }
+}
+
+/// Stores linear colors as an sRGB image.
+fn encode_image(width: u32, height: u32, pixels: &[[f32; 4]]) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    for (pixel, color) in image.pixels_mut().zip(pixels.iter()) {
+        pixel.0 = [
+            linear_to_srgb(color[0]),
+            linear_to_srgb(color[1]),
+            linear_to_srgb(color[2]),
+            (color[3].clamp(0.0, 1.0) * 255.0).round() as u8,
+        ];
+    }
+    image
+}
+
+/// Samples the nearest texel, clamped to the edge, and decodes it to linear color.
+fn sample(texture: &RgbaImage, u: f32, v: f32) -> [f32; 4] {
+    let x = ((u * texture.width() as f32).floor() as i64).clamp(0, texture.width() as i64 - 1) as u32;
+    let y = ((v * texture.height() as f32).floor() as i64).clamp(0, texture.height() as i64 - 1) as u32;
+    let [r, g, b, a] = texture.get_pixel(x, y).0;
+    [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a as f32 / 255.0]
+}
+
+/// Blends `src` over `dst` the way the GPU pipelines' alpha blending does.
+fn blend(dst: &mut [f32; 4], src: [f32; 4]) {
+    let alpha = src[3].clamp(0.0, 1.0);
+    for i in 0..3 {
+        dst[i] = src[i] * alpha + dst[i] * (1.0 - alpha);
+    }
+    dst[3] = alpha + dst[3] * (1.0 - alpha);
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let c = value as f32 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let c = value.clamp(0.0, 1.0);
+    let encoded = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+    (encoded * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::gradient::Gradient;
+    use crate::graphics::nine_slice::NineSlice;
+    use crate::graphics::sprite_instance::SpriteInstance;
+
+    const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+    fn renderer() -> SoftwareRenderer {
+        let mut renderer = SoftwareRenderer::new(8, 8);
+        renderer.add_texture("white", RgbaImage::from_pixel(3, 3, image::Rgba([255, 255, 255, 255])));
+        renderer
+    }
+
+    fn is_white(renderer: &SoftwareRenderer, x: u32, y: u32) -> bool {
+        renderer.frame().get_pixel(x, y).0 == [255, 255, 255, 255]
+    }
+
+    #[test]
+    fn scissor_limits_drawing_to_its_rect() {
+        let mut renderer = renderer();
+        renderer.push_scissor((2.0, 2.0, 4.0, 4.0));
+        renderer.draw_rect(0, 0, 8, 8, WHITE);
+        renderer.present();
+        assert!(is_white(&renderer, 2, 2));
+        assert!(is_white(&renderer, 5, 5));
+        assert!(!is_white(&renderer, 1, 1));
+        assert!(!is_white(&renderer, 6, 6));
+    }
+
+    #[test]
+    fn scissor_clips_sprites_without_touching_earlier_drawing() {
+        let mut renderer = renderer();
+        renderer.draw_rect(0, 0, 8, 8, RED);
+        renderer.push_scissor((0.0, 0.0, 4.0, 8.0));
+        renderer.draw_sprite(WgpuSprite::deferred((0.0, 0.0, 3.0, 3.0), (0.0, 0.0, 8.0, 8.0), WHITE, "white", (3, 3)));
+        renderer.present();
+        assert!(is_white(&renderer, 3, 3));
+        assert_eq!(renderer.frame().get_pixel(4, 3).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn circle_mask_limits_drawing_to_the_circle() {
+        let mut renderer = renderer();
+        renderer.push_mask(vec![ClipMask::Circle { x: 4.0, y: 4.0, radius: 3.0 }]);
+        renderer.draw_rect(0, 0, 8, 8, WHITE);
+        renderer.present();
+        assert!(is_white(&renderer, 4, 4));
+        assert!(!is_white(&renderer, 0, 0));
+        assert!(!is_white(&renderer, 7, 0));
+    }
+
+    #[test]
+    fn masks_clip_text() {
+        let mut renderer = SoftwareRenderer::new(32, 16);
+        renderer.load_font("examples/resources/fonts/press-start/PressStart2P-vaV7.ttf").unwrap();
+        renderer.set_clear_color(0.0, 0.0, 0.0, 1.0);
+        let lit = |renderer: &SoftwareRenderer, columns: std::ops::Range<u32>| {
+            columns.flat_map(|x| (0..16).map(move |y| (x, y))).any(|(x, y)| renderer.frame().get_pixel(x, y).0[0] > 0)
+        };
+        renderer.push_mask(vec![ClipMask::Rect((0.0, 0.0, 16.0, 16.0))]);
+        renderer.draw_text("MM", 0.0, 12.0, 16.0, WHITE);
+        renderer.pop_clip();
+        renderer.present();
+        assert!(lit(&renderer, 0..16));
+        assert!(!lit(&renderer, 16..32));
+    }
+
+    #[test]
+    fn nested_clips_intersect() {
+        let mut renderer = renderer();
+        renderer.push_scissor((0.0, 0.0, 4.0, 8.0));
+        renderer.push_mask(vec![ClipMask::Rect((2.0, 0.0, 6.0, 4.0))]);
+        renderer.draw_rect(0, 0, 8, 8, WHITE);
+        renderer.present();
+        assert!(is_white(&renderer, 3, 1));
+        assert!(!is_white(&renderer, 1, 1));
+        assert!(!is_white(&renderer, 5, 1));
+        assert!(!is_white(&renderer, 3, 5));
+    }
+
+    #[test]
+    fn pop_clip_restores_the_previous_clip() {
+        let mut renderer = renderer();
+        renderer.push_scissor((0.0, 0.0, 4.0, 8.0));
+        renderer.push_scissor((0.0, 0.0, 2.0, 8.0));
+        renderer.pop_clip();
+        renderer.draw_rect(0, 0, 8, 8, WHITE);
+        renderer.pop_clip();
+        renderer.draw_rect(0, 6, 8, 2, RED);
+        renderer.present();
+        assert!(is_white(&renderer, 3, 0));
+        assert!(!is_white(&renderer, 4, 0));
+        assert_eq!(renderer.frame().get_pixel(7, 7).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn clips_end_with_the_frame() {
+        let mut renderer = renderer();
+        renderer.push_scissor((0.0, 0.0, 1.0, 1.0));
+        renderer.present();
+        renderer.draw_rect(0, 0, 8, 8, WHITE);
+        renderer.present();
+        assert!(is_white(&renderer, 7, 7));
+    }
+
+    #[test]
+    fn nine_slice_is_drawn_through_the_default_method() {
+        let mut renderer = renderer();
+        let slice = NineSlice::new("white", (3, 3), (0.0, 0.0, 3.0, 3.0), 1.0, 1.0, 1.0, 1.0);
+        renderer.draw_nine_slice(&slice, (1.0, 1.0, 6.0, 6.0));
+        renderer.present();
+        assert!(is_white(&renderer, 1, 1));
+        assert!(is_white(&renderer, 4, 4));
+        assert!(is_white(&renderer, 6, 6));
+        assert!(!is_white(&renderer, 0, 0));
+        assert!(!is_white(&renderer, 7, 7));
+    }
+
+    #[test]
+    fn gradient_rect_is_drawn_through_the_default_method() {
+        let mut renderer = renderer();
+        let rect = (0.0, 0.0, 8.0, 8.0);
+        renderer.draw_gradient_rect(rect, &Gradient::horizontal(rect, RED, [0.0, 0.0, 1.0, 1.0]));
+        renderer.present();
+        let left = renderer.frame().get_pixel(0, 4).0;
+        let right = renderer.frame().get_pixel(7, 4).0;
+        assert!(left[0] > left[2]);
+        assert!(right[2] > right[0]);
+    }
+
+    #[test]
+    fn sprite_instances_are_drawn_through_the_default_method() {
+        let mut renderer = renderer();
+        let instance = |x: f32, rotation: f32| SpriteInstance { position: [x, 4.0], size: [2.0, 2.0], rotation, color: WHITE };
+        renderer.draw_sprite_instances("white", (0.0, 0.0, 3.0, 3.0), &[instance(2.0, 0.0), instance(6.0, std::f32::consts::FRAC_PI_2)]);
+        renderer.present();
+        assert!(is_white(&renderer, 1, 3));
+        assert!(is_white(&renderer, 6, 4));
+        assert!(!is_white(&renderer, 4, 4));
+        assert!(!is_white(&renderer, 2, 0));
+    }
+
+    #[test]
+    fn stats_count_the_last_presented_frame() {
+        let mut renderer = renderer();
+        renderer.draw_rect(0, 0, 8, 8, WHITE);
+        renderer.draw_sprite(WgpuSprite::deferred((0.0, 0.0, 3.0, 3.0), (0.0, 0.0, 3.0, 3.0), WHITE, "white", (3, 3)));
+        renderer.draw_sprite(WgpuSprite::deferred((0.0, 0.0, 3.0, 3.0), (0.0, 0.0, 3.0, 3.0), WHITE, "missing", (3, 3)));
+        renderer.present();
+        let stats = renderer.stats();
+        assert_eq!(stats.draw_calls, 2);
+        assert_eq!(stats.vertices, 12);
+        assert_eq!(stats.texture_memory, 3 * 3 * 4);
+        renderer.present();
+        assert_eq!(renderer.stats().draw_calls, 0);
+    }
+
+    #[test]
+    fn captures_the_next_presented_frame() {
+        let mut renderer = renderer();
+        renderer.present();
+        assert!(renderer.take_captured_frame().is_none());
+        renderer.capture_next_frame();
+        renderer.draw_rect(0, 0, 8, 8, WHITE);
+        renderer.present();
+        let captured = renderer.take_captured_frame().unwrap();
+        assert_eq!(captured.as_raw(), renderer.frame().as_raw());
+        renderer.present();
+        assert!(renderer.take_captured_frame().is_none());
+    }
+
+    #[test]
+    fn records_presented_frames() {
+        let path = std::env::temp_dir().join(format!("barn_software_recording_{}.gif", std::process::id()));
+        let mut renderer = renderer();
+        renderer.start_recording(RecordingFormat::Gif(path.to_string_lossy().to_string())).unwrap();
+        assert!(renderer.is_recording());
+        renderer.present();
+        renderer.present();
+        renderer.stop_recording().unwrap();
+        assert!(!renderer.is_recording());
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
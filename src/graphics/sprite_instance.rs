@@ -0,0 +1,12 @@
+/// Placement of one copy of a sprite region drawn by `Renderer::draw_sprite_instances`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpriteInstance {
+    /// Center of the sprite in screen pixels.
+    pub position: [f32; 2],
+    /// Width and height in screen pixels.
+    pub size: [f32; 2],
+    /// Clockwise rotation around the center, in radians.
+    pub rotation: f32,
+    /// Tint multiplied with the texture.
+    pub color: [f32; 4],
+}
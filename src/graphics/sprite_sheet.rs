@@ -0,0 +1,484 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::graphics::wgpu_sprite::{WgpuSprite, WgpuSpriteAnimation, WgpuSpriteFrame};
+use crate::graphics::Rect;
+
+/// Frame duration used for exports that carry no timing, such as TexturePacker sheets.
+pub const DEFAULT_FRAME_DURATION: f32 = 0.1;
+
+/// A single frame of a sprite sheet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SheetFrame {
+    pub name: String,
+    /// Region of the sheet image.
+    pub src: Rect,
+    /// Duration in seconds.
+    pub duration: f32,
+    /// Size of the frame before transparent borders were trimmed.
+    pub source_size: (f32, f32),
+    /// Position of `src` within the untrimmed frame.
+    pub trim_offset: (f32, f32),
+    /// Normalized pivot, if the exporter provided one.
+    pub pivot: Option<(f32, f32)>,
+}
+
+/// Playback direction of an Aseprite tag.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagDirection {
+    #[default]
+    Forward,
+    Reverse,
+    PingPong,
+    #[serde(rename = "pingpong_reverse")]
+    PingPongReverse,
+}
+
+/// A named frame range, e.g. "run" or "jump".
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameTag {
+    pub name: String,
+    pub from: usize,
+    pub to: usize,
+    pub direction: TagDirection,
+    /// Number of times Aseprite repeats the tag; `None` loops forever.
+    pub repeat: Option<u32>,
+}
+
+/// The bounds of a slice on one frame. Keys apply from `frame` until the next key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SliceKey {
+    pub frame: usize,
+    pub bounds: Rect,
+    /// Nine-slice center, relative to `bounds`.
+    pub center: Option<Rect>,
+    /// Pivot point, relative to `bounds`.
+    pub pivot: Option<(f32, f32)>,
+}
+
+/// A named region of the canvas such as a hitbox or a nine-slice panel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SheetSlice {
+    pub name: String,
+    pub data: Option<String>,
+    pub keys: Vec<SliceKey>,
+}
+
+impl SheetSlice {
+
+    /// Returns the key in effect on `frame`.
+    pub fn key_for_frame(&self, frame: usize) -> Option<&SliceKey> {
+        self.keys.iter().rev().find(|key| key.frame <= frame).or(self.keys.first())
+    }
+}
+
+/// Frames, tags and slices loaded from an Aseprite or TexturePacker JSON export.
+pub struct SpriteSheet {
+    /// Path of the sheet image, resolved relative to the JSON file.
+    pub image: String,
+    pub size: (u32, u32),
+    pub frames: Vec<SheetFrame>,
+    pub tags: Vec<FrameTag>,
+    pub slices: Vec<SheetSlice>,
+    frame_names: HashMap<String, usize>,
+}
+
+impl SpriteSheet {
+
+    /// Loads an Aseprite (`Export Sprite Sheet` with JSON data) or TexturePacker
+    /// (JSON hash or array) export. The formats share the same layout; frames without
+    /// a `duration` get `DEFAULT_FRAME_DURATION`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use barn::graphics::sprite_sheet::SpriteSheet;
+    /// let sheet = SpriteSheet::from_file("examples/resources/images/debug_boy.json").unwrap();
+    /// let walk = sheet.tag("walk").unwrap();
+    /// assert_eq!(sheet.tag_frames(walk).len(), 4);
+    /// assert_eq!(sheet.frames[0].duration, 0.2);
+    /// ```
+    pub fn from_file(path: &str) -> Result<SpriteSheet, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        SpriteSheet::parse(&text, path).map_err(|e| format!("{}: {}", path, e).into())
+    }
+
+    fn parse(text: &str, path: &str) -> Result<SpriteSheet, Box<dyn std::error::Error>> {
+        let file: SheetFile = serde_json::from_str(text)?;
+        let meta = file.meta;
+        let image = Path::new(path).parent().unwrap_or(Path::new("")).join(&meta.image).to_string_lossy().into_owned();
+
+        // TexturePacker's hash format keys frames by name; the array format names them.
+        let mut frames = Vec::new();
+        match file.frames {
+            Value::Object(entries) => {
+                for (name, frame) in entries {
+                    frames.push(parse_frame(name, serde_json::from_value(frame)?)?);
+                }
+            }
+            Value::Array(entries) => {
+                for frame in entries {
+                    let frame: NamedFrameFile = serde_json::from_value(frame)?;
+                    frames.push(parse_frame(frame.filename, frame.frame)?);
+                }
+            }
+            _ => return Err("'frames' must be an object or an array".into()),
+        }
+
+        let mut tags = Vec::new();
+        for tag in meta.frame_tags {
+            if tag.from > tag.to || tag.to >= frames.len() {
+                return Err(format!("frame tag '{}' is out of range", tag.name).into());
+            }
+            // Aseprite writes the repeat count as a string.
+            let repeat = match tag.repeat {
+                Some(RepeatFile::Text(text)) => Some(text.parse()?),
+                Some(RepeatFile::Count(count)) => Some(count),
+                None => None,
+            }.filter(|count| *count > 0);
+            tags.push(FrameTag { name: tag.name, from: tag.from, to: tag.to, direction: tag.direction, repeat });
+        }
+
+        let slices = meta.slices.into_iter()
+            .map(|slice| SheetSlice {
+                name: slice.name,
+                data: slice.data,
+                keys: slice.keys.into_iter()
+                    .map(|key| SliceKey {
+                        frame: key.frame,
+                        bounds: key.bounds.into(),
+                        center: key.center.map(Rect::from),
+                        pivot: key.pivot.map(|p| (p.x, p.y)),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let frame_names = frames.iter().enumerate().map(|(i, frame)| (frame.name.clone(), i)).collect();
+        Ok(SpriteSheet { image, size: (meta.size.w, meta.size.h), frames, tags, slices, frame_names })
+    }
+
+    pub fn frame(&self, name: &str) -> Option<&SheetFrame> {
+        self.frame_names.get(name).map(|&i| &self.frames[i])
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&FrameTag> {
+        self.tags.iter().find(|tag| tag.name == name)
+    }
+
+    pub fn slice(&self, name: &str) -> Option<&SheetSlice> {
+        self.slices.iter().find(|slice| slice.name == name)
+    }
+
+    /// Returns the frame indices of one pass through a tag in playback order.
+    /// Ping-pong tags play their end frames once per pass.
+    pub fn tag_frames(&self, tag: &FrameTag) -> Vec<usize> {
+        let forward: Vec<usize> = (tag.from..=tag.to).collect();
+        let backward: Vec<usize> = forward.iter().rev().copied().collect();
+        let bounce = |first: &[usize], second: &[usize]| {
+            let mut order = first.to_vec();
+            if second.len() > 2 {
+                order.extend_from_slice(&second[1..second.len() - 1]);
+            }
+            order
+        };
+        match tag.direction {
+            TagDirection::Forward => forward,
+            TagDirection::Reverse => backward,
+            TagDirection::PingPong => bounce(&forward, &backward),
+            TagDirection::PingPongReverse => bounce(&backward, &forward),
+        }
+    }
+
+    /// Builds an animation from a tag. `dst` is where the untrimmed frame is drawn;
+    /// trimmed frames are offset and scaled to match.
+    pub fn animation(&self, tag: &str, dst: Rect, repeat: bool) -> Option<WgpuSpriteAnimation> {
+        let tag = self.tag(tag)?;
+        Some(self.animation_from_frames(&self.tag_frames(tag), dst, repeat))
+    }
+
+    /// Builds an animation playing every frame of the sheet in order.
+    pub fn animation_all(&self, dst: Rect, repeat: bool) -> WgpuSpriteAnimation {
+        let frames: Vec<usize> = (0..self.frames.len()).collect();
+        self.animation_from_frames(&frames, dst, repeat)
+    }
+
+    fn animation_from_frames(&self, indices: &[usize], dst: Rect, repeat: bool) -> WgpuSpriteAnimation {
+        let frames = indices.iter().map(|&i| self.sprite_frame(i, dst)).collect();
+        WgpuSpriteAnimation::new(frames, repeat)
+    }
+
+    /// Returns frame `index` as an animation frame whose untrimmed area covers `dst`.
+    pub fn sprite_frame(&self, index: usize, dst: Rect) -> WgpuSpriteFrame {
+        let frame = &self.frames[index];
+        WgpuSpriteFrame { src: frame.src, dst: self.frame_dst(frame, dst), duration: frame.duration }
+    }
+
+    /// Returns a sprite showing the named frame, with the untrimmed frame covering `dst`.
+    pub fn sprite(&self, name: &str, dst: Rect, color: [f32; 4]) -> Option<WgpuSprite> {
+        let frame = self.frame(name)?;
+        Some(WgpuSprite::deferred(frame.src, self.frame_dst(frame, dst), color, &self.image, self.size))
+    }
+
+    fn frame_dst(&self, frame: &SheetFrame, dst: Rect) -> Rect {
+        let scale_x = if frame.source_size.0 > 0.0 { dst.2 / frame.source_size.0 } else { 1.0 };
+        let scale_y = if frame.source_size.1 > 0.0 { dst.3 / frame.source_size.1 } else { 1.0 };
+        (
+            dst.0 + frame.trim_offset.0 * scale_x,
+            dst.1 + frame.trim_offset.1 * scale_y,
+            frame.src.2 * scale_x,
+            frame.src.3 * scale_y,
+        )
+    }
+}
+
+/// The JSON layout shared by Aseprite and TexturePacker exports.
+#[derive(Deserialize)]
+struct SheetFile {
+    frames: Value,
+    meta: MetaFile,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MetaFile {
+    image: String,
+    size: SizeFile,
+    #[serde(default)]
+    frame_tags: Vec<TagFile>,
+    #[serde(default)]
+    slices: Vec<SliceFile>,
+}
+
+#[derive(Deserialize)]
+struct SizeFile<T = u32> {
+    w: T,
+    h: T,
+}
+
+#[derive(Deserialize)]
+struct RectFile {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+impl From<RectFile> for Rect {
+    fn from(rect: RectFile) -> Rect {
+        (rect.x, rect.y, rect.w, rect.h)
+    }
+}
+
+#[derive(Deserialize)]
+struct PointFile {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FrameFile {
+    frame: RectFile,
+    sprite_source_size: Option<RectFile>,
+    source_size: Option<SizeFile<f32>>,
+    /// In milliseconds.
+    duration: Option<f32>,
+    pivot: Option<PointFile>,
+    #[serde(default)]
+    rotated: bool,
+}
+
+#[derive(Deserialize)]
+struct NamedFrameFile {
+    filename: String,
+    #[serde(flatten)]
+    frame: FrameFile,
+}
+
+#[derive(Deserialize)]
+struct TagFile {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: TagDirection,
+    repeat: Option<RepeatFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RepeatFile {
+    Text(String),
+    Count(u32),
+}
+
+#[derive(Deserialize)]
+struct SliceFile {
+    name: String,
+    data: Option<String>,
+    #[serde(default)]
+    keys: Vec<SliceKeyFile>,
+}
+
+#[derive(Deserialize)]
+struct SliceKeyFile {
+    frame: usize,
+    bounds: RectFile,
+    center: Option<RectFile>,
+    pivot: Option<PointFile>,
+}
+
+fn parse_frame(name: String, frame: FrameFile) -> Result<SheetFrame, Box<dyn std::error::Error>> {
+    if frame.rotated {
+        return Err(format!("frame '{}' is rotated; disable rotation in the exporter", name).into());
+    }
+    let src: Rect = frame.frame.into();
+    Ok(SheetFrame {
+        name,
+        src,
+        duration: frame.duration.map(|ms| ms / 1000.0).unwrap_or(DEFAULT_FRAME_DURATION),
+        source_size: frame.source_size.map(|size| (size.w, size.h)).unwrap_or((src.2, src.3)),
+        trim_offset: frame.sprite_source_size.map(|r| (r.x, r.y)).unwrap_or((0.0, 0.0)),
+        pivot: frame.pivot.map(|p| (p.x, p.y)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(text: &str) -> Result<SpriteSheet, Box<dyn std::error::Error>> {
+        SpriteSheet::parse(text, "sheets/hero.json")
+    }
+
+    const HASH: &str = r#"{
+        "frames": {
+            "idle 0.png": { "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "duration": 100 },
+            "idle 1.png": { "frame": { "x": 16, "y": 0, "w": 16, "h": 16 }, "duration": 250 }
+        },
+        "meta": { "image": "hero.png", "size": { "w": 32, "h": 16 } }
+    }"#;
+
+    const ARRAY: &str = r#"{
+        "frames": [
+            { "filename": "idle 0.png", "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "duration": 100 },
+            { "filename": "idle 1.png", "frame": { "x": 16, "y": 0, "w": 16, "h": 16 }, "duration": 250 }
+        ],
+        "meta": { "image": "hero.png", "size": { "w": 32, "h": 16 } }
+    }"#;
+
+    fn tagged(direction: &str) -> String {
+        let frames: Vec<String> = (0..4)
+            .map(|i| format!(r#"{{ "filename": "{}", "frame": {{ "x": {}, "y": 0, "w": 8, "h": 8 }} }}"#, i, i * 8))
+            .collect();
+        format!(
+            r#"{{ "frames": [{}], "meta": {{ "image": "hero.png", "size": {{ "w": 32, "h": 8 }},
+                "frameTags": [{{ "name": "run", "from": 0, "to": 3, "direction": "{}", "repeat": "2" }}] }} }}"#,
+            frames.join(","),
+            direction
+        )
+    }
+
+    #[test]
+    fn hash_and_array_frames_load_the_same() {
+        let hash = load(HASH).unwrap();
+        let array = load(ARRAY).unwrap();
+        assert_eq!(hash.frames, array.frames);
+        assert_eq!(hash.image, array.image);
+        assert_eq!(hash.size, (32, 16));
+        assert_eq!(hash.frame("idle 1.png").unwrap().src, (16.0, 0.0, 16.0, 16.0));
+        assert_eq!(hash.frames[1].duration, 0.25);
+        assert_eq!(Path::new(&hash.image), Path::new("sheets").join("hero.png"));
+    }
+
+    #[test]
+    fn missing_duration_uses_default() {
+        let sheet = load(&tagged("forward")).unwrap();
+        assert!(sheet.frames.iter().all(|frame| frame.duration == DEFAULT_FRAME_DURATION));
+    }
+
+    #[test]
+    fn array_frames_need_a_filename() {
+        let text = r#"{ "frames": [{ "frame": { "x": 0, "y": 0, "w": 8, "h": 8 } }], "meta": { "image": "a.png", "size": { "w": 8, "h": 8 } } }"#;
+        let error = load(text).err().unwrap();
+        assert!(error.to_string().contains("filename"));
+    }
+
+    #[test]
+    fn trimmed_frames_are_offset_within_the_source_size() {
+        let text = r#"{
+            "frames": { "jump": {
+                "frame": { "x": 10, "y": 20, "w": 12, "h": 6 },
+                "spriteSourceSize": { "x": 2, "y": 8, "w": 12, "h": 6 },
+                "sourceSize": { "w": 16, "h": 16 }
+            } },
+            "meta": { "image": "hero.png", "size": { "w": 64, "h": 64 } }
+        }"#;
+        let sheet = load(text).unwrap();
+        let frame = sheet.frame("jump").unwrap();
+        assert_eq!(frame.source_size, (16.0, 16.0));
+        assert_eq!(frame.trim_offset, (2.0, 8.0));
+
+        // Drawing the untrimmed 16x16 frame at 2x moves and scales the trimmed region.
+        let sprite = sheet.sprite("jump", (100.0, 50.0, 32.0, 32.0), [1.0; 4]).unwrap();
+        assert_eq!(sprite.src, (10.0, 20.0, 12.0, 6.0));
+        assert_eq!(sprite.dst, (104.0, 66.0, 24.0, 12.0));
+        assert_eq!(sheet.sprite_frame(0, (0.0, 0.0, 16.0, 16.0)).dst, (2.0, 8.0, 12.0, 6.0));
+    }
+
+    #[test]
+    fn tag_directions_order_frames() {
+        let order = |direction: &str| {
+            let sheet = load(&tagged(direction)).unwrap();
+            sheet.tag_frames(sheet.tag("run").unwrap())
+        };
+        assert_eq!(order("forward"), vec![0, 1, 2, 3]);
+        assert_eq!(order("reverse"), vec![3, 2, 1, 0]);
+        assert_eq!(order("pingpong"), vec![0, 1, 2, 3, 2, 1]);
+        assert_eq!(order("pingpong_reverse"), vec![3, 2, 1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn tag_repeat_is_read_from_a_string() {
+        let sheet = load(&tagged("forward")).unwrap();
+        let tag = sheet.tag("run").unwrap();
+        assert_eq!(tag.direction, TagDirection::Forward);
+        assert_eq!(tag.repeat, Some(2));
+    }
+
+    #[test]
+    fn invalid_tags_are_rejected() {
+        let error = load(&tagged("sideways")).err().unwrap();
+        assert!(error.to_string().contains("sideways"));
+        let error = load(&tagged("forward").replace(r#""to": 3"#, r#""to": 4"#)).err().unwrap();
+        assert!(error.to_string().contains("out of range"));
+    }
+
+    #[test]
+    fn rotated_frames_are_rejected() {
+        let text = HASH.replace(r#""duration": 100"#, r#""duration": 100, "rotated": true"#);
+        let error = load(&text).err().unwrap();
+        assert!(error.to_string().contains("'idle 0.png' is rotated"));
+    }
+
+    #[test]
+    fn slice_keys_apply_until_the_next_key() {
+        let text = HASH.replace(r#""size": { "w": 32, "h": 16 }"#, r#""size": { "w": 32, "h": 16 }, "slices": [{
+            "name": "hitbox", "data": "solid",
+            "keys": [
+                { "frame": 0, "bounds": { "x": 1, "y": 2, "w": 10, "h": 12 }, "pivot": { "x": 5, "y": 12 } },
+                { "frame": 1, "bounds": { "x": 3, "y": 2, "w": 10, "h": 12 } }
+            ]
+        }]"#);
+        let sheet = load(&text).unwrap();
+        let slice = sheet.slice("hitbox").unwrap();
+        assert_eq!(slice.data.as_deref(), Some("solid"));
+        assert_eq!(slice.key_for_frame(0).unwrap().pivot, Some((5.0, 12.0)));
+        assert_eq!(slice.key_for_frame(7).unwrap().bounds, (3.0, 2.0, 10.0, 12.0));
+    }
+}
@@ -2,8 +2,6 @@
 
 use std::sync::Arc;
 use wgpu::util::DeviceExt;
-use std::fs;
-use std::path::Path;
 
 pub struct Texture {
     pub texture: wgpu::Texture,
@@ -69,80 +67,67 @@ impl Texture {
         }
     }
     
+    /// Creates a texture that render passes can draw into, sprites can sample and
+    /// frame captures can copy from, in the same format as the surface.
+    pub fn render_target(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        label: &str,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&format!("Render Target: {}", label)),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        Self {
+            texture,
+            view,
+            sampler,
+            size,
+        }
+    }
+
     pub fn from_file(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         path: &str,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let abs_path = fs::canonicalize(Path::new(path));
-        println!("Attempting to load image from: {:?}", abs_path);
-        let img = image::open(path)?;
-        println!("Image color type for {}: {:?}", path, img.color());
+        let img = image::open(path)
+            .map_err(|e| format!("Failed to load texture {}: {}", path, e))?;
         let rgba = img.into_rgba8();
+        Ok(Self::from_image(device, queue, &rgba, path))
+    }
+
+    /// Uploads an image that is already in memory, e.g. a packed atlas page.
+    pub fn from_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: &image::RgbaImage,
+        label: &str,
+    ) -> Self {
         let dimensions = rgba.dimensions();
-        println!("Loaded image: {} ({}x{})", path, dimensions.0, dimensions.1);
-        let bytes = rgba.as_raw();
-        println!("First 16 bytes: {:?}", &bytes[..16.min(bytes.len())]);
-        // Debug: count non-zero pixels and print alpha histogram
-        let mut non_zero_pixels = 0;
-        let mut alpha_hist = [0u32; 256];
-        for chunk in bytes.chunks(4) {
-            if chunk[0] != 0 || chunk[1] != 0 || chunk[2] != 0 || chunk[3] != 0 {
-                non_zero_pixels += 1;
-            }
-            alpha_hist[chunk[3] as usize] += 1;
-        }
-        println!("Non-zero RGBA pixels: {} / {}", non_zero_pixels, bytes.len() / 4);
-        println!("Alpha histogram (nonzero only):");
-        for (i, &count) in alpha_hist.iter().enumerate() {
-            if count > 0 {
-                println!("  alpha={}: {}", i, count);
-            }
-        }
-        
-        // Debug: show some sample pixels from the image
-        println!("Sample pixels from {}:", path);
-        for i in 0..10.min(bytes.len() / 4) {
-            let pixel = &bytes[i * 4..(i + 1) * 4];
-            println!("  pixel {}: R={}, G={}, B={}, A={}", i, pixel[0], pixel[1], pixel[2], pixel[3]);
-        }
-        
-        // Also show some pixels from the middle of the image
-        let mid_pixel = bytes.len() / 8;
-        for i in 0..10 {
-            let pixel_idx = mid_pixel + i * 4;
-            if pixel_idx + 3 < bytes.len() {
-                let pixel = &bytes[pixel_idx..pixel_idx + 4];
-                println!("  mid pixel {}: R={}, G={}, B={}, A={}", i, pixel[0], pixel[1], pixel[2], pixel[3]);
-            }
-        }
-        
-        // For ground texture, show pixels from the bottom half (where the visible part is)
-        if path.contains("snow_ground") {
-            println!("=== GROUND TEXTURE DEBUG ===");
-            println!("Ground texture bottom half pixels:");
-            let bottom_start = bytes.len() / 2; // Start from middle (bottom half)
-            for i in 0..20 {
-                let pixel_idx = bottom_start + i * 4;
-                if pixel_idx + 3 < bytes.len() {
-                    let pixel = &bytes[pixel_idx..pixel_idx + 4];
-                    println!("  bottom pixel {}: R={}, G={}, B={}, A={}", i, pixel[0], pixel[1], pixel[2], pixel[3]);
-                }
-            }
-            
-            // Also show some pixels from the very bottom (last few rows)
-            println!("Ground texture very bottom pixels:");
-            let very_bottom_start = bytes.len() - (512 * 4 * 4); // Last 4 rows
-            for i in 0..20 {
-                let pixel_idx = very_bottom_start + i * 4;
-                if pixel_idx + 3 < bytes.len() {
-                    let pixel = &bytes[pixel_idx..pixel_idx + 4];
-                    println!("  very bottom pixel {}: R={}, G={}, B={}, A={}", i, pixel[0], pixel[1], pixel[2], pixel[3]);
-                }
-            }
-            println!("=== END GROUND TEXTURE DEBUG ===");
-        }
-        
         let size = wgpu::Extent3d {
             width: dimensions.0,
             height: dimensions.1,
@@ -150,7 +135,7 @@ impl Texture {
         };
         
         let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some(&format!("Texture: {}", path)),
+            label: Some(&format!("Texture: {}", label)),
             size,
             mip_level_count: 1,
             sample_count: 1,
@@ -167,7 +152,7 @@ impl Texture {
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
             },
-            &rgba,
+            rgba,
             wgpu::ImageDataLayout {
                 offset: 0,
                 bytes_per_row: Some(4 * dimensions.0),
@@ -188,11 +173,11 @@ impl Texture {
             ..Default::default()
         });
         
-        Ok(Self {
+        Self {
             texture,
             view,
             sampler,
             size,
-        })
+        }
     }
 } 
\ No newline at end of file
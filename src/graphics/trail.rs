@@ -0,0 +1,337 @@
+use std::collections::VecDeque;
+
+use crate::graphics::mesh::MeshVertex;
+
+/// How a trail texture is laid out along the trail.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrailTextureMode {
+    /// Stretch the texture once over the whole trail.
+    Stretch,
+    /// Repeat the texture every given number of pixels along the trail.
+    Tile(f32),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TrailPoint {
+    position: [f32; 2],
+    age: f32,
+}
+
+/// A ribbon following a moving point, e.g. for sword swipes and projectile streaks.
+///
+/// Positions are recorded with `push`, aged with `update` and turned into a
+/// triangle strip by `mesh`. Width and color are interpolated from the head
+/// (newest point) to the tail.
+///
+/// # Examples
+///
+/// ```
+/// use barn::graphics::trail::Trail;
+///
+/// let mut trail = Trail::new(32, 0.5).with_width(12.0, 0.0);
+/// for x in 0..5 {
+///     trail.push([x as f32 * 10.0, 100.0]);
+///     trail.update(0.016);
+/// }
+/// let (vertices, indices) = trail.mesh();
+/// assert_eq!(vertices.len(), 4 * 4);
+/// assert_eq!(indices.len(), 4 * 6);
+/// ```
+#[derive(Clone)]
+pub struct Trail {
+    points: VecDeque<TrailPoint>,
+    /// Maximum number of recorded points; the oldest are dropped first.
+    pub max_points: usize,
+    /// Seconds a point lives before it is removed. Zero or less keeps points forever.
+    pub lifetime: f32,
+    /// Points closer than this to the point behind the head move the head instead
+    /// of adding a point.
+    pub min_distance: f32,
+    pub head_width: f32,
+    pub tail_width: f32,
+    pub head_color: [f32; 4],
+    pub tail_color: [f32; 4],
+    pub texture_path: Option<String>,
+    pub texture_mode: TrailTextureMode,
+}
+
+impl Trail {
+    pub fn new(max_points: usize, lifetime: f32) -> Trail {
+        Trail {
+            points: VecDeque::new(),
+            max_points,
+            lifetime,
+            min_distance: 2.0,
+            head_width: 8.0,
+            tail_width: 0.0,
+            head_color: [1.0, 1.0, 1.0, 1.0],
+            tail_color: [1.0, 1.0, 1.0, 0.0],
+            texture_path: None,
+            texture_mode: TrailTextureMode::Stretch,
+        }
+    }
+
+    pub fn with_width(mut self, head: f32, tail: f32) -> Trail {
+        self.head_width = head;
+        self.tail_width = tail;
+        self
+    }
+
+    pub fn with_colors(mut self, head: [f32; 4], tail: [f32; 4]) -> Trail {
+        self.head_color = head;
+        self.tail_color = tail;
+        self
+    }
+
+    pub fn with_min_distance(mut self, min_distance: f32) -> Trail {
+        self.min_distance = min_distance;
+        self
+    }
+
+    /// Textures the trail. The texture's u axis runs along the trail from the head,
+    /// and its v axis across it.
+    pub fn with_texture(mut self, texture_path: &str, mode: TrailTextureMode) -> Trail {
+        self.texture_path = Some(texture_path.to_string());
+        self.texture_mode = mode;
+        self
+    }
+
+    /// Records a new head position in screen pixels.
+    pub fn push(&mut self, position: [f32; 2]) {
+        // Keep the newest point glued to the emitter while it moves less than min_distance.
+        if self.points.len() > 1 && distance(self.points[1].position, position) < self.min_distance {
+            self.points[0] = TrailPoint { position, age: 0.0 };
+            return;
+        }
+        self.points.push_front(TrailPoint { position, age: 0.0 });
+        self.points.truncate(self.max_points.max(2));
+    }
+
+    /// Ages every point and drops the ones older than `lifetime`.
+    pub fn update(&mut self, dt: f32) {
+        for point in self.points.iter_mut() {
+            point.age += dt;
+        }
+        if self.lifetime > 0.0 {
+            while self.points.back().is_some_and(|point| point.age > self.lifetime) {
+                self.points.pop_back();
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Total length of the trail in pixels.
+    pub fn length(&self) -> f32 {
+        self.points.iter().zip(self.points.iter().skip(1)).map(|(a, b)| distance(a.position, b.position)).sum()
+    }
+
+    /// Builds the trail as an indexed triangle list with one quad per segment, or per
+    /// texture repetition when tiling.
+    pub fn mesh(&self) -> (Vec<MeshVertex>, Vec<u32>) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let count = self.points.len();
+        if count < 2 {
+            return (vertices, indices);
+        }
+
+        let mut distances = Vec::with_capacity(count);
+        let mut total = 0.0;
+        for i in 0..count {
+            if i > 0 {
+                total += distance(self.points[i - 1].position, self.points[i].position);
+            }
+            distances.push(total);
+        }
+        if total <= f32::EPSILON {
+            return (vertices, indices);
+        }
+
+        // Normals average the neighbouring segments so joints do not show gaps.
+        let normals: Vec<[f32; 2]> = (0..count).map(|i| {
+            let before = self.points[i.saturating_sub(1)].position;
+            let after = self.points[(i + 1).min(count - 1)].position;
+            let (dx, dy) = (after[0] - before[0], after[1] - before[1]);
+            let length = (dx * dx + dy * dy).sqrt().max(f32::EPSILON);
+            [-dy / length, dx / length]
+        }).collect();
+
+        for i in 0..count - 1 {
+            let (d0, d1) = (distances[i], distances[i + 1]);
+            if d1 - d0 <= f32::EPSILON {
+                continue;
+            }
+            // Split the segment wherever a tiled texture wraps around.
+            let mut cuts = vec![d0];
+            if let TrailTextureMode::Tile(tile) = self.texture_mode {
+                if tile > 0.0 {
+                    let mut next = ((d0 / tile).floor() + 1.0) * tile;
+                    while next < d1 {
+                        cuts.push(next);
+                        next += tile;
+                    }
+                }
+            }
+            cuts.push(d1);
+
+            for piece in cuts.windows(2) {
+                let (u0, u1) = match self.texture_mode {
+                    TrailTextureMode::Tile(tile) if tile > 0.0 => {
+                        let start = (piece[0] / tile).floor() * tile;
+                        ((piece[0] - start) / tile, (piece[1] - start) / tile)
+                    }
+                    _ => (piece[0] / total, piece[1] / total),
+                };
+                let first = vertices.len() as u32;
+                for (d, u) in [(piece[0], u0), (piece[1], u1)] {
+                    let s = (d - d0) / (d1 - d0);
+                    let (a, b) = (self.points[i].position, self.points[i + 1].position);
+                    let position = [a[0] + (b[0] - a[0]) * s, a[1] + (b[1] - a[1]) * s];
+                    let normal = normalize(lerp2(normals[i], normals[i + 1], s));
+                    let t = d / total;
+                    let half_width = (self.head_width + (self.tail_width - self.head_width) * t) * 0.5;
+                    let color = std::array::from_fn(|c| self.head_color[c] + (self.tail_color[c] - self.head_color[c]) * t);
+                    vertices.push(MeshVertex::new([position[0] + normal[0] * half_width, position[1] + normal[1] * half_width], color, [u, 0.0]));
+                    vertices.push(MeshVertex::new([position[0] - normal[0] * half_width, position[1] - normal[1] * half_width], color, [u, 1.0]));
+                }
+                indices.extend_from_slice(&[first, first + 1, first + 2, first + 1, first + 3, first + 2]);
+            }
+        }
+        (vertices, indices)
+    }
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt()
+}
+
+fn lerp2(a: [f32; 2], b: [f32; 2], t: f32) -> [f32; 2] {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t]
+}
+
+fn normalize(v: [f32; 2]) -> [f32; 2] {
+    let length = (v[0] * v[0] + v[1] * v[1]).sqrt();
+    if length <= f32::EPSILON { [0.0, 0.0] } else { [v[0] / length, v[1] / length] }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn straight_trail(points: usize, spacing: f32) -> Trail {
+        let mut trail = Trail::new(64, 0.0).with_width(10.0, 2.0).with_min_distance(0.0);
+        // Pushed from the tail, so the head ends up at the largest x
+        for i in 0..points {
+            trail.push([i as f32 * spacing, 50.0]);
+        }
+        trail
+    }
+
+    #[test]
+    fn small_moves_drag_the_head() {
+        let mut trail = Trail::new(8, 0.0).with_min_distance(5.0);
+        trail.push([0.0, 0.0]);
+        trail.push([2.0, 0.0]);
+        trail.push([4.0, 0.0]);
+        assert_eq!(trail.len(), 2);
+        assert_eq!(trail.length(), 4.0);
+        // Measured from the point behind the head, so slow movement still extends the trail
+        trail.push([6.0, 0.0]);
+        assert_eq!(trail.len(), 3);
+    }
+
+    #[test]
+    fn oldest_points_are_dropped_past_max_points() {
+        let mut trail = straight_trail(10, 5.0);
+        trail.max_points = 4;
+        trail.push([100.0, 50.0]);
+        assert_eq!(trail.len(), 4);
+
+        // A trail always keeps enough points for a segment
+        let mut short = Trail::new(0, 0.0).with_min_distance(0.0);
+        for x in 0..5 {
+            short.push([x as f32, 0.0]);
+        }
+        assert_eq!(short.len(), 2);
+    }
+
+    #[test]
+    fn points_expire_after_their_lifetime() {
+        let mut trail = Trail::new(8, 0.1).with_min_distance(0.0);
+        trail.push([0.0, 0.0]);
+        trail.update(0.06);
+        trail.push([10.0, 0.0]);
+        trail.update(0.06);
+        assert_eq!(trail.len(), 1);
+        trail.update(0.06);
+        assert!(trail.is_empty());
+
+        let mut forever = Trail::new(8, 0.0);
+        forever.push([0.0, 0.0]);
+        forever.update(1000.0);
+        assert_eq!(forever.len(), 1);
+    }
+
+    #[test]
+    fn degenerate_trails_have_no_mesh() {
+        let mut trail = Trail::new(8, 0.0).with_min_distance(0.0);
+        assert!(trail.mesh().0.is_empty());
+        trail.push([5.0, 5.0]);
+        assert!(trail.mesh().0.is_empty());
+        trail.push([5.0, 5.0]);
+        assert_eq!(trail.len(), 2);
+        let (vertices, indices) = trail.mesh();
+        assert!(vertices.is_empty() && indices.is_empty());
+    }
+
+    #[test]
+    fn width_and_color_run_from_head_to_tail() {
+        let trail = straight_trail(3, 10.0).with_colors([1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 0.0]);
+        let (vertices, indices) = trail.mesh();
+        assert_eq!(vertices.len(), 2 * 4);
+        assert_eq!(indices.len(), 2 * 6);
+        let (head_a, head_b) = (vertices[0], vertices[1]);
+        assert_eq!(head_a.position[0], 20.0);
+        assert!((head_a.position[1] - head_b.position[1]).abs() - 10.0 < 1e-4);
+        assert_eq!(head_a.color, [1.0, 0.0, 0.0, 1.0]);
+        let (tail_a, tail_b) = (vertices[6], vertices[7]);
+        assert_eq!(tail_a.position[0], 0.0);
+        assert!(((tail_a.position[1] - tail_b.position[1]).abs() - 2.0).abs() < 1e-4);
+        assert_eq!(tail_a.color, [0.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn stretched_textures_span_the_whole_trail() {
+        let trail = straight_trail(3, 10.0).with_texture("streak.png", TrailTextureMode::Stretch);
+        let (vertices, _) = trail.mesh();
+        assert_eq!(vertices[0].tex_coords, [0.0, 0.0]);
+        assert_eq!(vertices[1].tex_coords, [0.0, 1.0]);
+        assert_eq!(vertices[7].tex_coords, [1.0, 1.0]);
+    }
+
+    #[test]
+    fn tiled_textures_split_segments_where_they_wrap() {
+        // One 25 pixel segment with a 10 pixel tile needs three quads
+        let trail = straight_trail(2, 25.0).with_texture("chain.png", TrailTextureMode::Tile(10.0));
+        let (vertices, indices) = trail.mesh();
+        assert_eq!(indices.len(), 3 * 6);
+        let us: Vec<f32> = vertices.iter().step_by(2).map(|vertex| vertex.tex_coords[0]).collect();
+        assert_eq!(us, vec![0.0, 1.0, 0.0, 1.0, 0.0, 0.5]);
+        assert_eq!(vertices[4].position[0], 15.0);
+
+        // A zero tile falls back to a single quad
+        let trail = straight_trail(2, 25.0).with_texture("chain.png", TrailTextureMode::Tile(0.0));
+        assert_eq!(trail.mesh().1.len(), 6);
+    }
+}
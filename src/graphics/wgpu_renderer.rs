@@ -3,12 +3,86 @@ use winit::window::Window;
 use wgpu::util::DeviceExt;
 use ab_glyph::{Font, FontArc, Glyph, point, PxScale, Rect};
 use crate::graphics::texture::Texture;
+use crate::graphics::atlas::TextureAtlas;
 use crate::graphics::wgpu_sprite::WgpuSprite;
+use crate::graphics::clip::ClipMask;
+use crate::graphics::mesh::MeshVertex;
+use crate::graphics::sprite_instance::SpriteInstance;
+use crate::graphics::capture::{check_format, FrameCapture, FrameReadback, RecordingFormat};
+use crate::graphics::render_stats::{GpuTimer, RenderStats};
+use crate::graphics::render_settings::{GraphicsBackend, RenderSettings, VSync};
+use crate::graphics::renderer::Renderer;
 use crate::graphics::Rect as BarnRect;
 
 pub enum DrawCommand {
     Sprite(WgpuSprite),
     Rect { x: i32, y: i32, width: u32, height: u32, color: [f32; 4] },
+    Instances { texture_path: String, src: BarnRect, instances: Vec<SpriteInstance> },
+    Mesh { vertices: Vec<MeshVertex>, indices: Vec<u32>, texture_path: Option<String> },
+    /// Glyph quads from the font atlas, in normalized device coordinates.
+    Text(Vec<TextVertex>),
+    PushScissor(BarnRect),
+    PushMask(Vec<ClipMask>),
+    PopClip,
+    /// Commands up to the matching `EndTarget` are drawn into the named render target.
+    BeginTarget(String),
+    EndTarget,
+}
+
+/// GPU resources created for a draw command before the render pass begins.
+enum PreparedCommand {
+    None,
+    Sprite(wgpu::BindGroup),
+    Rect(wgpu::Buffer),
+    Instances {
+        bind_group: wgpu::BindGroup,
+        quad: wgpu::Buffer,
+        instances: wgpu::Buffer,
+        count: u32,
+    },
+    Mesh {
+        bind_group: wgpu::BindGroup,
+        vertices: wgpu::Buffer,
+        indices: wgpu::Buffer,
+        index_count: u32,
+    },
+    Mask {
+        primitives: Option<(wgpu::Buffer, u32)>,
+        sprites: Vec<(wgpu::BindGroup, wgpu::Buffer)>,
+    },
+    Text {
+        vertices: wgpu::Buffer,
+        count: u32,
+    },
+}
+
+/// An entry on the clip stack while the render pass replays draw commands.
+enum ClipLevel {
+    /// Holds the scissor rectangle to restore when popped.
+    Scissor((u32, u32, u32, u32)),
+    Mask,
+}
+
+/// The textures a render pass draws into.
+struct PassAttachments<'a> {
+    view: &'a wgpu::TextureView,
+    /// Multisampled color target resolved into `view`; `None` without MSAA.
+    msaa_view: Option<&'a wgpu::TextureView>,
+    depth_stencil_view: &'a wgpu::TextureView,
+    size: (u32, u32),
+}
+
+const DEPTH_STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+/// Every render pipeline, rebuilt when the MSAA sample count changes.
+struct Pipelines {
+    sprite: wgpu::RenderPipeline,
+    instanced_sprite: wgpu::RenderPipeline,
+    text: wgpu::RenderPipeline,
+    rectangle: wgpu::RenderPipeline,
+    stencil_increment: wgpu::RenderPipeline,
+    stencil_decrement: wgpu::RenderPipeline,
+    stencil_sprite: wgpu::RenderPipeline,
 }
 
 pub struct WgpuRenderer {
@@ -16,18 +90,51 @@ pub struct WgpuRenderer {
     pub queue: Arc<wgpu::Queue>,
     pub size: winit::dpi::PhysicalSize<u32>,
     pub sprite_pipeline: wgpu::RenderPipeline,
+    pub instanced_sprite_pipeline: wgpu::RenderPipeline,
     pub text_pipeline: wgpu::RenderPipeline,
     pub rectangle_pipeline: wgpu::RenderPipeline,
     pub surface_format: wgpu::TextureFormat,
     pub clear_color: wgpu::Color,
     pub text_vertex_buffer: wgpu::Buffer,
     pub text_index_buffer: wgpu::Buffer,
-    pub text_vertices: Vec<TextVertex>,
-    pub current_text_vertex_buffer: Option<wgpu::Buffer>,
     pub font_atlas: Option<FontAtlas>,
     pub draw_commands: Vec<DrawCommand>,
     pub sprite_bind_group_layout: wgpu::BindGroupLayout,
     pub textures: std::collections::HashMap<String, Texture>,
+    /// A white pixel bound for untextured meshes.
+    pub default_texture: Texture,
+    pub stencil_increment_pipeline: wgpu::RenderPipeline,
+    pub stencil_decrement_pipeline: wgpu::RenderPipeline,
+    pub stencil_sprite_pipeline: wgpu::RenderPipeline,
+    pub depth_stencil_view: wgpu::TextureView,
+    pub fullscreen_vertex_buffer: wgpu::Buffer,
+    pub capture: FrameCapture,
+    readback: FrameReadback,
+    /// Whether frames can be copied straight from the surface texture.
+    surface_copy_src: bool,
+    /// Offscreen color target frames are drawn into for capture when the surface can't be copied.
+    capture_texture: Option<Texture>,
+    stats: RenderStats,
+    frame_stats: RenderStats,
+    gpu_timer: Option<GpuTimer>,
+    gpu_time_ms: Option<f32>,
+    settings: RenderSettings,
+    adapter_info: wgpu::AdapterInfo,
+    present_modes: Vec<wgpu::PresentMode>,
+    supported_sample_counts: Vec<u32>,
+    sample_count: u32,
+    /// Multisampled color target resolved into the frame; `None` without MSAA.
+    msaa_view: Option<wgpu::TextureView>,
+    text_bind_group_layout: wgpu::BindGroupLayout,
+    /// The surface configuration. The renderer is its only owner, so resizing keeps
+    /// the present mode chosen by `apply_settings`; it is reapplied on the next
+    /// `present` when vsync changes.
+    surface_config: wgpu::SurfaceConfiguration,
+    surface_config_changed: bool,
+    /// Names of the entries in `textures` that are render targets.
+    render_targets: std::collections::HashSet<String>,
+    /// Render targets opened by `begin_render_target` and not yet ended.
+    open_targets: Vec<String>,
 }
 
 #[repr(C)]
@@ -38,6 +145,17 @@ pub struct TextVertex {
     pub tex_coords: [f32; 2],
 }
 
+/// Per-instance vertex data. The transform is baked into NDC axes so the shader
+/// needs no screen size uniform.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct InstanceVertex {
+    center: [f32; 2],
+    axis_x: [f32; 2],
+    axis_y: [f32; 2],
+    color: [f32; 4],
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct RectangleVertex {
@@ -71,6 +189,23 @@ impl TextVertex {
     }
 }
 
+impl InstanceVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        3 => Float32x2,
+        4 => Float32x2,
+        5 => Float32x2,
+        6 => Float32x4,
+    ];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
 impl RectangleVertex {
     fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
@@ -107,31 +242,30 @@ pub struct FontAtlas {
     pub glyphs: std::collections::HashMap<char, GlyphInfo>,
     pub width: u32,
     pub height: u32,
+    /// The pixel size the glyphs were rasterized at; other sizes are scaled from it.
+    pub font_size: f32,
 }
 
 impl WgpuRenderer {
-    pub fn new(window: &Window) -> Result<(Self, wgpu::Surface, wgpu::SurfaceConfiguration), Box<dyn std::error::Error>> {
+    pub fn new<'w>(window: &'w Window, settings: RenderSettings) -> Result<(Self, wgpu::Surface<'w>), Box<dyn std::error::Error>> {
         let size = window.inner_size();
         
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            flags: wgpu::InstanceFlags::default(),
-            dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
-            gles_minor_version: wgpu::Gles3MinorVersion::Automatic,
-        });
-        
-        let surface = unsafe { instance.create_surface(window) }?;
-        
-        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
-            compatible_surface: Some(&surface),
-            force_fallback_adapter: false,
-        })).ok_or("Failed to find an appropriate adapter")?;
+        let (surface, adapter) = match Self::request_adapter(window, settings.backend.into(), settings.power_profile.into()) {
+            Some(found) => found,
+            None if settings.backend != GraphicsBackend::Auto => {
+                log::warn!("No {:?} adapter found, falling back to any backend", settings.backend);
+                Self::request_adapter(window, wgpu::Backends::all(), settings.power_profile.into())
+                    .ok_or("Failed to find an appropriate adapter")?
+            },
+            None => return Err("Failed to find an appropriate adapter".into()),
+        };
         
         let (device, queue) = pollster::block_on(adapter.request_device(
             &wgpu::DeviceDescriptor {
                 label: None,
-                required_features: wgpu::Features::empty(),
+                // Timestamp queries are optional; without them `RenderStats::gpu_time_ms` stays `None`.
+                // Adapter specific format features unlock MSAA sample counts other than 1 and 4.
+                required_features: adapter.features() & (wgpu::Features::TIMESTAMP_QUERY | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES),
                 required_limits: wgpu::Limits::default(),
             },
             None,
@@ -143,22 +277,32 @@ impl WgpuRenderer {
             .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
         
+        // Copying the surface lets captures read the finished frame without drawing it twice
+        let surface_copy_src = surface_caps.usages.contains(wgpu::TextureUsages::COPY_SRC);
+        let mut usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        if surface_copy_src {
+            usage |= wgpu::TextureUsages::COPY_SRC;
+        }
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage,
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: surface_caps.present_modes[0],
+            present_mode: settings.present_mode(&surface_caps.present_modes),
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
         };
         surface.configure(&device, &config);
-        
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Sprite Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/sprite.wgsl").into()),
-        });
+
+        // Both the color and the depth stencil attachment are multisampled.
+        let supported_sample_counts: Vec<u32> = if device.features().contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+            let color = adapter.get_texture_format_features(surface_format).flags;
+            let depth = adapter.get_texture_format_features(DEPTH_STENCIL_FORMAT).flags;
+            color.supported_sample_counts().into_iter().filter(|count| depth.sample_count_supported(*count)).collect()
+        } else {
+            vec![1, 4]
+        };
         
         let sprite_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[
@@ -181,63 +325,7 @@ impl WgpuRenderer {
             ],
             label: Some("Sprite Bind Group Layout"),
         });
-        
-        let sprite_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Sprite Pipeline"),
-            layout: Some(&device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                bind_group_layouts: &[&sprite_bind_group_layout],
-                push_constant_ranges: &[],
-                label: Some("Sprite Pipeline Layout"),
-            })),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[TextVertex::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState {
-                        color: wgpu::BlendComponent {
-                            src_factor: wgpu::BlendFactor::SrcAlpha,
-                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                            operation: wgpu::BlendOperation::Add,
-                        },
-                        alpha: wgpu::BlendComponent {
-                            src_factor: wgpu::BlendFactor::One,
-                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                            operation: wgpu::BlendOperation::Add,
-                        },
-                    }),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-        });
-        
-        // Create text rendering shader
-        let text_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Text Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/text.wgsl").into()),
-        });
-        
+
         // Create text bind group layout for the font atlas
         let text_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[
@@ -260,9 +348,156 @@ impl WgpuRenderer {
             ],
             label: Some("Text Bind Group Layout"),
         });
+
+        let sample_count = settings.sample_count(&supported_sample_counts);
+        let Pipelines {
+            sprite: sprite_pipeline,
+            instanced_sprite: instanced_sprite_pipeline,
+            text: text_pipeline,
+            rectangle: rectangle_pipeline,
+            stencil_increment: stencil_increment_pipeline,
+            stencil_decrement: stencil_decrement_pipeline,
+            stencil_sprite: stencil_sprite_pipeline,
+        } = Self::create_pipelines(&device, config.format, &sprite_bind_group_layout, &text_bind_group_layout, sample_count);
+        let depth_stencil_view = Self::create_depth_stencil_view(&device, size.width, size.height, sample_count);
+        let msaa_view = Self::create_msaa_view(&device, config.format, size.width, size.height, sample_count);
+        let fullscreen_color = [1.0, 1.0, 1.0, 1.0];
+        let fullscreen_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Fullscreen Vertex Buffer"),
+            contents: bytemuck::cast_slice(&[
+                RectangleVertex { position: [-1.0, 1.0], color: fullscreen_color },
+                RectangleVertex { position: [1.0, 1.0], color: fullscreen_color },
+                RectangleVertex { position: [1.0, -1.0], color: fullscreen_color },
+                RectangleVertex { position: [-1.0, 1.0], color: fullscreen_color },
+                RectangleVertex { position: [1.0, -1.0], color: fullscreen_color },
+                RectangleVertex { position: [-1.0, -1.0], color: fullscreen_color },
+            ]),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        
+        // Create text rendering resources
+        let text_vertices = vec![
+            TextVertex { position: [-0.5, -0.5], color: [1.0, 1.0, 1.0, 1.0], tex_coords: [0.0, 0.0] },
+            TextVertex { position: [ 0.5, -0.5], color: [1.0, 1.0, 1.0, 1.0], tex_coords: [1.0, 0.0] },
+            TextVertex { position: [ 0.5,  0.5], color: [1.0, 1.0, 1.0, 1.0], tex_coords: [1.0, 1.0] },
+            TextVertex { position: [-0.5,  0.5], color: [1.0, 1.0, 1.0, 1.0], tex_coords: [0.0, 1.0] },
+        ];
+        
+        let text_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Text Vertex Buffer"),
+            contents: bytemuck::cast_slice(&text_vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        
+        let text_indices = vec![0, 1, 2, 0, 2, 3];
+        let text_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Text Index Buffer"),
+            contents: bytemuck::cast_slice(&text_indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        
+        // Create font atlas
+        let gpu_timer = GpuTimer::new(&device, &queue);
+        let default_texture = Texture::create_default(&device, &queue);
+
+        let font_atlas = Self::create_font_atlas(&device, &queue, &text_bind_group_layout, "examples/resources/fonts/press-start/PressStart2P-vaV7.ttf", 48.0)?;
+
+        Ok((WgpuRenderer {
+            device: Arc::new(device),
+            queue: Arc::new(queue),
+            size,
+            sprite_pipeline,
+            instanced_sprite_pipeline,
+            text_pipeline,
+            rectangle_pipeline,
+            surface_format,
+            clear_color: wgpu::Color {
+                r: 0.1,
+                g: 0.2,
+                b: 0.3,
+                a: 1.0,
+            },
+            text_vertex_buffer,
+            text_index_buffer,
+            font_atlas: Some(font_atlas),
+            draw_commands: Vec::new(),
+            sprite_bind_group_layout: sprite_bind_group_layout,
+            textures: std::collections::HashMap::new(),
+            default_texture,
+            stencil_increment_pipeline,
+            stencil_decrement_pipeline,
+            stencil_sprite_pipeline,
+            depth_stencil_view,
+            fullscreen_vertex_buffer,
+            capture: FrameCapture::default(),
+            readback: FrameReadback::default(),
+            surface_copy_src,
+            capture_texture: None,
+            render_targets: std::collections::HashSet::new(),
+            open_targets: Vec::new(),
+            stats: RenderStats::default(),
+            frame_stats: RenderStats::default(),
+            gpu_timer,
+            gpu_time_ms: None,
+            settings,
+            adapter_info: adapter.get_info(),
+            present_modes: surface_caps.present_modes,
+            supported_sample_counts,
+            sample_count,
+            msaa_view,
+            text_bind_group_layout,
+            surface_config: config,
+            surface_config_changed: false,
+        }, surface))
+    }
+    
+    fn request_adapter(window: &Window, backends: wgpu::Backends, power_preference: wgpu::PowerPreference) -> Option<(wgpu::Surface<'_>, wgpu::Adapter)> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends,
+            flags: wgpu::InstanceFlags::default(),
+            dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
+            gles_minor_version: wgpu::Gles3MinorVersion::Automatic,
+        });
+        let surface = instance.create_surface(window).ok()?;
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference,
+            compatible_surface: Some(&surface),
+            force_fallback_adapter: false,
+        }))?;
+        Some((surface, adapter))
+    }
+
+    /// Builds every render pipeline for the surface format and MSAA sample count.
+    fn create_pipelines(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sprite_bind_group_layout: &wgpu::BindGroupLayout,
+        text_bind_group_layout: &wgpu::BindGroupLayout,
+        sample_count: u32,
+    ) -> Pipelines {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Sprite Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/sprite.wgsl").into()),
+        });
+        
+        
+        let sprite_pipeline = Self::create_sprite_pipeline(device, &shader, sprite_bind_group_layout, &[TextVertex::desc()], format, sample_count, "Sprite Pipeline");
+
+        let instanced_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Instanced Sprite Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/instanced_sprite.wgsl").into()),
+        });
+        let instanced_sprite_pipeline = Self::create_sprite_pipeline(device, &instanced_shader, sprite_bind_group_layout, &[TextVertex::desc(), InstanceVertex::desc()], format, sample_count, "Instanced Sprite Pipeline");
+        
+        // Create text rendering shader
+        let text_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Text Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/text.wgsl").into()),
+        });
+        
         
         let text_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            bind_group_layouts: &[&text_bind_group_layout],
+            bind_group_layouts: &[text_bind_group_layout],
             push_constant_ranges: &[],
             label: Some("Text Pipeline Layout"),
         });
@@ -279,7 +514,7 @@ impl WgpuRenderer {
                 module: &text_shader,
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
+                    format,
                     blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -293,9 +528,9 @@ impl WgpuRenderer {
                 unclipped_depth: false,
                 conservative: false,
             },
-            depth_stencil: None,
+            depth_stencil: Some(Self::stencil_state(wgpu::CompareFunction::Equal, wgpu::StencilOperation::Keep)),
             multisample: wgpu::MultisampleState {
-                count: 1,
+                count: sample_count,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
@@ -320,7 +555,7 @@ impl WgpuRenderer {
                 module: &rectangle_shader,
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
+                    format,
                     blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -334,200 +569,899 @@ impl WgpuRenderer {
                 unclipped_depth: false,
                 conservative: false,
             },
-            depth_stencil: None,
+            depth_stencil: Some(Self::stencil_state(wgpu::CompareFunction::Equal, wgpu::StencilOperation::Keep)),
             multisample: wgpu::MultisampleState {
-                count: 1,
+                count: sample_count,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
             multiview: None,
         });
         
-        // Create text rendering resources
-        let text_vertices = vec![
-            TextVertex { position: [-0.5, -0.5], color: [1.0, 1.0, 1.0, 1.0], tex_coords: [0.0, 0.0] },
-            TextVertex { position: [ 0.5, -0.5], color: [1.0, 1.0, 1.0, 1.0], tex_coords: [1.0, 0.0] },
-            TextVertex { position: [ 0.5,  0.5], color: [1.0, 1.0, 1.0, 1.0], tex_coords: [1.0, 1.0] },
-            TextVertex { position: [-0.5,  0.5], color: [1.0, 1.0, 1.0, 1.0], tex_coords: [0.0, 1.0] },
-        ];
-        
-        let text_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Text Vertex Buffer"),
-            contents: bytemuck::cast_slice(&text_vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        
-        let text_indices = vec![0, 1, 2, 0, 2, 3];
-        let text_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Text Index Buffer"),
-            contents: bytemuck::cast_slice(&text_indices),
-            usage: wgpu::BufferUsages::INDEX,
+        // Create clipping mask pipelines. Masks only write to the stencil buffer.
+        let stencil_increment_pipeline = Self::create_stencil_pipeline(device, &rectangle_shader, "fs_main", None, RectangleVertex::desc(), format, sample_count, wgpu::StencilOperation::IncrementClamp, "Stencil Increment Pipeline");
+        let stencil_decrement_pipeline = Self::create_stencil_pipeline(device, &rectangle_shader, "fs_main", None, RectangleVertex::desc(), format, sample_count, wgpu::StencilOperation::DecrementClamp, "Stencil Decrement Pipeline");
+        let stencil_sprite_pipeline = Self::create_stencil_pipeline(device, &shader, "fs_mask", Some(sprite_bind_group_layout), TextVertex::desc(), format, sample_count, wgpu::StencilOperation::IncrementClamp, "Stencil Sprite Pipeline");
+
+        Pipelines {
+            sprite: sprite_pipeline,
+            instanced_sprite: instanced_sprite_pipeline,
+            text: text_pipeline,
+            rectangle: rectangle_pipeline,
+            stencil_increment: stencil_increment_pipeline,
+            stencil_decrement: stencil_decrement_pipeline,
+            stencil_sprite: stencil_sprite_pipeline,
+        }
+    }
+
+    /// Reconfigures the surface for a new window size. A zero size, e.g. while the
+    /// window is minimized, keeps the surface as it is and pauses presenting.
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>, surface: &mut wgpu::Surface) {
+        self.size = new_size;
+        if new_size.width > 0 && new_size.height > 0 {
+            self.surface_config.width = new_size.width;
+            self.surface_config.height = new_size.height;
+            surface.configure(&self.device, &self.surface_config);
+            self.surface_config_changed = false;
+            self.create_frame_targets();
+        }
+    }
+
+    pub fn set_vsync(&mut self, vsync: VSync) {
+        self.settings.vsync = vsync;
+        let present_mode = self.settings.present_mode(&self.present_modes);
+        if present_mode != self.surface_config.present_mode {
+            self.surface_config.present_mode = present_mode;
+            self.surface_config_changed = true;
+        }
+    }
+
+    /// Sets the MSAA sample count, lowered to the nearest supported count, and
+    /// rebuilds the pipelines if it changed.
+    pub fn set_msaa(&mut self, samples: u32) {
+        self.settings.msaa_samples = samples;
+        let sample_count = self.settings.sample_count(&self.supported_sample_counts);
+        if sample_count == self.sample_count {
+            return;
+        }
+        self.sample_count = sample_count;
+        let pipelines = Self::create_pipelines(&self.device, self.surface_format, &self.sprite_bind_group_layout, &self.text_bind_group_layout, sample_count);
+        self.sprite_pipeline = pipelines.sprite;
+        self.instanced_sprite_pipeline = pipelines.instanced_sprite;
+        self.text_pipeline = pipelines.text;
+        self.rectangle_pipeline = pipelines.rectangle;
+        self.stencil_increment_pipeline = pipelines.stencil_increment;
+        self.stencil_decrement_pipeline = pipelines.stencil_decrement;
+        self.stencil_sprite_pipeline = pipelines.stencil_sprite;
+        self.create_frame_targets();
+    }
+
+    /// Returns the configuration the surface is presented with.
+    pub fn surface_config(&self) -> &wgpu::SurfaceConfiguration {
+        &self.surface_config
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.surface_config.present_mode
+    }
+
+    /// Present modes the surface supports, for listing vsync choices in a settings menu.
+    pub fn present_modes(&self) -> &[wgpu::PresentMode] {
+        &self.present_modes
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// MSAA sample counts the GPU supports for the surface format, always including 1.
+    pub fn supported_sample_counts(&self) -> &[u32] {
+        &self.supported_sample_counts
+    }
+
+    /// Describes the GPU and backend the renderer runs on.
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.adapter_info
+    }
+
+    /// Recreates the depth stencil and MSAA targets for the current size and sample count.
+    fn create_frame_targets(&mut self) {
+        let (width, height) = (self.size.width, self.size.height);
+        self.depth_stencil_view = Self::create_depth_stencil_view(&self.device, width, height, self.sample_count);
+        self.msaa_view = Self::create_msaa_view(&self.device, self.surface_format, width, height, self.sample_count);
+    }
+
+    /// Creates an alpha-blended pipeline sampling the sprite bind group.
+    fn create_sprite_pipeline(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        buffers: &[wgpu::VertexBufferLayout],
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        label: &str,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                bind_group_layouts: &[bind_group_layout],
+                push_constant_ranges: &[],
+                label: Some("Sprite Pipeline Layout"),
+            })),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::SrcAlpha,
+                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::One,
+                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(Self::stencil_state(wgpu::CompareFunction::Equal, wgpu::StencilOperation::Keep)),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
+
+    fn create_depth_stencil_view(device: &wgpu::Device, width: u32, height: u32, sample_count: u32) -> wgpu::TextureView {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Depth Stencil Texture"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_STENCIL_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
         });
-        
-        // Create font atlas
-        let font_atlas = Self::create_font_atlas(&device, &queue, &text_bind_group_layout, "examples/resources/fonts/press-start/PressStart2P-vaV7.ttf", 48.0);
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
 
-        Ok((WgpuRenderer {
-            device: Arc::new(device),
-            queue: Arc::new(queue),
-            size,
-            sprite_pipeline,
-            text_pipeline,
-            rectangle_pipeline,
-            surface_format,
-            clear_color: wgpu::Color {
-                r: 0.1,
-                g: 0.2,
-                b: 0.3,
-                a: 1.0,
+    fn create_msaa_view(device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32, sample_count: u32) -> Option<wgpu::TextureView> {
+        if sample_count <= 1 {
+            return None;
+        }
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("MSAA Color Texture"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
             },
-            text_vertex_buffer,
-            text_index_buffer,
-            text_vertices: Vec::new(),
-            current_text_vertex_buffer: None,
-            font_atlas: Some(font_atlas),
-            draw_commands: Vec::new(),
-            sprite_bind_group_layout: sprite_bind_group_layout,
-            textures: std::collections::HashMap::new(),
-        }, surface, config))
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
     }
-    
-    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>, surface: &mut wgpu::Surface, config: &mut wgpu::SurfaceConfiguration) {
-        if new_size.width > 0 && new_size.height > 0 {
-            self.size = new_size;
-            config.width = new_size.width;
-            config.height = new_size.height;
-            surface.configure(&self.device, config);
+
+    /// Depth is unused; the stencil buffer holds the nesting level of clipping masks.
+    fn stencil_state(compare: wgpu::CompareFunction, pass_op: wgpu::StencilOperation) -> wgpu::DepthStencilState {
+        let face = wgpu::StencilFaceState {
+            compare,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op,
+        };
+        wgpu::DepthStencilState {
+            format: DEPTH_STENCIL_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState {
+                front: face,
+                back: face,
+                read_mask: 0xff,
+                write_mask: 0xff,
+            },
+            bias: wgpu::DepthBiasState::default(),
         }
     }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_stencil_pipeline(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        fragment_entry: &str,
+        bind_group_layout: Option<&wgpu::BindGroupLayout>,
+        vertex_layout: wgpu::VertexBufferLayout,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        pass_op: wgpu::StencilOperation,
+        label: &str,
+    ) -> wgpu::RenderPipeline {
+        let layout = bind_group_layout.map(|bind_group_layout| device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[bind_group_layout],
+            push_constant_ranges: &[],
+            label: Some(label),
+        }));
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: layout.as_ref(),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[vertex_layout],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: fragment_entry,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::empty(),
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(Self::stencil_state(wgpu::CompareFunction::Equal, pass_op)),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
     
     pub fn set_clear_color(&mut self, r: f64, g: f64, b: f64, a: f64) {
         self.clear_color = wgpu::Color { r, g, b, a };
     }
     
-    pub fn present(&mut self, surface: &mut wgpu::Surface) {
-        let frame = surface.get_current_texture().unwrap();
+    /// Draws the recorded commands and presents the frame.
+    ///
+    /// Lost and outdated surfaces are reconfigured and the frame is skipped, as are
+    /// frames that time out or arrive while the window has zero size. Render targets
+    /// are still drawn in skipped frames. Only `SurfaceError::OutOfMemory` is
+    /// returned, after which rendering cannot continue.
+    pub fn present(&mut self, surface: &mut wgpu::Surface) -> Result<(), wgpu::SurfaceError> {
+        if self.size.width == 0 || self.size.height == 0 {
+            self.skip_frame();
+            return Ok(());
+        }
+        if self.surface_config_changed {
+            surface.configure(&self.device, &self.surface_config);
+            self.surface_config_changed = false;
+        }
+        let frame = match surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                surface.configure(&self.device, &self.surface_config);
+                self.skip_frame();
+                return Ok(());
+            },
+            Err(wgpu::SurfaceError::Timeout) => {
+                self.skip_frame();
+                return Ok(());
+            },
+            Err(e) => {
+                self.clear_draw_commands();
+                return Err(e);
+            },
+        };
         let view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder"),
         });
 
-        // Pre-allocate sprite bind groups and rectangle vertex buffers
-        let mut sprite_bind_groups = Vec::new();
-        let mut rect_vertex_buffers = Vec::new();
-        for cmd in &self.draw_commands {
-            match cmd {
-                DrawCommand::Sprite(sprite) => {
-                    if let Some(texture_path) = sprite.get_texture_path() {
-                        if let Some(texture) = self.textures.get(texture_path) {
-                            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                                layout: &self.sprite_bind_group_layout,
-                                entries: &[
-                                    wgpu::BindGroupEntry {
-                                        binding: 0,
-                                        resource: wgpu::BindingResource::TextureView(&texture.view),
-                                    },
-                                    wgpu::BindGroupEntry {
-                                        binding: 1,
-                                        resource: wgpu::BindingResource::Sampler(&texture.sampler),
-                                    },
-                                ],
-                                label: Some("Sprite Bind Group"),
-                            });
-                            sprite_bind_groups.push(Some(bind_group));
-                            rect_vertex_buffers.push(None);
-                        } else {
-                            sprite_bind_groups.push(None);
-                            rect_vertex_buffers.push(None);
-                        }
-                    } else {
-                        sprite_bind_groups.push(None);
-                        rect_vertex_buffers.push(None);
-                    }
+        // Without a copyable surface, a captured frame is drawn offscreen and then blitted to the surface
+        let capture = self.capture.wants_frame().then(|| self.capture.take_request());
+        // Only wait on the GPU when every readback buffer is still in use
+        if capture.is_some() && self.readback.is_full() {
+            self.deliver_captured_frames(true);
+        }
+        if capture.is_some() && !self.surface_copy_src {
+            let (width, height) = (self.size.width.max(1), self.size.height.max(1));
+            if self.capture_texture.as_ref().map(|texture| (texture.size.width, texture.size.height)) != Some((width, height)) {
+                self.capture_texture = Some(Texture::render_target(&self.device, self.surface_format, width, height, "Capture"));
+            }
+        }
+        let offscreen = self.capture_texture.as_ref().filter(|_| capture.is_some() && !self.surface_copy_src);
+
+        // Pre-allocate bind groups and vertex buffers for every draw command
+        let prepared: Vec<PreparedCommand> = self.draw_commands.iter()
+            .map(|cmd| self.prepare_command(cmd))
+            .collect();
+        // The GPU timer starts with the first pass and stops with the last one
+        let timestamp_writes = |first: bool, last: bool| self.gpu_timer.as_ref().and_then(|timer| timer.timestamp_writes(first, last));
+        let no_targets = self.target_names().is_empty();
+        let mut pass_stats = self.encode_target_passes(&mut encoder, &prepared, true);
+        let frame_view = offscreen.map_or(&view, |texture| &texture.view);
+        pass_stats += self.encode_draw_pass(&mut encoder, self.frame_attachments(frame_view), &prepared, None, timestamp_writes(no_targets, offscreen.is_none()));
+        if let Some(texture) = offscreen {
+            self.encode_blit(&mut encoder, texture, &view, timestamp_writes(false, true));
+        }
+        if let Some(timer) = self.gpu_timer.as_mut() {
+            timer.resolve(&mut encoder);
+        }
+
+        if let Some(request) = capture {
+            let source = offscreen.map_or(&frame.texture, |texture| &texture.texture);
+            if let Err(e) = self.readback.copy(&self.device, &mut encoder, source, request) {
+                log::error!("Failed to capture frame: {}", e);
+            }
+        }
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.readback.map_submitted();
+        frame.present();
+        self.finish_frame_stats(pass_stats, &prepared);
+        if !self.readback.is_empty() {
+            self.deliver_captured_frames(false);
+        }
+        self.clear_draw_commands();
+        Ok(())
+    }
+
+    /// Draws this frame's render targets without presenting, so a target drawn only
+    /// once, like a transition's outgoing screen, survives a skipped frame.
+    fn skip_frame(&mut self) {
+        if !self.target_names().is_empty() {
+            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Target Encoder"),
+            });
+            let prepared: Vec<PreparedCommand> = self.draw_commands.iter()
+                .map(|cmd| self.prepare_command(cmd))
+                .collect();
+            self.encode_target_passes(&mut encoder, &prepared, false);
+            self.queue.submit(std::iter::once(encoder.finish()));
+        }
+        self.clear_draw_commands();
+    }
+
+    /// Draws each render target in its own pass, before the frame that samples it.
+    /// `timed` starts the GPU timer with the first pass.
+    fn encode_target_passes(&self, encoder: &mut wgpu::CommandEncoder, prepared: &[PreparedCommand], timed: bool) -> RenderStats {
+        let targets: Vec<(&str, &Texture)> = self.target_names().into_iter()
+            .filter_map(|name| self.textures.get(name).map(|texture| (name, texture)))
+            .collect();
+        // A target begun before the window was resized keeps its old size, so it needs
+        // depth stencil and MSAA attachments of its own
+        let target_attachments: Vec<Option<(wgpu::TextureView, Option<wgpu::TextureView>)>> = targets.iter()
+            .map(|(_, texture)| {
+                let (width, height) = (texture.size.width, texture.size.height);
+                ((width, height) != (self.size.width, self.size.height)).then(|| (
+                    Self::create_depth_stencil_view(&self.device, width, height, self.sample_count),
+                    Self::create_msaa_view(&self.device, self.surface_format, width, height, self.sample_count),
+                ))
+            })
+            .collect();
+        let mut pass_stats = RenderStats::default();
+        for (i, ((name, texture), own)) in targets.iter().zip(target_attachments.iter()).enumerate() {
+            let attachments = match own {
+                Some((depth_stencil_view, msaa_view)) => PassAttachments {
+                    view: &texture.view,
+                    msaa_view: msaa_view.as_ref(),
+                    depth_stencil_view,
+                    size: (texture.size.width, texture.size.height),
                 },
-                DrawCommand::Rect { x, y, width, height, color } => {
-                    let x = *x;
-                    let y = *y;
-                    let width = *width;
-                    let height = *height;
-                    let color = *color;
-                    let screen_width = self.size.width as f32;
-                    let screen_height = self.size.height as f32;
-                    let x1 = (x as f32 / screen_width) * 2.0 - 1.0;
-                    let y1 = 1.0 - (y as f32 / screen_height) * 2.0;
-                    let x2 = ((x + width as i32) as f32 / screen_width) * 2.0 - 1.0;
-                    let y2 = 1.0 - ((y + height as i32) as f32 / screen_height) * 2.0;
-                    let vertices = [
-                        RectangleVertex { position: [x1, y1], color },
-                        RectangleVertex { position: [x2, y1], color },
-                        RectangleVertex { position: [x2, y2], color },
-                        RectangleVertex { position: [x1, y1], color },
-                        RectangleVertex { position: [x2, y2], color },
-                        RectangleVertex { position: [x1, y2], color },
-                    ];
-                    let vertex_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                        label: Some("Rectangle Vertex Buffer (temp)"),
-                        contents: bytemuck::cast_slice(&vertices),
-                        usage: wgpu::BufferUsages::VERTEX,
-                    });
-                    sprite_bind_groups.push(None);
-                    rect_vertex_buffers.push(Some(vertex_buffer));
+                None => self.frame_attachments(&texture.view),
+            };
+            let timestamp_writes = self.gpu_timer.as_ref()
+                .filter(|_| timed)
+                .and_then(|timer| timer.timestamp_writes(i == 0, false));
+            pass_stats += self.encode_draw_pass(encoder, attachments, prepared, Some(name), timestamp_writes);
+        }
+        pass_stats
+    }
+
+    /// Draws `texture` over the whole of `view` with the sprite pipeline.
+    fn encode_blit(&self, encoder: &mut wgpu::CommandEncoder, texture: &Texture, view: &wgpu::TextureView, timestamp_writes: Option<wgpu::RenderPassTimestampWrites>) {
+        let white = [1.0, 1.0, 1.0, 1.0];
+        let vertices = [
+            TextVertex { position: [-1.0, 1.0], color: white, tex_coords: [0.0, 0.0] },
+            TextVertex { position: [1.0, 1.0], color: white, tex_coords: [1.0, 0.0] },
+            TextVertex { position: [1.0, -1.0], color: white, tex_coords: [1.0, 1.0] },
+            TextVertex { position: [-1.0, 1.0], color: white, tex_coords: [0.0, 0.0] },
+            TextVertex { position: [1.0, -1.0], color: white, tex_coords: [1.0, 1.0] },
+            TextVertex { position: [-1.0, -1.0], color: white, tex_coords: [0.0, 1.0] },
+        ];
+        let vertex_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Capture Blit Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let bind_group = self.texture_bind_group(texture);
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Capture Blit Pass"),
+            // The sprite pipeline is multisampled, so the blit resolves through the MSAA target too.
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: self.msaa_view.as_ref().unwrap_or(view),
+                resolve_target: self.msaa_view.as_ref().map(|_| view),
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.clear_color),
+                    store: if self.msaa_view.is_some() { wgpu::StoreOp::Discard } else { wgpu::StoreOp::Store },
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_stencil_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Discard,
+                }),
+                stencil_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(0),
+                    store: wgpu::StoreOp::Discard,
+                }),
+            }),
+            occlusion_query_set: None,
+            timestamp_writes,
+        });
+        render_pass.set_pipeline(&self.sprite_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.draw(0..6, 0..1);
+    }
+
+    /// Hands the frames the GPU has finished copying to their capture requests.
+    fn deliver_captured_frames(&mut self, wait: bool) {
+        for (image, request) in self.readback.collect(&self.device, wait) {
+            if let Err(e) = image.and_then(|image| self.capture.deliver(image, request)) {
+                log::error!("Failed to capture frame: {}", e);
+            }
+        }
+    }
+
+    /// Returns the render targets drawn into this frame, in the order they were first begun.
+    fn target_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for cmd in self.draw_commands.iter() {
+            if let DrawCommand::BeginTarget(name) = cmd {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
                 }
             }
         }
+        names
+    }
+
+    /// Combines the counters gathered while building and encoding the frame into `stats`.
+    fn finish_frame_stats(&mut self, pass_stats: RenderStats, prepared: &[PreparedCommand]) {
+        let mut stats = RenderStats {
+            buffer_allocations: self.frame_stats.buffer_allocations,
+            buffer_bytes: self.frame_stats.buffer_bytes,
+            ..pass_stats
+        };
+        let text_vertex_size = std::mem::size_of::<TextVertex>() as u64;
+        let rectangle_vertex_size = std::mem::size_of::<RectangleVertex>() as u64;
+        for command in prepared.iter() {
+            match command {
+                PreparedCommand::Rect(_) => stats.record_buffer(6 * rectangle_vertex_size),
+                PreparedCommand::Mesh { vertices, indices, .. } => {
+                    stats.record_buffer(vertices.size());
+                    stats.record_buffer(indices.size());
+                },
+                PreparedCommand::Instances { count, .. } => {
+                    stats.record_buffer(6 * text_vertex_size);
+                    stats.record_buffer(*count as u64 * std::mem::size_of::<InstanceVertex>() as u64);
+                },
+                PreparedCommand::Mask { primitives, sprites } => {
+                    if let Some((_, vertex_count)) = primitives {
+                        stats.record_buffer(*vertex_count as u64 * rectangle_vertex_size);
+                    }
+                    for _ in sprites.iter() {
+                        stats.record_buffer(6 * text_vertex_size);
+                    }
+                },
+                PreparedCommand::Text { count, .. } => stats.record_buffer(*count as u64 * text_vertex_size),
+                _ => {},
+            }
+        }
+        stats.texture_memory = self.textures.values()
+            .map(|texture| texture.size.width as u64 * texture.size.height as u64 * 4)
+            .sum::<u64>()
+            + self.font_atlas.as_ref().map_or(0, |atlas| atlas.width as u64 * atlas.height as u64 * 4);
+
+        if let Some(timer) = self.gpu_timer.as_mut() {
+            timer.after_submit();
+            if let Some(ms) = timer.collect(&self.device) {
+                self.gpu_time_ms = Some(ms);
+            }
+        }
+        stats.gpu_time_ms = self.gpu_time_ms;
+        self.stats = stats;
+        self.frame_stats = RenderStats::default();
+    }
+
+    /// Returns the screen-sized attachments for drawing into `view`.
+    fn frame_attachments<'a>(&'a self, view: &'a wgpu::TextureView) -> PassAttachments<'a> {
+        PassAttachments {
+            view,
+            msaa_view: self.msaa_view.as_ref(),
+            depth_stencil_view: &self.depth_stencil_view,
+            size: (self.size.width, self.size.height),
+        }
+    }
+
+    /// Records the render pass that replays the prepared draw commands into `attachments`.
+    /// Only the commands recorded for `target` are replayed, where `None` is the frame.
+    fn encode_draw_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        attachments: PassAttachments,
+        prepared: &[PreparedCommand],
+        target: Option<&str>,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) -> RenderStats {
+        let mut stats = RenderStats::default();
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
+                // With MSAA the pass renders into the multisampled target and resolves into `view`.
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
+                    view: attachments.msaa_view.unwrap_or(attachments.view),
+                    resolve_target: attachments.msaa_view.map(|_| attachments.view),
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(self.clear_color),
-                        store: wgpu::StoreOp::Store,
+                        // Only the resolved frame is needed once the pass ends.
+                        store: if attachments.msaa_view.is_some() { wgpu::StoreOp::Discard } else { wgpu::StoreOp::Store },
                     },
                 })],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: attachments.depth_stencil_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                }),
                 occlusion_query_set: None,
-                timestamp_writes: None,
+                timestamp_writes,
             });
-            for (i, cmd) in self.draw_commands.iter().enumerate() {
-                match *cmd {
-                    DrawCommand::Sprite(ref sprite) => {
-                        if let Some(ref bind_group) = sprite_bind_groups[i] {
-                            render_pass.set_pipeline(&self.sprite_pipeline);
-                            render_pass.set_bind_group(0, bind_group, &[]);
-                            render_pass.set_vertex_buffer(0, sprite.vertex_buffer.as_ref().unwrap().slice(..));
-                            render_pass.draw(0..sprite.vertex_count as u32, 0..1);
+            let mut scissor = (0, 0, attachments.size.0, attachments.size.1);
+            let mut clip_stack = Vec::new();
+            let mut stencil_level = 0u32;
+            let mut pipeline = None;
+            let mut targets: Vec<&str> = Vec::new();
+            for (cmd, prepared) in self.draw_commands.iter().zip(prepared.iter()) {
+                match cmd {
+                    DrawCommand::BeginTarget(name) => {
+                        targets.push(name);
+                        continue;
+                    },
+                    DrawCommand::EndTarget => {
+                        targets.pop();
+                        continue;
+                    },
+                    _ if targets.last().copied() != target => continue,
+                    _ => {},
+                }
+                match (cmd, prepared) {
+                    (DrawCommand::Sprite(sprite), PreparedCommand::Sprite(bind_group)) => {
+                        Self::set_pipeline(&mut render_pass, &mut pipeline, &self.sprite_pipeline, &mut stats);
+                        render_pass.set_bind_group(0, bind_group, &[]);
+                        stats.bind_group_switches += 1;
+                        render_pass.set_vertex_buffer(0, sprite.vertex_buffer.as_ref().unwrap().slice(..));
+                        Self::draw(&mut render_pass, sprite.vertex_count as u32, &mut stats);
+                    },
+                    (DrawCommand::Rect { .. }, PreparedCommand::Rect(vertex_buffer)) => {
+                        Self::set_pipeline(&mut render_pass, &mut pipeline, &self.rectangle_pipeline, &mut stats);
+                        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                        Self::draw(&mut render_pass, 6, &mut stats);
+                    },
+                    (DrawCommand::Instances { .. }, PreparedCommand::Instances { bind_group, quad, instances, count }) => {
+                        Self::set_pipeline(&mut render_pass, &mut pipeline, &self.instanced_sprite_pipeline, &mut stats);
+                        render_pass.set_bind_group(0, bind_group, &[]);
+                        stats.bind_group_switches += 1;
+                        render_pass.set_vertex_buffer(0, quad.slice(..));
+                        render_pass.set_vertex_buffer(1, instances.slice(..));
+                        render_pass.draw(0..6, 0..*count);
+                        stats.draw_calls += 1;
+                        stats.vertices += 6 * count;
+                    },
+                    (DrawCommand::Mesh { .. }, PreparedCommand::Mesh { bind_group, vertices, indices, index_count }) => {
+                        Self::set_pipeline(&mut render_pass, &mut pipeline, &self.sprite_pipeline, &mut stats);
+                        render_pass.set_bind_group(0, bind_group, &[]);
+                        stats.bind_group_switches += 1;
+                        render_pass.set_vertex_buffer(0, vertices.slice(..));
+                        render_pass.set_index_buffer(indices.slice(..), wgpu::IndexFormat::Uint32);
+                        render_pass.draw_indexed(0..*index_count, 0, 0..1);
+                        stats.draw_calls += 1;
+                        stats.vertices += index_count;
+                    },
+                    (DrawCommand::Text(_), PreparedCommand::Text { vertices, count }) => {
+                        if let Some(font_atlas) = self.font_atlas.as_ref() {
+                            Self::set_pipeline(&mut render_pass, &mut pipeline, &self.text_pipeline, &mut stats);
+                            render_pass.set_bind_group(0, &font_atlas.bind_group, &[]);
+                            stats.bind_group_switches += 1;
+                            render_pass.set_vertex_buffer(0, vertices.slice(..));
+                            Self::draw(&mut render_pass, *count, &mut stats);
                         }
                     },
-                    DrawCommand::Rect { x, y, width, height, color } => {
-                        if let Some(ref vertex_buffer) = rect_vertex_buffers[i] {
-                            render_pass.set_pipeline(&self.rectangle_pipeline);
+                    (DrawCommand::PushScissor(rect), _) => {
+                        clip_stack.push(ClipLevel::Scissor(scissor));
+                        scissor = Self::intersect_scissor(scissor, *rect);
+                        render_pass.set_scissor_rect(scissor.0, scissor.1, scissor.2, scissor.3);
+                    },
+                    (DrawCommand::PushMask(_), PreparedCommand::Mask { primitives, sprites }) => {
+                        // Raise the stencil value of every pixel inside both the current and the new mask.
+                        if let Some((vertex_buffer, vertex_count)) = primitives {
+                            Self::set_pipeline(&mut render_pass, &mut pipeline, &self.stencil_increment_pipeline, &mut stats);
+                            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                            Self::draw(&mut render_pass, *vertex_count, &mut stats);
+                        }
+                        for (bind_group, vertex_buffer) in sprites.iter() {
+                            Self::set_pipeline(&mut render_pass, &mut pipeline, &self.stencil_sprite_pipeline, &mut stats);
+                            render_pass.set_bind_group(0, bind_group, &[]);
+                            stats.bind_group_switches += 1;
                             render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-                            render_pass.draw(0..6, 0..1);
+                            Self::draw(&mut render_pass, 6, &mut stats);
                         }
+                        clip_stack.push(ClipLevel::Mask);
+                        stencil_level += 1;
+                        render_pass.set_stencil_reference(stencil_level);
+                    },
+                    (DrawCommand::PopClip, _) => match clip_stack.pop() {
+                        Some(ClipLevel::Scissor(previous)) => {
+                            scissor = previous;
+                            render_pass.set_scissor_rect(scissor.0, scissor.1, scissor.2, scissor.3);
+                        },
+                        Some(ClipLevel::Mask) => {
+                            // Lower every pixel at the current level back to the enclosing mask.
+                            Self::set_pipeline(&mut render_pass, &mut pipeline, &self.stencil_decrement_pipeline, &mut stats);
+                            render_pass.set_vertex_buffer(0, self.fullscreen_vertex_buffer.slice(..));
+                            Self::draw(&mut render_pass, 6, &mut stats);
+                            stencil_level -= 1;
+                            render_pass.set_stencil_reference(stencil_level);
+                        },
+                        None => {},
                     },
+                    _ => {},
                 }
             }
-            
-            // Render text on top of everything
-            if let Some(ref text_vertex_buffer) = self.current_text_vertex_buffer {
-                if let Some(ref font_atlas) = self.font_atlas {
-                    render_pass.set_pipeline(&self.text_pipeline);
-                    render_pass.set_bind_group(0, &font_atlas.bind_group, &[]);
-                    render_pass.set_vertex_buffer(0, text_vertex_buffer.slice(..));
-                    render_pass.draw(0..self.text_vertices.len() as u32, 0..1);
+        }
+        stats
+    }
+
+    /// Binds `next` unless it is already the active pipeline.
+    fn set_pipeline<'a>(render_pass: &mut wgpu::RenderPass<'a>, current: &mut Option<&'a wgpu::RenderPipeline>, next: &'a wgpu::RenderPipeline, stats: &mut RenderStats) {
+        if !current.is_some_and(|pipeline| std::ptr::eq(pipeline, next)) {
+            render_pass.set_pipeline(next);
+            *current = Some(next);
+            stats.pipeline_switches += 1;
+        }
+    }
+
+    fn draw(render_pass: &mut wgpu::RenderPass, vertex_count: u32, stats: &mut RenderStats) {
+        render_pass.draw(0..vertex_count, 0..1);
+        stats.draw_calls += 1;
+        stats.vertices += vertex_count;
+    }
+
+    fn prepare_command(&self, cmd: &DrawCommand) -> PreparedCommand {
+        match cmd {
+            DrawCommand::Sprite(sprite) => {
+                match sprite.get_texture_path().and_then(|path| self.sprite_bind_group(path)) {
+                    Some(bind_group) if sprite.vertex_buffer.is_some() => PreparedCommand::Sprite(bind_group),
+                    _ => PreparedCommand::None,
                 }
-            }
+            },
+            DrawCommand::Rect { x, y, width, height, color } => {
+                let color = *color;
+                let [x1, y1] = self.to_ndc(*x as f32, *y as f32);
+                let [x2, y2] = self.to_ndc((*x + *width as i32) as f32, (*y + *height as i32) as f32);
+                let vertices = [
+                    RectangleVertex { position: [x1, y1], color },
+                    RectangleVertex { position: [x2, y1], color },
+                    RectangleVertex { position: [x2, y2], color },
+                    RectangleVertex { position: [x1, y1], color },
+                    RectangleVertex { position: [x2, y2], color },
+                    RectangleVertex { position: [x1, y2], color },
+                ];
+                let vertex_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Rectangle Vertex Buffer (temp)"),
+                    contents: bytemuck::cast_slice(&vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+                PreparedCommand::Rect(vertex_buffer)
+            },
+            DrawCommand::PushMask(shapes) => {
+                let mut vertices = Vec::new();
+                let mut sprites = Vec::new();
+                for shape in shapes.iter() {
+                    match (shape, shape.triangles()) {
+                        (_, Some(triangles)) => {
+                            vertices.extend(triangles.iter().map(|[x, y]| RectangleVertex {
+                                position: self.to_ndc(*x, *y),
+                                color: [1.0, 1.0, 1.0, 1.0],
+                            }));
+                        },
+                        (ClipMask::Sprite(sprite), None) => {
+                            if let Some(bind_group) = sprite.get_texture_path().and_then(|path| self.sprite_bind_group(path)) {
+                                let vertex_buffer = sprite.create_vertex_buffer(&self.device, self.size.width as f32, self.size.height as f32);
+                                sprites.push((bind_group, vertex_buffer));
+                            }
+                        },
+                        _ => {},
+                    }
+                }
+                let primitives = if vertices.is_empty() {
+                    None
+                } else {
+                    let vertex_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Mask Vertex Buffer (temp)"),
+                        contents: bytemuck::cast_slice(&vertices),
+                        usage: wgpu::BufferUsages::VERTEX,
+                    });
+                    Some((vertex_buffer, vertices.len() as u32))
+                };
+                PreparedCommand::Mask { primitives, sprites }
+            },
+            DrawCommand::Instances { texture_path, src, instances } => {
+                match (self.textures.get(texture_path), self.sprite_bind_group(texture_path)) {
+                    (Some(texture), Some(bind_group)) if !instances.is_empty() => {
+                        let quad = self.instance_quad(*src, (texture.size.width, texture.size.height));
+                        let vertices: Vec<InstanceVertex> = instances.iter().map(|instance| self.instance_vertex(instance)).collect();
+                        let instances = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Sprite Instance Buffer (temp)"),
+                            contents: bytemuck::cast_slice(&vertices),
+                            usage: wgpu::BufferUsages::VERTEX,
+                        });
+                        PreparedCommand::Instances { bind_group, quad, instances, count: vertices.len() as u32 }
+                    },
+                    _ => PreparedCommand::None,
+                }
+            },
+            DrawCommand::Mesh { vertices, indices, texture_path } => {
+                let bind_group = match texture_path {
+                    Some(path) => self.sprite_bind_group(path),
+                    None => Some(self.texture_bind_group(&self.default_texture)),
+                };
+                match bind_group {
+                    Some(bind_group) if !indices.is_empty() => {
+                        let converted: Vec<TextVertex> = vertices.iter().map(|v| TextVertex {
+                            position: self.to_ndc(v.position[0], v.position[1]),
+                            color: v.color,
+                            tex_coords: v.tex_coords,
+                        }).collect();
+                        let vertices = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Mesh Vertex Buffer (temp)"),
+                            contents: bytemuck::cast_slice(&converted),
+                            usage: wgpu::BufferUsages::VERTEX,
+                        });
+                        let index_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Mesh Index Buffer (temp)"),
+                            contents: bytemuck::cast_slice(indices),
+                            usage: wgpu::BufferUsages::INDEX,
+                        });
+                        PreparedCommand::Mesh { bind_group, vertices, indices: index_buffer, index_count: indices.len() as u32 }
+                    },
+                    _ => PreparedCommand::None,
+                }
+            },
+            DrawCommand::Text(vertices) if !vertices.is_empty() && self.font_atlas.is_some() => {
+                let buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Text Vertex Buffer"),
+                    contents: bytemuck::cast_slice(vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+                PreparedCommand::Text { vertices: buffer, count: vertices.len() as u32 }
+            },
+            DrawCommand::Text(_) => PreparedCommand::None,
+            DrawCommand::PushScissor(_) | DrawCommand::PopClip | DrawCommand::BeginTarget(_) | DrawCommand::EndTarget => PreparedCommand::None,
         }
-        
-        self.queue.submit(std::iter::once(encoder.finish()));
-        frame.present();
-        self.clear_draw_commands();
+    }
+
+    fn sprite_bind_group(&self, texture_path: &str) -> Option<wgpu::BindGroup> {
+        self.textures.get(texture_path).map(|texture| self.texture_bind_group(texture))
+    }
+
+    fn texture_bind_group(&self, texture: &Texture) -> wgpu::BindGroup {
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.sprite_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: Some("Sprite Bind Group"),
+        })
+    }
+
+    /// Builds the unit quad shared by every instance, textured with `src`.
+    fn instance_quad(&self, src: BarnRect, texture_size: (u32, u32)) -> wgpu::Buffer {
+        let (tex_w, tex_h) = (texture_size.0 as f32, texture_size.1 as f32);
+        let (u0, v0) = (src.0 / tex_w, src.1 / tex_h);
+        let (u1, v1) = ((src.0 + src.2) / tex_w, (src.1 + src.3) / tex_h);
+        let color = [1.0, 1.0, 1.0, 1.0];
+        let vertices = [
+            TextVertex { position: [-0.5, -0.5], color, tex_coords: [u0, v0] },
+            TextVertex { position: [0.5, -0.5], color, tex_coords: [u1, v0] },
+            TextVertex { position: [-0.5, 0.5], color, tex_coords: [u0, v1] },
+            TextVertex { position: [0.5, -0.5], color, tex_coords: [u1, v0] },
+            TextVertex { position: [0.5, 0.5], color, tex_coords: [u1, v1] },
+            TextVertex { position: [-0.5, 0.5], color, tex_coords: [u0, v1] },
+        ];
+        self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance Quad Buffer (temp)"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        })
+    }
+
+    /// Bakes an instance's pixel-space size and rotation into NDC axes. Pixel y points
+    /// down while NDC y points up, hence the negated y components.
+    fn instance_vertex(&self, instance: &SpriteInstance) -> InstanceVertex {
+        let (sin, cos) = instance.rotation.sin_cos();
+        let (scale_x, scale_y) = (2.0 / self.size.width as f32, 2.0 / self.size.height as f32);
+        let [w, h] = instance.size;
+        InstanceVertex {
+            center: self.to_ndc(instance.position[0], instance.position[1]),
+            axis_x: [w * cos * scale_x, -w * sin * scale_y],
+            axis_y: [-h * sin * scale_x, -h * cos * scale_y],
+            color: instance.color,
+        }
+    }
+
+    /// Converts a position in screen pixels to normalized device coordinates.
+    fn to_ndc(&self, x: f32, y: f32) -> [f32; 2] {
+        [
+            (x / self.size.width as f32) * 2.0 - 1.0,
+            1.0 - (y / self.size.height as f32) * 2.0,
+        ]
+    }
+
+    fn intersect_scissor(current: (u32, u32, u32, u32), rect: BarnRect) -> (u32, u32, u32, u32) {
+        let (cx, cy, cw, ch) = current;
+        let x1 = (rect.0.max(0.0) as u32).max(cx);
+        let y1 = (rect.1.max(0.0) as u32).max(cy);
+        let x2 = ((rect.0 + rect.2).max(0.0) as u32).min(cx + cw);
+        let y2 = ((rect.1 + rect.3).max(0.0) as u32).min(cy + ch);
+        (x1.min(x2), y1.min(y2), x2.saturating_sub(x1), y2.saturating_sub(y1))
     }
     
-    pub fn render(&mut self, surface: &mut wgpu::Surface) {
-        self.present(surface);
+    pub fn render(&mut self, surface: &mut wgpu::Surface) -> Result<(), wgpu::SurfaceError> {
+        self.present(surface)
     }
     
     pub fn load_texture(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
@@ -536,22 +1470,30 @@ impl WgpuRenderer {
         Ok(())
     }
     
+    /// Uploads an image that is already in memory, e.g. an atlas page, under `path`.
+    fn upload_texture(&mut self, path: &str, image: &image::RgbaImage) {
+        let texture = Texture::from_image(&self.device, &self.queue, image, path);
+        self.textures.insert(path.to_string(), texture);
+        self.render_targets.remove(path);
+    }
+
+    /// Draws a line of text with the loaded font atlas, in order with the other draw
+    /// commands and into the open render target, if any. Glyphs are scaled from the
+    /// atlas to `font_size`.
     pub fn draw_text(&mut self, text: &str, x: f32, y: f32, font_size: f32, color: [f32; 4]) {
-        self.text_vertices.clear();
+        let mut vertices = Vec::new();
         if let Some(ref font_atlas) = self.font_atlas {
             let screen_width = self.size.width as f32;
             let screen_height = self.size.height as f32;
+            let scale = font_size / font_atlas.font_size;
             
             let mut current_x = x; // accumulate in pixels
-            let mut first = true;
-            let mut char_count = 0;
             for c in text.chars() {
-                char_count += 1;
                 if let Some(glyph_info) = font_atlas.glyphs.get(&c) {
-                    let char_width = glyph_info.size[0];
-                    let char_height = glyph_info.size[1];
-                    let offset_x = glyph_info.offset[0];
-                    let offset_y = glyph_info.offset[1];
+                    let char_width = glyph_info.size[0] * scale;
+                    let char_height = glyph_info.size[1] * scale;
+                    let offset_x = glyph_info.offset[0] * scale;
+                    let offset_y = glyph_info.offset[1] * scale;
                     let px1 = current_x + offset_x;
                     let py1 = y + offset_y;
                     let px2 = px1 + char_width;
@@ -571,28 +1513,22 @@ impl WgpuRenderer {
                         TextVertex { position: [ndc_x1, ndc_y2], color, tex_coords: [u0, v1] },
                         TextVertex { position: [ndc_x2, ndc_y2], color, tex_coords: [u1, v1] },
                     ];
-                    self.text_vertices.extend_from_slice(&verts);
+                    vertices.extend_from_slice(&verts);
                     let advance = if c == ' ' {
                         16.0 // Fixed width for space
                     } else {
                         glyph_info.advance
                     };
-                    current_x += advance;
+                    current_x += advance * scale;
                 } else {
-                    println!("Character '{}' not found in atlas!", c);
+                    log::warn!("Character '{}' not found in atlas!", c);
                 }
             }
         } else {
-            println!("No font atlas available!");
+            log::warn!("No font atlas available!");
         }
-        if !self.text_vertices.is_empty() {
-            self.current_text_vertex_buffer = Some(
-                self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Text Vertex Buffer"),
-                    contents: bytemuck::cast_slice(&self.text_vertices),
-                    usage: wgpu::BufferUsages::VERTEX,
-                })
-            );
+        if !vertices.is_empty() {
+            self.draw_commands.push(DrawCommand::Text(vertices));
         }
     }
 
@@ -606,37 +1542,127 @@ impl WgpuRenderer {
             );
             sprite.vertex_buffer = Some(buffer);
             sprite.vertex_count = 6;
+            self.frame_stats.record_buffer(6 * std::mem::size_of::<TextVertex>() as u64);
         }
         self.draw_commands.push(DrawCommand::Sprite(sprite));
     }
 
+    /// Draws the `src` region of a loaded texture once per instance with a single
+    /// instanced draw call. Useful for bullets, particles, crowds and foliage.
+    pub fn draw_sprite_instances(&mut self, texture_path: &str, src: BarnRect, instances: &[SpriteInstance]) {
+        self.draw_commands.push(DrawCommand::Instances {
+            texture_path: texture_path.to_string(),
+            src,
+            instances: instances.to_vec(),
+        });
+    }
+
+    /// Draws an indexed triangle list. Positions are in screen pixels; without a
+    /// texture the vertex colors are drawn as they are. Indices that point past the
+    /// end of `vertices` are dropped along with their triangle.
+    pub fn draw_mesh(&mut self, vertices: &[MeshVertex], indices: &[u32], texture_path: Option<&str>) {
+        let count = vertices.len() as u32;
+        let indices: Vec<u32> = indices.chunks_exact(3)
+            .filter(|triangle| triangle.iter().all(|&i| i < count))
+            .flatten()
+            .copied()
+            .collect();
+        self.draw_commands.push(DrawCommand::Mesh {
+            vertices: vertices.to_vec(),
+            indices,
+            texture_path: texture_path.map(|path| path.to_string()),
+        });
+    }
+
     pub fn draw_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: [f32; 4]) {
         self.draw_commands.push(DrawCommand::Rect { x, y, width, height, color });
     }
 
+    /// Restricts subsequent drawing to `rect`, intersected with any active scissor.
+    pub fn push_scissor(&mut self, rect: BarnRect) {
+        self.draw_commands.push(DrawCommand::PushScissor(rect));
+    }
+
+    /// Restricts subsequent drawing to the union of `shapes`, intersected with
+    /// any active mask. Useful for circular minimaps and reveal effects.
+    pub fn push_mask(&mut self, shapes: Vec<ClipMask>) {
+        self.draw_commands.push(DrawCommand::PushMask(shapes));
+    }
+
+    /// Removes the most recently pushed scissor or mask.
+    pub fn pop_clip(&mut self) {
+        self.draw_commands.push(DrawCommand::PopClip);
+    }
+
+    /// Draws everything up to the matching `end_render_target` into a texture the size
+    /// of the screen instead of the frame. The texture is cleared to the clear color,
+    /// kept between frames and drawn like any loaded texture under `name`. A target
+    /// must not sample itself.
+    pub fn begin_render_target(&mut self, name: &str) {
+        let (width, height) = (self.size.width.max(1), self.size.height.max(1));
+        let current = self.textures.get(name).map(|texture| (texture.size.width, texture.size.height));
+        if !self.render_targets.contains(name) || current != Some((width, height)) {
+            let texture = Texture::render_target(&self.device, self.surface_format, width, height, name);
+            self.textures.insert(name.to_string(), texture);
+            self.render_targets.insert(name.to_string());
+        }
+        self.open_targets.push(name.to_string());
+        self.draw_commands.push(DrawCommand::BeginTarget(name.to_string()));
+    }
+
+    /// Returns drawing to the render target that was active before the last
+    /// `begin_render_target`, or to the frame.
+    pub fn end_render_target(&mut self) {
+        if self.open_targets.pop().is_some() {
+            self.draw_commands.push(DrawCommand::EndTarget);
+        }
+    }
+
+    /// Keeps a copy of the next presented frame for `take_captured_frame`.
+    pub fn capture_next_frame(&mut self) {
+        self.capture.capture_next_frame();
+    }
+
+    /// Returns the frame captured after `capture_next_frame`, once it has been read
+    /// back, usually a frame or two after it was presented.
+    pub fn take_captured_frame(&mut self) -> Option<image::RgbaImage> {
+        self.capture.take_captured_frame()
+    }
+
+    /// Saves the next presented frame as an image once it has been read back.
+    /// The format follows the file extension. The file is written on the capture
+    /// thread; `capture.flush()` waits for it.
+    pub fn save_screenshot(&mut self, path: &str) {
+        self.capture.save_next_frame(path);
+    }
+
+    /// Starts writing every presented frame to disk, replacing any active recording.
+    pub fn start_recording(&mut self, format: RecordingFormat) -> Result<(), Box<dyn std::error::Error>> {
+        check_format(self.surface_format)?;
+        self.deliver_captured_frames(true);
+        self.capture.start_recording(format)
+    }
+
+    /// Writes the frames still being read back, then closes the recording.
+    pub fn stop_recording(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.deliver_captured_frames(true);
+        self.capture.stop_recording()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.capture.is_recording()
+    }
+
     pub fn clear_draw_commands(&mut self) {
         self.draw_commands.clear();
+        self.open_targets.clear();
     }
 
-    fn create_font_atlas(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout, font_path: &str, font_size: f32) -> FontAtlas {
-        // Check if file exists
-        if !std::path::Path::new(font_path).exists() {
-            panic!("Font file does not exist: {}", font_path);
-        }
-        
-        let font_data = match std::fs::read(font_path) {
-            Ok(data) => {
-                data
-            },
-            Err(e) => panic!("Failed to read font file: {}", e),
-        };
-        
-        let font = match FontArc::try_from_vec(font_data) {
-            Ok(font) => {
-                font
-            },
-            Err(e) => panic!("Failed to load font: {}", e),
-        };
+    fn create_font_atlas(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout, font_path: &str, font_size: f32) -> Result<FontAtlas, Box<dyn std::error::Error>> {
+        let font_data = std::fs::read(font_path)
+            .map_err(|e| format!("Failed to read font file {}: {}", font_path, e))?;
+        let font = FontArc::try_from_vec(font_data)
+            .map_err(|e| format!("Failed to load font {}: {}", font_path, e))?;
         
         let scale = PxScale::from(font_size);
         let mut charset: Vec<char> = (32u8..127u8).map(|c| c as char).collect();
@@ -810,7 +1836,7 @@ impl WgpuRenderer {
             label: Some("Font Atlas Bind Group"),
         });
         
-        FontAtlas {
+        Ok(FontAtlas {
             texture,
             view,
             sampler,
@@ -818,6 +1844,122 @@ impl WgpuRenderer {
             glyphs,
             width: atlas_width,
             height: atlas_height,
+            font_size,
+        })
+    }
+}
+
+impl Renderer for WgpuRenderer {
+    fn size(&self) -> (u32, u32) {
+        (self.size.width, self.size.height)
+    }
+
+    fn set_clear_color(&mut self, r: f64, g: f64, b: f64, a: f64) {
+        WgpuRenderer::set_clear_color(self, r, g, b, a);
+    }
+
+    fn load_texture(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        WgpuRenderer::load_texture(self, path)
+    }
+
+    fn add_texture(&mut self, path: &str, image: image::RgbaImage) {
+        self.upload_texture(path, &image);
+    }
+
+    /// Uploads the pages without copying them first.
+    fn load_atlas(&mut self, atlas: &TextureAtlas) {
+        for page in atlas.pages.iter() {
+            self.upload_texture(&page.key, &page.image);
         }
     }
-} 
\ No newline at end of file
+
+    fn texture_size(&self, path: &str) -> Option<(u32, u32)> {
+        self.textures.get(path).map(|texture| (texture.size.width, texture.size.height))
+    }
+
+    fn draw_sprite(&mut self, sprite: WgpuSprite) {
+        WgpuRenderer::draw_sprite(self, sprite);
+    }
+
+    fn draw_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: [f32; 4]) {
+        WgpuRenderer::draw_rect(self, x, y, width, height, color);
+    }
+
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, font_size: f32, color: [f32; 4]) {
+        WgpuRenderer::draw_text(self, text, x, y, font_size, color);
+    }
+
+    fn draw_mesh(&mut self, vertices: &[MeshVertex], indices: &[u32], texture_path: Option<&str>) {
+        WgpuRenderer::draw_mesh(self, vertices, indices, texture_path);
+    }
+
+    fn begin_render_target(&mut self, name: &str) {
+        WgpuRenderer::begin_render_target(self, name);
+    }
+
+    fn end_render_target(&mut self) {
+        WgpuRenderer::end_render_target(self);
+    }
+
+    fn clear_draw_commands(&mut self) {
+        WgpuRenderer::clear_draw_commands(self);
+    }
+
+    fn push_scissor(&mut self, rect: BarnRect) {
+        WgpuRenderer::push_scissor(self, rect);
+    }
+
+    fn push_mask(&mut self, shapes: Vec<ClipMask>) {
+        WgpuRenderer::push_mask(self, shapes);
+    }
+
+    fn pop_clip(&mut self) {
+        WgpuRenderer::pop_clip(self);
+    }
+
+    fn capture_next_frame(&mut self) {
+        WgpuRenderer::capture_next_frame(self);
+    }
+
+    fn take_captured_frame(&mut self) -> Option<image::RgbaImage> {
+        WgpuRenderer::take_captured_frame(self)
+    }
+
+    fn save_screenshot(&mut self, path: &str) {
+        WgpuRenderer::save_screenshot(self, path);
+    }
+
+    fn start_recording(&mut self, format: RecordingFormat) -> Result<(), Box<dyn std::error::Error>> {
+        WgpuRenderer::start_recording(self, format)
+    }
+
+    fn stop_recording(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        WgpuRenderer::stop_recording(self)
+    }
+
+    fn is_recording(&self) -> bool {
+        WgpuRenderer::is_recording(self)
+    }
+
+    fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    /// Returns the settings currently in effect. `msaa_samples` holds the sample count
+    /// actually used, which may be lower than the one requested.
+    fn settings(&self) -> RenderSettings {
+        RenderSettings { msaa_samples: self.sample_count, ..self.settings }
+    }
+
+    /// Vsync takes effect on the next frame and MSAA immediately; the power profile
+    /// and backend are only remembered, as they apply when a renderer is created.
+    fn apply_settings(&mut self, settings: RenderSettings) {
+        self.set_vsync(settings.vsync);
+        self.set_msaa(settings.msaa_samples);
+        self.settings = settings;
+    }
+
+    fn draw_sprite_instances(&mut self, texture_path: &str, src: BarnRect, instances: &[SpriteInstance]) {
+        WgpuRenderer::draw_sprite_instances(self, texture_path, src, instances);
+    }
+}
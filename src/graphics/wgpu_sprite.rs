@@ -60,6 +60,20 @@ impl WgpuSprite {
         }
     }
 
+    /// Creates a sprite without a vertex buffer. The renderer builds the buffer
+    /// when the sprite is drawn, so no device is needed up front.
+    pub fn deferred(src: Rect, dst: Rect, color: [f32; 4], texture_path: &str, texture_size: (u32, u32)) -> WgpuSprite {
+        WgpuSprite {
+            src,
+            dst,
+            color,
+            texture_path: Some(texture_path.to_string()),
+            texture_size: Some(texture_size),
+            vertex_buffer: None,
+            vertex_count: 6,
+        }
+    }
+
     pub fn set_position(&mut self, x: f32, y: f32, device: &wgpu::Device, _texture_size: (u32, u32), screen_width: f32, screen_height: f32) {
         self.dst.0 = x;
         self.dst.1 = y;
@@ -4,3 +4,4 @@ pub mod game;
 pub mod graphics;
 pub mod input;
 pub mod math;
+pub mod tiled;
@@ -0,0 +1,44 @@
+/// Curves that map linear progress from 0.0 to 1.0 onto eased progress.
+///
+/// # Examples
+///
+/// ```
+/// use barn::math::easing::Easing;
+///
+/// assert_eq!(Easing::QuadIn.apply(0.5), 0.25);
+/// assert_eq!(Easing::QuadOut.apply(0.5), 0.75);
+/// assert_eq!(Easing::CubicInOut.apply(1.0), 1.0);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineInOut,
+}
+
+impl Easing {
+    /// Eases `t`, which is clamped to 0.0..=1.0 first.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::QuadInOut => {
+                if t < 0.5 { 2.0 * t * t } else { 1.0 - (-2.0 * t + 2.0).powi(2) / 2.0 }
+            },
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 { 4.0 * t * t * t } else { 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0 }
+            },
+            Easing::SineInOut => -((std::f32::consts::PI * t).cos() - 1.0) / 2.0,
+        }
+    }
+}
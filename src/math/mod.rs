@@ -1,3 +1,4 @@
 pub mod vector2;
 pub mod bounding_box_2d;
 pub mod bounding_circle_2d;
+pub mod easing;
@@ -0,0 +1,257 @@
+use std::io::Read;
+
+use crate::math::bounding_box_2d::BoundingBox2D;
+use crate::math::vector2::Vector2;
+use crate::tiled::object::MapObject;
+use crate::tiled::properties::Properties;
+use crate::tiled::tileset::TiledImage;
+
+const FLIPPED_HORIZONTALLY_FLAG: u32 = 0x80000000;
+const FLIPPED_VERTICALLY_FLAG: u32 = 0x40000000;
+const FLIPPED_DIAGONALLY_FLAG: u32 = 0x20000000;
+const ROTATED_HEXAGONAL_120_FLAG: u32 = 0x10000000;
+
+/// A single placed tile on a tile layer.
+#[derive(Clone, Copy, PartialEq)]
+pub struct LayerTile {
+    /// Global tile id with the flip flags stripped.
+    pub gid: u32,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    pub flip_diagonal: bool,
+}
+
+impl LayerTile {
+    /// Decodes a raw global id as stored in Tiled files. Returns `None` for empty cells.
+    pub fn from_raw(raw: u32) -> Option<LayerTile> {
+        let gid = raw & !(FLIPPED_HORIZONTALLY_FLAG | FLIPPED_VERTICALLY_FLAG | FLIPPED_DIAGONALLY_FLAG | ROTATED_HEXAGONAL_120_FLAG);
+        if gid == 0 {
+            return None;
+        }
+        Some(LayerTile {
+            gid,
+            flip_horizontal: raw & FLIPPED_HORIZONTALLY_FLAG != 0,
+            flip_vertical: raw & FLIPPED_VERTICALLY_FLAG != 0,
+            flip_diagonal: raw & FLIPPED_DIAGONALLY_FLAG != 0,
+        })
+    }
+}
+
+/// Settings shared by every kind of layer.
+#[derive(Clone)]
+pub struct LayerInfo {
+    pub id: u32,
+    pub name: String,
+    pub class: String,
+    pub visible: bool,
+    pub opacity: f32,
+    /// Pixel offset applied when drawing the layer.
+    pub offset: Vector2,
+    pub parallax: Vector2,
+    pub properties: Properties,
+}
+
+#[derive(Clone)]
+pub struct TileLayer {
+    pub info: LayerInfo,
+    /// Width of the layer in tiles.
+    pub width: u32,
+    /// Height of the layer in tiles.
+    pub height: u32,
+    /// Tiles in row-major order. Empty cells are `None`.
+    pub tiles: Vec<Option<LayerTile>>,
+}
+
+impl TileLayer {
+    /// Creates a layer, checking that there is exactly one tile per cell so that
+    /// truncated or corrupt data doesn't load as a shorter layer.
+    pub(crate) fn new(info: LayerInfo, width: u32, height: u32, tiles: Vec<Option<LayerTile>>) -> Result<TileLayer, Box<dyn std::error::Error>> {
+        let expected = width as usize * height as usize;
+        if tiles.len() != expected {
+            return Err(format!("tile layer '{}' has {} tiles but is {}x{}", info.name, tiles.len(), width, height).into());
+        }
+        Ok(TileLayer { info, width, height, tiles })
+    }
+
+    pub fn tile_at(&self, x: u32, y: u32) -> Option<&LayerTile> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.tiles.get((y * self.width + x) as usize).and_then(|t| t.as_ref())
+    }
+}
+
+#[derive(Clone)]
+pub struct ObjectLayer {
+    pub info: LayerInfo,
+    pub objects: Vec<MapObject>,
+}
+
+impl ObjectLayer {
+    /// Returns the bounding boxes of every object on the layer, e.g. for collision.
+    pub fn bounding_boxes(&self) -> Vec<BoundingBox2D> {
+        self.objects.iter().map(|o| o.bounding_box()).collect()
+    }
+
+    pub fn object(&self, name: &str) -> Option<&MapObject> {
+        self.objects.iter().find(|o| o.name == name)
+    }
+}
+
+#[derive(Clone)]
+pub struct ImageLayer {
+    pub info: LayerInfo,
+    pub image: Option<TiledImage>,
+    pub repeat_x: bool,
+    pub repeat_y: bool,
+}
+
+#[derive(Clone)]
+pub struct GroupLayer {
+    pub info: LayerInfo,
+    pub layers: Vec<Layer>,
+}
+
+#[derive(Clone)]
+pub enum Layer {
+    Tiles(TileLayer),
+    Objects(ObjectLayer),
+    Image(ImageLayer),
+    Group(GroupLayer),
+}
+
+impl Layer {
+    pub fn info(&self) -> &LayerInfo {
+        match self {
+            Layer::Tiles(layer) => &layer.info,
+            Layer::Objects(layer) => &layer.info,
+            Layer::Image(layer) => &layer.info,
+            Layer::Group(layer) => &layer.info,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.info().name
+    }
+}
+
+/// Decodes the contents of a tile layer's data, in either CSV or base64 encoding.
+pub(crate) fn decode_tile_data(data: &str, encoding: &str, compression: &str) -> Result<Vec<Option<LayerTile>>, Box<dyn std::error::Error>> {
+    let raw: Vec<u32> = match encoding {
+        "csv" => data
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<u32>())
+            .collect::<Result<_, _>>()?,
+        "base64" => {
+            let bytes = decode_base64(data)?;
+            let bytes = match compression {
+                "" => bytes,
+                "zlib" => {
+                    let mut out = Vec::new();
+                    flate2::read::ZlibDecoder::new(&bytes[..]).read_to_end(&mut out)?;
+                    out
+                }
+                "gzip" => {
+                    let mut out = Vec::new();
+                    flate2::read::GzDecoder::new(&bytes[..]).read_to_end(&mut out)?;
+                    out
+                }
+                other => return Err(format!("unsupported tile layer compression '{}'", other).into()),
+            };
+            if bytes.len() % 4 != 0 {
+                return Err(format!("tile layer data is {} bytes, not a multiple of 4", bytes.len()).into());
+            }
+            bytes.chunks_exact(4)
+                .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .collect()
+        }
+        other => return Err(format!("unsupported tile layer encoding '{}'", other).into()),
+    };
+    Ok(raw.into_iter().map(LayerTile::from_raw).collect())
+}
+
+/// Decodes padded base64, ignoring whitespace. Truncated data and data after the
+/// padding are rejected rather than decoded to fewer tiles.
+fn decode_base64(data: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    use base64::Engine;
+
+    let data: String = data.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    base64::engine::general_purpose::STANDARD.decode(&data).map_err(|e| match e {
+        base64::DecodeError::InvalidByte(_, byte) => format!("invalid base64 character '{}'", byte as char).into(),
+        e => format!("invalid base64 tile data: {}", e).into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_error(data: &str, encoding: &str, compression: &str) -> String {
+        match decode_tile_data(data, encoding, compression) {
+            Ok(_) => panic!("{:?} should not decode", data),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn from_raw_strips_every_flag() {
+        let tile = LayerTile::from_raw(5 | FLIPPED_HORIZONTALLY_FLAG | FLIPPED_DIAGONALLY_FLAG | ROTATED_HEXAGONAL_120_FLAG).unwrap();
+        assert_eq!(tile.gid, 5);
+        assert!(tile.flip_horizontal && !tile.flip_vertical && tile.flip_diagonal);
+        assert!(LayerTile::from_raw(0).is_none());
+        // A flipped empty cell is still empty
+        assert!(LayerTile::from_raw(FLIPPED_VERTICALLY_FLAG).is_none());
+    }
+
+    #[test]
+    fn csv_ignores_whitespace_and_trailing_commas() {
+        let tiles = decode_tile_data("\n1, 0,\n 2147483651,\n", "csv", "").unwrap();
+        assert_eq!(tiles.len(), 3);
+        assert!(tiles[1].is_none());
+        assert!(tiles[2].unwrap().gid == 3 && tiles[2].unwrap().flip_horizontal);
+    }
+
+    #[test]
+    fn base64_ignores_whitespace() {
+        let tiles = decode_tile_data(" AQAA\n AAIAAAA= ", "base64", "").unwrap();
+        assert_eq!(tiles.iter().map(|t| t.map(|t| t.gid)).collect::<Vec<_>>(), vec![Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn rejects_partial_tiles() {
+        // Five bytes: one tile and a stray byte
+        assert!(decode_error("AQAAAAI=", "base64", "").contains("not a multiple of 4"));
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(decode_error("1,x,3", "csv", "").contains("invalid digit"));
+        assert!(decode_error("AQ*A", "base64", "").contains("invalid base64 character"));
+        // Truncated data and data after the padding
+        assert!(decode_error("AQAAAAIAAAA", "base64", "").contains("invalid base64 tile data"));
+        assert!(decode_error("AQAAAA==AgAAAA==", "base64", "").contains("invalid base64"));
+        assert!(!decode_error("aGVsbG8=", "base64", "zlib").is_empty());
+        assert!(!decode_error("aGVsbG8=", "base64", "gzip").is_empty());
+        assert!(decode_error("AQAAAA==", "base64", "zstd").contains("unsupported tile layer compression"));
+        assert!(decode_error("1", "xml", "").contains("unsupported tile layer encoding"));
+    }
+
+    #[test]
+    fn new_checks_the_tile_count() {
+        let info = LayerInfo {
+            id: 1,
+            name: String::from("ground"),
+            class: String::new(),
+            visible: true,
+            opacity: 1.0,
+            offset: Vector2::ZERO,
+            parallax: Vector2::new(1.0, 1.0),
+            properties: Properties::new(),
+        };
+        assert!(TileLayer::new(info.clone(), 2, 2, vec![None; 4]).is_ok());
+        let error = TileLayer::new(info, 2, 2, vec![None; 3]).err().unwrap().to_string();
+        assert_eq!(error, "tile layer 'ground' has 3 tiles but is 2x2");
+    }
+}
@@ -0,0 +1,367 @@
+use crate::graphics::color::Color;
+use crate::tiled::layer::{Layer, ObjectLayer, TileLayer};
+use crate::tiled::properties::Properties;
+use crate::tiled::tileset::{extension, Tileset};
+use crate::tiled::{tmj, tmx};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Orientation {
+    Orthogonal,
+    Isometric,
+    Staggered,
+    Hexagonal,
+}
+
+impl Orientation {
+    pub(crate) fn parse(value: &str) -> Result<Orientation, Box<dyn std::error::Error>> {
+        Ok(match value {
+            "orthogonal" => Orientation::Orthogonal,
+            "isometric" => Orientation::Isometric,
+            "staggered" => Orientation::Staggered,
+            "hexagonal" => Orientation::Hexagonal,
+            other => return Err(format!("unknown map orientation '{}'", other).into()),
+        })
+    }
+}
+
+/// A map created with the Tiled editor.
+#[derive(Clone)]
+pub struct TiledMap {
+    pub orientation: Orientation,
+    /// Width of the map in tiles.
+    pub width: u32,
+    /// Height of the map in tiles.
+    pub height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub background_color: Option<Color>,
+    /// Tilesets sorted by ascending `first_gid`.
+    pub tilesets: Vec<Tileset>,
+    /// Layers in drawing order, bottom first.
+    pub layers: Vec<Layer>,
+    pub properties: Properties,
+}
+
+impl TiledMap {
+    /// Loads a map from a `.tmx` (XML) or `.tmj`/`.json` file. External tilesets
+    /// and image paths are resolved relative to the map file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use barn::tiled::map::TiledMap;
+    /// let map = TiledMap::from_file("examples/resources/maps/snow_level.tmx").unwrap();
+    /// let ground = map.tile_layers().next().unwrap();
+    /// assert!(ground.tile_at(0, map.height - 1).is_some());
+    /// ```
+    pub fn from_file(path: &str) -> Result<TiledMap, Box<dyn std::error::Error>> {
+        let mut map = match extension(path).as_str() {
+            "tmx" | "xml" => tmx::load_map(path)?,
+            "tmj" | "json" => tmj::load_map(path)?,
+            other => return Err(format!("unsupported map format '{}': {}", other, path).into()),
+        };
+        map.tilesets.sort_by_key(|t| t.first_gid);
+        Ok(map)
+    }
+
+    /// Finds the tileset that owns a global tile id, along with the tile's local id.
+    pub fn tileset_for_gid(&self, gid: u32) -> Option<(&Tileset, u32)> {
+        self.tilesets.iter()
+            .rev()
+            .find(|t| t.first_gid <= gid)
+            .map(|t| (t, gid - t.first_gid))
+    }
+
+    /// Looks up a top-level layer by name.
+    pub fn layer(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|l| l.name() == name)
+    }
+
+    /// Iterates over all tile layers, including those nested in groups.
+    pub fn tile_layers(&self) -> impl Iterator<Item = &TileLayer> {
+        flatten(&self.layers).into_iter().filter_map(|l| match l {
+            Layer::Tiles(layer) => Some(layer),
+            _ => None,
+        })
+    }
+
+    /// Iterates over all object layers, including those nested in groups.
+    pub fn object_layers(&self) -> impl Iterator<Item = &ObjectLayer> {
+        flatten(&self.layers).into_iter().filter_map(|l| match l {
+            Layer::Objects(layer) => Some(layer),
+            _ => None,
+        })
+    }
+}
+
+fn flatten(layers: &[Layer]) -> Vec<&Layer> {
+    let mut out = Vec::new();
+    for layer in layers {
+        match layer {
+            Layer::Group(group) => out.extend(flatten(&group.layers)),
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::vector2::Vector2;
+    use crate::tiled::layer::LayerTile;
+    use crate::tiled::object::ObjectShape;
+    use crate::tiled::properties::PropertyValue;
+
+    const FIXTURES: &str = "tests/resources/tiled";
+
+    fn load(name: &str) -> TiledMap {
+        TiledMap::from_file(&format!("{}/{}", FIXTURES, name)).unwrap()
+    }
+
+    fn load_error(name: &str) -> String {
+        match TiledMap::from_file(&format!("{}/{}", FIXTURES, name)) {
+            Ok(_) => panic!("{} should not load", name),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    fn tile(gid: u32, flip_horizontal: bool, flip_vertical: bool, flip_diagonal: bool) -> Option<LayerTile> {
+        Some(LayerTile { gid, flip_horizontal, flip_vertical, flip_diagonal })
+    }
+
+    /// The tiles every top-level tile layer in the fixtures stores, each in a different encoding.
+    fn expected_tiles() -> Vec<Option<LayerTile>> {
+        vec![
+            tile(1, false, false, false),
+            tile(2, true, false, false),
+            None,
+            tile(3, false, true, true),
+            tile(1, false, false, false),
+            tile(2, false, false, false),
+        ]
+    }
+
+    fn tile_layer<'a>(map: &'a TiledMap, name: &str) -> &'a TileLayer {
+        map.tile_layers().find(|layer| layer.info.name == name).unwrap()
+    }
+
+    fn object_layer(map: &TiledMap) -> &ObjectLayer {
+        map.object_layers().next().unwrap()
+    }
+
+    fn property<'a>(properties: &'a Properties, name: &str) -> &'a PropertyValue {
+        properties.get(name).unwrap_or_else(|| panic!("missing property {}", name))
+    }
+
+    fn close(a: Vector2, b: Vector2) -> bool {
+        (a.x - b.x).abs() < 1e-4 && (a.y - b.y).abs() < 1e-4
+    }
+
+    #[test]
+    fn tmx_decodes_every_tile_encoding() {
+        let map = load("map.tmx");
+        for name in ["csv", "base64", "zlib", "gzip", "xml"] {
+            let layer = tile_layer(&map, name);
+            assert_eq!((layer.width, layer.height), (3, 2));
+            assert!(layer.tiles == expected_tiles(), "layer {} decoded wrong tiles", name);
+        }
+    }
+
+    #[test]
+    fn tmj_decodes_every_tile_encoding() {
+        let map = load("map.tmj");
+        for name in ["array", "base64", "zlib", "gzip"] {
+            assert!(tile_layer(&map, name).tiles == expected_tiles(), "layer {} decoded wrong tiles", name);
+        }
+    }
+
+    #[test]
+    fn tile_at_reads_flip_flags_and_bounds() {
+        let map = load("map.tmx");
+        let layer = tile_layer(&map, "csv");
+        let flipped = layer.tile_at(1, 0).unwrap();
+        assert_eq!(flipped.gid, 2);
+        assert!(flipped.flip_horizontal && !flipped.flip_vertical && !flipped.flip_diagonal);
+        let rotated = layer.tile_at(0, 1).unwrap();
+        assert!(!rotated.flip_horizontal && rotated.flip_vertical && rotated.flip_diagonal);
+        assert!(layer.tile_at(2, 0).is_none());
+        assert!(layer.tile_at(3, 0).is_none());
+        assert!(layer.tile_at(0, 2).is_none());
+    }
+
+    #[test]
+    fn tmx_loads_external_tileset() {
+        let map = load("map.tmx");
+        assert_eq!(map.tilesets.len(), 1);
+        let tileset = &map.tilesets[0];
+        assert_eq!(tileset.first_gid, 1);
+        assert_eq!(tileset.name, "test_tiles");
+        assert_eq!(tileset.source.as_deref(), Some("tests/resources/tiled/tiles.tsx"));
+        assert_eq!(tileset.image.as_ref().unwrap().source, "tests/resources/tiled/tiles.png");
+        assert_eq!(tileset.properties.get("theme").and_then(|p| p.as_str()), Some("test"));
+
+        let (owner, local) = map.tileset_for_gid(4).unwrap();
+        assert_eq!((owner.name.as_str(), local), ("test_tiles", 3));
+        assert!(map.tileset_for_gid(0).is_none());
+
+        let coin = tileset.tile(1).unwrap();
+        assert_eq!(coin.tile_type, "coin");
+        assert_eq!(coin.collision.len(), 1);
+        assert_eq!(coin.animation.iter().map(|f| (f.tile_id, f.duration)).collect::<Vec<_>>(), vec![(1, 0.1), (2, 0.15)]);
+        assert!(*property(&coin.properties, "value") == PropertyValue::Int(10));
+        assert!(*property(&coin.properties, "spin") == PropertyValue::Float(1.5));
+        assert!(*property(&coin.properties, "collectable") == PropertyValue::Bool(true));
+        assert!(*property(&coin.properties, "tint") == PropertyValue::Color(Color::from_hex(0x112233ff)));
+        assert!(*property(&coin.properties, "sound") == PropertyValue::File("tests/resources/tiled/sounds/coin.wav".to_string()));
+        assert!(*property(&coin.properties, "target") == PropertyValue::Object(3));
+    }
+
+    #[test]
+    fn tmj_mixes_embedded_and_external_tilesets() {
+        let map = load("map.tmj");
+        assert_eq!(map.tilesets.iter().map(|t| t.first_gid).collect::<Vec<_>>(), vec![1, 5]);
+        let (embedded, local) = map.tileset_for_gid(3).unwrap();
+        assert_eq!((embedded.name.as_str(), local), ("embedded", 2));
+        assert!(embedded.source.is_none());
+        let spike = embedded.tile(2).unwrap();
+        assert_eq!(spike.tile_type, "spike");
+        assert!(*property(&spike.properties, "damage") == PropertyValue::Int(5));
+        assert_eq!(spike.animation.len(), 2);
+        let (external, local) = map.tileset_for_gid(6).unwrap();
+        assert_eq!((external.name.as_str(), local), ("test_tiles", 1));
+    }
+
+    #[test]
+    fn tmx_reads_typed_map_properties() {
+        let map = load("map.tmx");
+        assert!(map.orientation == Orientation::Orthogonal);
+        // #AARRGGBB
+        assert!(map.background_color == Some(Color::from_hex(0x40201080)));
+        let properties = &map.properties;
+        assert!(*property(properties, "title") == PropertyValue::String("Test & map".to_string()));
+        assert!(*property(properties, "lives") == PropertyValue::Int(3));
+        assert!(*property(properties, "gravity") == PropertyValue::Float(9.5));
+        assert!(*property(properties, "dark") == PropertyValue::Bool(false));
+        assert!(*property(properties, "fog") == PropertyValue::Color(Color::from_hex(0x336699ff)));
+        assert!(*property(properties, "music") == PropertyValue::File("tests/resources/tiled/../audio/theme.ogg".to_string()));
+        assert_eq!(property(properties, "intro").as_str(), Some("First line\nSecond line"));
+        match property(properties, "spawn") {
+            PropertyValue::Class(members) => assert!(*property(members, "count") == PropertyValue::Int(2)),
+            _ => panic!("spawn should be a class property"),
+        }
+    }
+
+    #[test]
+    fn tmj_reads_typed_map_properties() {
+        let map = load("map.tmj");
+        assert!(map.orientation == Orientation::Isometric);
+        assert!(map.background_color == Some(Color::from_hex(0x102030ff)));
+        let properties = &map.properties;
+        assert!(*property(properties, "title") == PropertyValue::String("Test \"map\"".to_string()));
+        assert!(*property(properties, "dark") == PropertyValue::Bool(true));
+        assert!(*property(properties, "fog") == PropertyValue::Color(Color::from_hex(0x336699ff)));
+        assert!(*property(properties, "target") == PropertyValue::Object(3));
+        let members = match property(properties, "spawn") {
+            PropertyValue::Class(members) => members,
+            _ => panic!("spawn should be a class property"),
+        };
+        assert!(*property(members, "count") == PropertyValue::Int(2));
+        assert!(*property(members, "speed") == PropertyValue::Float(1.5));
+        assert!(*property(members, "active") == PropertyValue::Bool(true));
+        assert!(*property(members, "label") == PropertyValue::String("start".to_string()));
+        match property(members, "inner") {
+            PropertyValue::Class(inner) => assert!(*property(inner, "depth") == PropertyValue::Int(1)),
+            _ => panic!("inner should be a class property"),
+        }
+    }
+
+    /// Checks the object layer both fixtures share.
+    fn check_objects(map: &TiledMap) {
+        let layer = object_layer(map);
+        assert!(layer.info.offset == Vector2::new(4.0, -2.0));
+        assert_eq!(layer.info.opacity, 0.5);
+        assert!(!layer.info.visible);
+        assert_eq!(layer.objects.len(), 7);
+
+        let rect = layer.object("box").unwrap();
+        assert_eq!((rect.object_type.as_str(), rect.rotation), ("trigger", 45.0));
+        assert!(matches!(rect.shape, ObjectShape::Rectangle));
+        let bounds = rect.bounding_box();
+        assert!(bounds.origin == Vector2::new(8.0, 16.0));
+        assert_eq!((bounds.width, bounds.height), (16, 8));
+
+        assert!(matches!(layer.object("ellipse").unwrap().shape, ObjectShape::Ellipse));
+        let spawn = layer.object("spawn").unwrap();
+        assert!(matches!(spawn.shape, ObjectShape::Point));
+        assert_eq!(spawn.properties.get("facing").and_then(|p| p.as_str()), Some("left"));
+
+        let slope = layer.object("slope").unwrap();
+        let bounds = slope.bounding_box();
+        assert!(bounds.origin == Vector2::new(10.0, 10.0));
+        assert_eq!((bounds.width, bounds.height), (20, 10));
+
+        let path = layer.object("path").unwrap();
+        assert!(matches!(path.shape, ObjectShape::Polyline(_)));
+        let points = path.polygon().unwrap();
+        assert!(close(points[1], Vector2::new(0.0, 10.0)), "polyline points are rotated");
+
+        // Tile objects strip the flip flags and are anchored at the bottom left.
+        let coin = layer.object("coin").unwrap();
+        assert_eq!(coin.gid, Some(2));
+        assert!(coin.bounding_box().origin == Vector2::new(32.0, 16.0));
+
+        match &layer.object("sign").unwrap().shape {
+            ObjectShape::Text(text) => assert_eq!(text, "Hello"),
+            _ => panic!("sign should be a text object"),
+        }
+        assert_eq!(layer.bounding_boxes().len(), 7);
+    }
+
+    #[test]
+    fn tmx_reads_object_layers() {
+        check_objects(&load("map.tmx"));
+    }
+
+    #[test]
+    fn tmj_reads_object_layers() {
+        let map = load("map.tmj");
+        check_objects(&map);
+        assert_eq!(object_layer(&map).object("coin").unwrap().object_type, "pickup");
+    }
+
+    #[test]
+    fn group_layers_are_searched() {
+        for name in ["map.tmx", "map.tmj"] {
+            let map = load(name);
+            let group = match map.layer("decoration") {
+                Some(Layer::Group(group)) => group,
+                _ => panic!("{} should have a decoration group", name),
+            };
+            assert_eq!(group.info.parallax.x, 0.5);
+            assert_eq!(tile_layer(&map, "nested").tile_at(2, 1).map(|t| t.gid), Some(4));
+            match &group.layers[1] {
+                Layer::Image(sky) => {
+                    assert!(sky.repeat_x && !sky.repeat_y);
+                    assert_eq!(sky.image.as_ref().unwrap().source, "tests/resources/tiled/sky.png");
+                },
+                _ => panic!("{} should have an image layer in the group", name),
+            }
+            assert!(map.layer("nested").is_none(), "layer() only searches the top level");
+        }
+    }
+
+    #[test]
+    fn rejects_tile_layers_with_missing_tiles() {
+        assert!(load_error("truncated.tmx").contains("has 5 tiles but is 3x2"));
+        assert!(load_error("short_csv.tmx").contains("has 4 tiles but is 3x2"));
+        assert!(load_error("truncated.tmj").contains("has 5 tiles but is 3x2"));
+    }
+
+    #[test]
+    fn rejects_infinite_maps_and_unknown_files() {
+        assert!(load_error("infinite.tmx").contains("infinite maps are not supported"));
+        assert!(load_error("map.txt").contains("unsupported map format"));
+        assert!(TiledMap::from_file("tests/resources/tiled/missing.tmx").is_err());
+    }
+}
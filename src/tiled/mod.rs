@@ -0,0 +1,10 @@
+pub mod layer;
+pub mod map;
+pub mod object;
+pub mod properties;
+pub mod tileset;
+mod tmj;
+mod tmx;
+
+pub use map::TiledMap;
+pub use tileset::Tileset;
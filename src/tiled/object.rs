@@ -0,0 +1,137 @@
+use crate::math::bounding_box_2d::BoundingBox2D;
+use crate::math::vector2::Vector2;
+use crate::tiled::properties::Properties;
+
+/// The geometry of an object placed on an object layer.
+#[derive(Clone)]
+pub enum ObjectShape {
+    Rectangle,
+    Ellipse,
+    Point,
+    /// Closed polygon, with points relative to the object's position.
+    Polygon(Vec<Vector2>),
+    /// Open polyline, with points relative to the object's position.
+    Polyline(Vec<Vector2>),
+    Text(String),
+}
+
+/// An object from an object layer, or a collision shape attached to a tile.
+#[derive(Clone)]
+pub struct MapObject {
+    pub id: u32,
+    pub name: String,
+    /// The object's class (called `type` before Tiled 1.9).
+    pub object_type: String,
+    /// Position in pixels. For tile objects this is the bottom-left corner.
+    pub position: Vector2,
+    pub width: f32,
+    pub height: f32,
+    /// Clockwise rotation in degrees around `position`.
+    pub rotation: f32,
+    /// Global tile id for tile objects.
+    pub gid: Option<u32>,
+    pub visible: bool,
+    pub shape: ObjectShape,
+    pub properties: Properties,
+}
+
+impl MapObject {
+    /// Returns the axis-aligned bounds of the object in map pixels. Rotation is
+    /// only taken into account for polygons and polylines.
+    pub fn bounding_box(&self) -> BoundingBox2D {
+        if let Some(points) = self.polygon() {
+            let mut min = Vector2::new(f32::MAX, f32::MAX);
+            let mut max = Vector2::new(f32::MIN, f32::MIN);
+            for point in points.iter() {
+                min.x = min.x.min(point.x);
+                min.y = min.y.min(point.y);
+                max.x = max.x.max(point.x);
+                max.y = max.y.max(point.y);
+            }
+            if points.is_empty() {
+                min = self.position;
+                max = self.position;
+            }
+            return BoundingBox2D::new(min, (max.x - min.x) as u32, (max.y - min.y) as u32);
+        }
+        let mut origin = self.position;
+        if self.gid.is_some() {
+            // Tile objects are anchored at their bottom-left corner.
+            origin.y -= self.height;
+        }
+        BoundingBox2D::new(origin, self.width as u32, self.height as u32)
+    }
+
+    /// Returns the polygon or polyline points in map pixels, if the object has any.
+    pub fn polygon(&self) -> Option<Vec<Vector2>> {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        match &self.shape {
+            ObjectShape::Polygon(points) | ObjectShape::Polyline(points) => Some(
+                points.iter()
+                    .map(|p| Vector2::new(p.x * cos - p.y * sin, p.x * sin + p.y * cos) + self.position)
+                    .collect()
+            ),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(shape: ObjectShape) -> MapObject {
+        MapObject {
+            id: 1,
+            name: String::new(),
+            object_type: String::new(),
+            position: Vector2::new(10.0, 20.0),
+            width: 8.0,
+            height: 4.0,
+            rotation: 0.0,
+            gid: None,
+            visible: true,
+            shape,
+            properties: Properties::new(),
+        }
+    }
+
+    fn close(a: Vector2, b: Vector2) -> bool {
+        (a.x - b.x).abs() < 1e-4 && (a.y - b.y).abs() < 1e-4
+    }
+
+    #[test]
+    fn rectangles_are_bounded_from_their_top_left() {
+        let bounds = object(ObjectShape::Rectangle).bounding_box();
+        assert!(close(bounds.origin, Vector2::new(10.0, 20.0)));
+        assert_eq!((bounds.width, bounds.height), (8, 4));
+        assert!(object(ObjectShape::Ellipse).polygon().is_none());
+    }
+
+    #[test]
+    fn tile_objects_are_anchored_at_the_bottom_left() {
+        let mut tile = object(ObjectShape::Rectangle);
+        tile.gid = Some(3);
+        let bounds = tile.bounding_box();
+        assert!(close(bounds.origin, Vector2::new(10.0, 16.0)));
+    }
+
+    #[test]
+    fn polygons_are_rotated_around_the_position() {
+        let mut triangle = object(ObjectShape::Polygon(vec![Vector2::new(0.0, 0.0), Vector2::new(4.0, 0.0), Vector2::new(0.0, 2.0)]));
+        triangle.rotation = 90.0;
+        let points = triangle.polygon().unwrap();
+        assert!(close(points[1], Vector2::new(10.0, 24.0)));
+        assert!(close(points[2], Vector2::new(8.0, 20.0)));
+        let bounds = triangle.bounding_box();
+        assert!(close(bounds.origin, Vector2::new(8.0, 20.0)));
+        assert_eq!((bounds.width, bounds.height), (2, 4));
+    }
+
+    #[test]
+    fn empty_polylines_are_bounded_by_their_position() {
+        let bounds = object(ObjectShape::Polyline(Vec::new())).bounding_box();
+        assert!(close(bounds.origin, Vector2::new(10.0, 20.0)));
+        assert_eq!((bounds.width, bounds.height), (0, 0));
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use crate::graphics::color::Color;
+
+/// Custom properties attached to maps, layers, tilesets, tiles and objects.
+pub type Properties = HashMap<String, PropertyValue>;
+
+/// A typed custom property value as authored in Tiled.
+#[derive(Clone, PartialEq)]
+pub enum PropertyValue {
+    String(String),
+    Int(i64),
+    Float(f32),
+    Bool(bool),
+    Color(Color),
+    /// Path to a file, resolved relative to the file that declared it.
+    File(String),
+    /// Id of another object on the map.
+    Object(u32),
+    /// Nested properties of a custom class type.
+    Class(Properties),
+}
+
+impl PropertyValue {
+    /// Builds a property value from Tiled's `type` attribute and its textual value.
+    pub fn parse(property_type: &str, value: &str) -> Result<PropertyValue, Box<dyn std::error::Error>> {
+        Ok(match property_type {
+            "" | "string" => PropertyValue::String(value.to_string()),
+            "int" => PropertyValue::Int(value.parse::<f64>()? as i64),
+            "float" => PropertyValue::Float(value.parse()?),
+            "bool" => PropertyValue::Bool(value == "true"),
+            "color" => PropertyValue::Color(parse_color(value).unwrap_or(Color::CLEAR)),
+            "file" => PropertyValue::File(value.to_string()),
+            "object" => PropertyValue::Object(value.parse()?),
+            other => return Err(format!("unknown property type '{}'", other).into()),
+        })
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            PropertyValue::String(s) | PropertyValue::File(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            PropertyValue::Int(i) => Some(*i),
+            PropertyValue::Object(id) => Some(*id as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f32> {
+        match self {
+            PropertyValue::Float(f) => Some(*f),
+            PropertyValue::Int(i) => Some(*i as f32),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            PropertyValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_color(&self) -> Option<Color> {
+        match self {
+            PropertyValue::Color(c) => Some(*c),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a Tiled color string, either `#RRGGBB` or `#AARRGGBB`.
+pub fn parse_color(value: &str) -> Option<Color> {
+    let hex = value.trim_start_matches('#');
+    let raw = u32::from_str_radix(hex, 16).ok()?;
+    match hex.len() {
+        6 => Some(Color::from_hex((raw << 8) | 0xff)),
+        8 => Some(Color::from_hex(raw.rotate_left(8))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_both_color_formats() {
+        assert!(parse_color("#336699") == Some(Color::from_hex(0x336699ff)));
+        // Tiled puts alpha first
+        assert!(parse_color("#80336699") == Some(Color::from_hex(0x33669980)));
+        assert!(parse_color("336699") == Some(Color::from_hex(0x336699ff)));
+        assert!(parse_color("#3369").is_none());
+        assert!(parse_color("#zz6699").is_none());
+    }
+
+    #[test]
+    fn parses_typed_values() {
+        assert!(PropertyValue::parse("", "text").unwrap() == PropertyValue::String("text".to_string()));
+        // Tiled writes whole floats for ints in some versions
+        assert!(PropertyValue::parse("int", "3.0").unwrap() == PropertyValue::Int(3));
+        assert!(PropertyValue::parse("bool", "false").unwrap() == PropertyValue::Bool(false));
+        assert!(PropertyValue::parse("color", "").unwrap() == PropertyValue::Color(Color::CLEAR));
+        assert!(PropertyValue::parse("object", "12").unwrap() == PropertyValue::Object(12));
+    }
+
+    #[test]
+    fn rejects_bad_values_and_unknown_types() {
+        assert!(PropertyValue::parse("int", "three").is_err());
+        assert!(PropertyValue::parse("float", "").is_err());
+        assert!(PropertyValue::parse("object", "-1").is_err());
+        assert!(PropertyValue::parse("vector", "1,2").err().unwrap().to_string().contains("unknown property type"));
+    }
+
+    #[test]
+    fn accessors_convert_compatible_types() {
+        assert_eq!(PropertyValue::Int(2).as_float(), Some(2.0));
+        assert_eq!(PropertyValue::Object(7).as_int(), Some(7));
+        assert_eq!(PropertyValue::File("a.png".to_string()).as_str(), Some("a.png"));
+        assert_eq!(PropertyValue::Float(1.5).as_int(), None);
+        assert_eq!(PropertyValue::String("true".to_string()).as_bool(), None);
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::graphics::Rect;
+use crate::tiled::object::MapObject;
+use crate::tiled::properties::Properties;
+use crate::tiled::{tmj, tmx};
+
+/// An image referenced by a tileset or image layer.
+#[derive(Clone)]
+pub struct TiledImage {
+    /// Path to the image, resolved relative to the file that referenced it.
+    pub source: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// One frame of a tile animation.
+#[derive(Clone, Copy)]
+pub struct TileFrame {
+    pub tile_id: u32,
+    /// Frame duration in seconds.
+    pub duration: f32,
+}
+
+/// Per-tile data defined in a tileset.
+#[derive(Clone)]
+pub struct TileData {
+    pub id: u32,
+    pub tile_type: String,
+    pub properties: Properties,
+    /// Individual image for tiles in an image collection tileset.
+    pub image: Option<TiledImage>,
+    /// Collision shapes drawn in the tile collision editor.
+    pub collision: Vec<MapObject>,
+    pub animation: Vec<TileFrame>,
+}
+
+/// A collection of tiles, either embedded in a map or loaded from a `.tsx`/`.tsj` file.
+#[derive(Clone)]
+pub struct Tileset {
+    /// The global id of the first tile in this tileset. Zero for standalone tilesets.
+    pub first_gid: u32,
+    pub name: String,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub spacing: u32,
+    pub margin: u32,
+    pub tile_count: u32,
+    pub columns: u32,
+    pub image: Option<TiledImage>,
+    pub tiles: HashMap<u32, TileData>,
+    pub properties: Properties,
+    /// Path of the external tileset file this was loaded from, if any.
+    pub source: Option<String>,
+}
+
+impl Tileset {
+    /// Loads an external tileset from a `.tsx` (XML) or `.tsj`/`.json` file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use barn::tiled::tileset::Tileset;
+    /// let tileset = Tileset::from_file("examples/resources/maps/snow_tiles.tsx").unwrap();
+    /// assert_eq!(tileset.columns, 16);
+    /// ```
+    pub fn from_file(path: &str) -> Result<Tileset, Box<dyn std::error::Error>> {
+        let mut tileset = match extension(path).as_str() {
+            "tsx" | "xml" => tmx::load_tileset(path)?,
+            "tsj" | "json" => tmj::load_tileset(path)?,
+            other => return Err(format!("unsupported tileset format '{}': {}", other, path).into()),
+        };
+        tileset.source = Some(path.to_string());
+        Ok(tileset)
+    }
+
+    /// Returns the source rectangle of a tile within the tileset image.
+    pub fn tile_src_rect(&self, tile_id: u32) -> Option<Rect> {
+        if tile_id >= self.tile_count || self.columns == 0 {
+            return None;
+        }
+        let column = tile_id % self.columns;
+        let row = tile_id / self.columns;
+        Some((
+            (self.margin + column * (self.tile_width + self.spacing)) as f32,
+            (self.margin + row * (self.tile_height + self.spacing)) as f32,
+            self.tile_width as f32,
+            self.tile_height as f32,
+        ))
+    }
+
+    pub fn tile(&self, tile_id: u32) -> Option<&TileData> {
+        self.tiles.get(&tile_id)
+    }
+}
+
+pub(crate) fn extension(path: &str) -> String {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+/// Resolves a path found inside `file` so that it is relative to the working directory.
+pub(crate) fn resolve_path(file: &str, relative: &str) -> String {
+    match Path::new(file).parent() {
+        Some(dir) if Path::new(relative).is_relative() => dir.join(relative).to_string_lossy().into_owned(),
+        _ => relative.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_a_standalone_tileset() {
+        let tileset = Tileset::from_file("tests/resources/tiled/tiles.tsx").unwrap();
+        assert_eq!(tileset.first_gid, 0);
+        assert_eq!((tileset.tile_width, tileset.tile_height, tileset.spacing, tileset.margin), (16, 16, 2, 1));
+        assert!(tileset.tile(1).is_some());
+        assert!(tileset.tile(0).is_none());
+    }
+
+    #[test]
+    fn source_rects_account_for_margin_and_spacing() {
+        let tileset = Tileset::from_file("tests/resources/tiled/tiles.tsx").unwrap();
+        assert_eq!(tileset.tile_src_rect(0), Some((1.0, 1.0, 16.0, 16.0)));
+        assert_eq!(tileset.tile_src_rect(3), Some((19.0, 19.0, 16.0, 16.0)));
+        assert_eq!(tileset.tile_src_rect(4), None);
+    }
+
+    #[test]
+    fn rejects_unknown_formats() {
+        let error = Tileset::from_file("tests/resources/tiled/tiles.png").err().unwrap().to_string();
+        assert!(error.contains("unsupported tileset format"));
+    }
+
+    #[test]
+    fn resolves_paths_relative_to_the_referencing_file() {
+        assert_eq!(resolve_path("maps/level.tmx", "tiles.tsx"), "maps/tiles.tsx");
+        assert_eq!(resolve_path("maps/level.tmx", "/abs/tiles.tsx"), "/abs/tiles.tsx");
+        assert_eq!(resolve_path("level.tmx", "tiles.tsx"), "tiles.tsx");
+        assert_eq!(extension("Level.TMX"), "tmx");
+    }
+}
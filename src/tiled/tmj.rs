@@ -0,0 +1,546 @@
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::math::vector2::Vector2;
+use crate::tiled::layer::{decode_tile_data, GroupLayer, ImageLayer, Layer, LayerInfo, LayerTile, ObjectLayer, TileLayer};
+use crate::tiled::map::{Orientation, TiledMap};
+use crate::tiled::object::{MapObject, ObjectShape};
+use crate::tiled::properties::{parse_color, Properties, PropertyValue};
+use crate::tiled::tileset::{resolve_path, TileData, TileFrame, TiledImage, Tileset};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+fn one() -> f32 {
+    1.0
+}
+
+fn yes() -> bool {
+    true
+}
+
+fn read<T: DeserializeOwned>(path: &str) -> Result<T> {
+    let source = std::fs::read_to_string(path)?;
+    serde_json::from_str(&source).map_err(|e| format!("{}: {}", path, e).into())
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct MapFile {
+    #[serde(rename = "type")]
+    kind: String,
+    infinite: bool,
+    orientation: String,
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    backgroundcolor: Option<String>,
+    tilesets: Vec<TilesetEntry>,
+    layers: Vec<LayerFile>,
+    properties: Vec<PropertyFile>,
+}
+
+impl Default for MapFile {
+    fn default() -> MapFile {
+        MapFile {
+            kind: "map".to_string(),
+            infinite: false,
+            orientation: "orthogonal".to_string(),
+            width: 0,
+            height: 0,
+            tilewidth: 0,
+            tileheight: 0,
+            backgroundcolor: None,
+            tilesets: Vec::new(),
+            layers: Vec::new(),
+            properties: Vec::new(),
+        }
+    }
+}
+
+/// A map's tileset: either a reference to an external file or embedded.
+#[derive(Deserialize)]
+struct TilesetEntry {
+    #[serde(default = "first_gid")]
+    firstgid: u32,
+    source: Option<String>,
+    #[serde(flatten)]
+    tileset: TilesetFile,
+}
+
+fn first_gid() -> u32 {
+    1
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct TilesetFile {
+    name: String,
+    tilewidth: u32,
+    tileheight: u32,
+    spacing: u32,
+    margin: u32,
+    tilecount: u32,
+    columns: u32,
+    #[serde(flatten)]
+    image: ImageFile,
+    tiles: Vec<TileFile>,
+    properties: Vec<PropertyFile>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ImageFile {
+    image: Option<String>,
+    imagewidth: u32,
+    imageheight: u32,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct TileFile {
+    id: u32,
+    class: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    properties: Vec<PropertyFile>,
+    #[serde(flatten)]
+    image: ImageFile,
+    objectgroup: Option<ObjectGroupFile>,
+    animation: Vec<FrameFile>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ObjectGroupFile {
+    objects: Vec<ObjectFile>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct FrameFile {
+    tileid: u32,
+    /// In milliseconds.
+    duration: f32,
+}
+
+#[derive(Deserialize)]
+struct PropertyFile {
+    name: Option<String>,
+    #[serde(rename = "type", default)]
+    kind: Option<String>,
+    #[serde(default)]
+    value: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum LayerFile {
+    #[serde(rename = "tilelayer")]
+    Tiles {
+        #[serde(flatten)]
+        info: LayerInfoFile,
+        #[serde(default)]
+        width: u32,
+        #[serde(default)]
+        height: u32,
+        data: Option<TileDataFile>,
+        #[serde(default)]
+        compression: String,
+    },
+    #[serde(rename = "objectgroup")]
+    Objects {
+        #[serde(flatten)]
+        info: LayerInfoFile,
+        #[serde(default)]
+        objects: Vec<ObjectFile>,
+    },
+    #[serde(rename = "imagelayer")]
+    Image {
+        #[serde(flatten)]
+        info: LayerInfoFile,
+        #[serde(flatten)]
+        image: ImageFile,
+        #[serde(default)]
+        repeatx: bool,
+        #[serde(default)]
+        repeaty: bool,
+    },
+    #[serde(rename = "group")]
+    Group {
+        #[serde(flatten)]
+        info: LayerInfoFile,
+        #[serde(default)]
+        layers: Vec<LayerFile>,
+    },
+    /// Layer types added by newer Tiled versions are skipped.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TileDataFile {
+    /// Base64, optionally compressed.
+    Encoded(String),
+    Gids(Vec<u32>),
+}
+
+#[derive(Deserialize)]
+struct LayerInfoFile {
+    #[serde(default)]
+    id: u32,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    class: String,
+    #[serde(default = "yes")]
+    visible: bool,
+    #[serde(default = "one")]
+    opacity: f32,
+    #[serde(default)]
+    offsetx: f32,
+    #[serde(default)]
+    offsety: f32,
+    #[serde(default = "one")]
+    parallaxx: f32,
+    #[serde(default = "one")]
+    parallaxy: f32,
+    #[serde(default)]
+    properties: Vec<PropertyFile>,
+}
+
+#[derive(Deserialize)]
+struct ObjectFile {
+    #[serde(default)]
+    id: u32,
+    #[serde(default)]
+    name: String,
+    class: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    x: f32,
+    #[serde(default)]
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    rotation: f32,
+    gid: Option<u32>,
+    #[serde(default = "yes")]
+    visible: bool,
+    #[serde(default)]
+    ellipse: bool,
+    #[serde(default)]
+    point: bool,
+    polygon: Option<Vec<PointFile>>,
+    polyline: Option<Vec<PointFile>>,
+    text: Option<TextFile>,
+    #[serde(default)]
+    properties: Vec<PropertyFile>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct PointFile {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct TextFile {
+    text: String,
+}
+
+pub(crate) fn load_map(path: &str) -> Result<TiledMap> {
+    let map: MapFile = read(path)?;
+    if map.kind != "map" {
+        return Err(format!("{}: not a Tiled map", path).into());
+    }
+    if map.infinite {
+        return Err(format!("{}: infinite maps are not supported", path).into());
+    }
+    let mut tilesets = Vec::new();
+    for entry in map.tilesets {
+        let mut tileset = match &entry.source {
+            Some(source) => Tileset::from_file(&resolve_path(path, source))?,
+            None => convert_tileset(entry.tileset, path)?,
+        };
+        tileset.first_gid = entry.firstgid;
+        tilesets.push(tileset);
+    }
+    Ok(TiledMap {
+        orientation: Orientation::parse(&map.orientation)?,
+        width: map.width,
+        height: map.height,
+        tile_width: map.tilewidth,
+        tile_height: map.tileheight,
+        background_color: map.backgroundcolor.as_deref().and_then(parse_color),
+        tilesets,
+        layers: convert_layers(map.layers, path)?,
+        properties: convert_properties(map.properties, path)?,
+    })
+}
+
+pub(crate) fn load_tileset(path: &str) -> Result<Tileset> {
+    convert_tileset(read(path)?, path)
+}
+
+fn convert_image(image: ImageFile, path: &str) -> Option<TiledImage> {
+    Some(TiledImage {
+        source: resolve_path(path, &image.image?),
+        width: image.imagewidth,
+        height: image.imageheight,
+    })
+}
+
+fn convert_tileset(tileset: TilesetFile, path: &str) -> Result<Tileset> {
+    let mut tiles = HashMap::new();
+    for tile in tileset.tiles {
+        let collision = match tile.objectgroup {
+            Some(group) => group.objects.into_iter().map(|o| convert_object(o, path)).collect::<Result<_>>()?,
+            None => Vec::new(),
+        };
+        let animation = tile.animation.iter()
+            .map(|f| TileFrame {
+                tile_id: f.tileid,
+                duration: f.duration / 1000.0,
+            })
+            .collect();
+        tiles.insert(tile.id, TileData {
+            id: tile.id,
+            tile_type: tile.class.or(tile.kind).unwrap_or_default(),
+            properties: convert_properties(tile.properties, path)?,
+            image: convert_image(tile.image, path),
+            collision,
+            animation,
+        });
+    }
+    Ok(Tileset {
+        first_gid: 0,
+        name: tileset.name,
+        tile_width: tileset.tilewidth,
+        tile_height: tileset.tileheight,
+        spacing: tileset.spacing,
+        margin: tileset.margin,
+        tile_count: tileset.tilecount,
+        columns: tileset.columns,
+        image: convert_image(tileset.image, path),
+        tiles,
+        properties: convert_properties(tileset.properties, path)?,
+        source: None,
+    })
+}
+
+fn parse_property_value(property_type: &str, value: &Value, path: &str) -> Result<PropertyValue> {
+    Ok(match (property_type, value) {
+        ("class", Value::Object(members)) => {
+            let mut properties = Properties::new();
+            for (name, member) in members.iter() {
+                let member_type = match member {
+                    Value::Bool(_) => "bool",
+                    Value::Number(n) if n.is_f64() => "float",
+                    Value::Number(_) => "int",
+                    Value::Object(_) => "class",
+                    _ => "string",
+                };
+                properties.insert(name.clone(), parse_property_value(member_type, member, path)?);
+            }
+            PropertyValue::Class(properties)
+        }
+        ("int", value) => PropertyValue::Int(value.as_i64().unwrap_or(0)),
+        ("float", value) => PropertyValue::Float(value.as_f64().unwrap_or(0.0) as f32),
+        ("bool", value) => PropertyValue::Bool(value.as_bool().unwrap_or(false)),
+        ("object", value) => PropertyValue::Object(value.as_u64().unwrap_or(0) as u32),
+        ("file", Value::String(file)) if !file.is_empty() => PropertyValue::File(resolve_path(path, file)),
+        (property_type, Value::String(text)) => PropertyValue::parse(property_type, text)?,
+        (property_type, _) => return Err(format!("invalid value for property type '{}'", property_type).into()),
+    })
+}
+
+fn convert_properties(values: Vec<PropertyFile>, path: &str) -> Result<Properties> {
+    let mut properties = Properties::new();
+    for property in values {
+        let name = property.name.ok_or("property is missing a name")?;
+        let property_type = property.kind.as_deref().unwrap_or("string");
+        properties.insert(name, parse_property_value(property_type, &property.value, path)?);
+    }
+    Ok(properties)
+}
+
+fn convert_layer_info(info: LayerInfoFile, path: &str) -> Result<LayerInfo> {
+    Ok(LayerInfo {
+        id: info.id,
+        name: info.name,
+        class: info.class,
+        visible: info.visible,
+        opacity: info.opacity,
+        offset: Vector2::new(info.offsetx, info.offsety),
+        parallax: Vector2::new(info.parallaxx, info.parallaxy),
+        properties: convert_properties(info.properties, path)?,
+    })
+}
+
+fn convert_layers(values: Vec<LayerFile>, path: &str) -> Result<Vec<Layer>> {
+    let mut layers = Vec::new();
+    for value in values {
+        let layer = match value {
+            LayerFile::Tiles { info, width, height, data, compression } => {
+                let tiles = match data {
+                    Some(TileDataFile::Encoded(data)) => decode_tile_data(&data, "base64", &compression)?,
+                    Some(TileDataFile::Gids(gids)) => gids.into_iter().map(LayerTile::from_raw).collect(),
+                    None => return Err("tile layer is missing data".into()),
+                };
+                Layer::Tiles(TileLayer::new(convert_layer_info(info, path)?, width, height, tiles)?)
+            },
+            LayerFile::Objects { info, objects } => Layer::Objects(ObjectLayer {
+                info: convert_layer_info(info, path)?,
+                objects: objects.into_iter().map(|o| convert_object(o, path)).collect::<Result<_>>()?,
+            }),
+            LayerFile::Image { info, image, repeatx, repeaty } => Layer::Image(ImageLayer {
+                info: convert_layer_info(info, path)?,
+                image: convert_image(image, path),
+                repeat_x: repeatx,
+                repeat_y: repeaty,
+            }),
+            LayerFile::Group { info, layers } => Layer::Group(GroupLayer {
+                info: convert_layer_info(info, path)?,
+                layers: convert_layers(layers, path)?,
+            }),
+            LayerFile::Unknown => continue,
+        };
+        layers.push(layer);
+    }
+    Ok(layers)
+}
+
+fn convert_points(points: Vec<PointFile>) -> Vec<Vector2> {
+    points.iter().map(|p| Vector2::new(p.x, p.y)).collect()
+}
+
+fn convert_object(object: ObjectFile, path: &str) -> Result<MapObject> {
+    let shape = if let Some(points) = object.polygon {
+        ObjectShape::Polygon(convert_points(points))
+    } else if let Some(points) = object.polyline {
+        ObjectShape::Polyline(convert_points(points))
+    } else if object.ellipse {
+        ObjectShape::Ellipse
+    } else if object.point {
+        ObjectShape::Point
+    } else if let Some(text) = object.text {
+        ObjectShape::Text(text.text)
+    } else {
+        ObjectShape::Rectangle
+    };
+    Ok(MapObject {
+        id: object.id,
+        name: object.name,
+        object_type: object.class.or(object.kind).unwrap_or_default(),
+        position: Vector2::new(object.x, object.y),
+        width: object.width,
+        height: object.height,
+        rotation: object.rotation,
+        gid: object.gid.and_then(LayerTile::from_raw).map(|t| t.gid),
+        visible: object.visible,
+        shape,
+        properties: convert_properties(object.properties, path)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json<T: DeserializeOwned>(source: &str) -> T {
+        serde_json::from_str(source).unwrap()
+    }
+
+    fn layers(source: &str) -> Result<Vec<Layer>> {
+        convert_layers(serde_json::from_str(source)?, "")
+    }
+
+    #[test]
+    fn class_properties_infer_member_types() {
+        let value: Value = json(r#"{"hp": 3, "speed": 1.5, "boss": true, "name": "Orc", "loot": {"gold": 10}}"#);
+        let members = match parse_property_value("class", &value, "maps/level.tmj").unwrap() {
+            PropertyValue::Class(members) => members,
+            _ => panic!("expected a class"),
+        };
+        assert!(matches!(members["hp"], PropertyValue::Int(3)));
+        assert!(matches!(members["speed"], PropertyValue::Float(speed) if speed == 1.5));
+        assert!(matches!(members["boss"], PropertyValue::Bool(true)));
+        assert!(matches!(&members["name"], PropertyValue::String(name) if name == "Orc"));
+        assert!(matches!(&members["loot"], PropertyValue::Class(loot) if matches!(loot["gold"], PropertyValue::Int(10))));
+    }
+
+    #[test]
+    fn property_values_must_match_their_type() {
+        assert!(parse_property_value("color", &json("12"), "").is_err());
+        assert!(parse_property_value("string", &Value::Null, "").is_err());
+        assert!(parse_property_value("vector", &json(r#""1,2""#), "").err().unwrap().to_string().contains("unknown property type 'vector'"));
+        match parse_property_value("file", &json(r#""sfx/hit.wav""#), "maps/level.tmj").unwrap() {
+            PropertyValue::File(file) => assert!(file.ends_with("hit.wav") && file.starts_with("maps")),
+            _ => panic!("expected a file"),
+        }
+    }
+
+    #[test]
+    fn properties_need_names() {
+        let properties = json(r#"[{"type": "int", "value": 1}]"#);
+        assert!(convert_properties(properties, "").err().unwrap().to_string().contains("property is missing a name"));
+        let properties = convert_properties(json(r#"[{"name": "title", "value": "Cave"}]"#), "").unwrap();
+        assert!(matches!(&properties["title"], PropertyValue::String(title) if title == "Cave"));
+    }
+
+    #[test]
+    fn tile_layers_need_numeric_data() {
+        let tiles = layers(r#"[{"type": "tilelayer", "width": 2, "height": 1, "data": [1, 0]}]"#).unwrap();
+        assert!(matches!(&tiles[0], Layer::Tiles(layer) if layer.tiles.len() == 2));
+        assert!(layers(r#"[{"type": "tilelayer", "width": 2, "height": 1, "data": [1, "2"]}]"#).is_err());
+        let error = layers(r#"[{"type": "tilelayer", "width": 2, "height": 1}]"#).err().unwrap().to_string();
+        assert!(error.contains("tile layer is missing data"));
+    }
+
+    #[test]
+    fn unknown_layer_types_are_skipped() {
+        let layers = layers(r#"[{"type": "future"}, {"type": "group", "name": "g", "layers": [{"type": "objectgroup", "objects": []}]}]"#).unwrap();
+        assert_eq!(layers.len(), 1);
+        assert!(matches!(&layers[0], Layer::Group(group) if group.info.name == "g" && group.layers.len() == 1));
+    }
+
+    #[test]
+    fn layer_info_defaults_match_tiled() {
+        let layers = layers(r#"[{"type": "objectgroup"}, {"type": "objectgroup", "visible": false, "opacity": 0.5, "parallaxx": 0.25}]"#).unwrap();
+        let info = |layer: &Layer| match layer {
+            Layer::Objects(objects) => (objects.info.visible, objects.info.opacity, objects.info.parallax.x, objects.info.parallax.y),
+            _ => panic!("expected an object layer"),
+        };
+        assert_eq!(info(&layers[0]), (true, 1.0, 1.0, 1.0));
+        assert_eq!(info(&layers[1]), (false, 0.5, 0.25, 1.0));
+    }
+
+    #[test]
+    fn objects_read_shape_flags() {
+        let shape = |source: &str| convert_object(json(source), "").unwrap().shape;
+        assert!(matches!(shape(r#"{"id": 1, "ellipse": true}"#), ObjectShape::Ellipse));
+        assert!(matches!(shape(r#"{"id": 1, "point": true}"#), ObjectShape::Point));
+        assert!(matches!(shape(r#"{"id": 1, "ellipse": false}"#), ObjectShape::Rectangle));
+        assert!(matches!(shape(r#"{"id": 1, "polygon": [{"x": 0, "y": 0}, {"x": 2}]}"#), ObjectShape::Polygon(points) if points.len() == 2));
+        assert!(matches!(shape(r#"{"id": 1, "text": {"text": "Hi"}}"#), ObjectShape::Text(text) if text == "Hi"));
+    }
+
+    #[test]
+    fn objects_strip_flip_flags_from_gids() {
+        let object = convert_object(json(r#"{"id": 1, "gid": 2147483653, "type": "coin"}"#), "").unwrap();
+        assert_eq!(object.gid, Some(5));
+        assert_eq!(object.object_type, "coin");
+        assert!(object.visible);
+    }
+}
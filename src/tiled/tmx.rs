@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+
+use quick_xml::events::Event;
+use quick_xml::{Reader, XmlVersion};
+
+use crate::math::vector2::Vector2;
+use crate::tiled::layer::{decode_tile_data, GroupLayer, ImageLayer, Layer, LayerInfo, LayerTile, ObjectLayer, TileLayer};
+use crate::tiled::map::{Orientation, TiledMap};
+use crate::tiled::object::{MapObject, ObjectShape};
+use crate::tiled::properties::{parse_color, Properties, PropertyValue};
+use crate::tiled::tileset::{resolve_path, TileData, TileFrame, TiledImage, Tileset};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// A minimal element tree built from the XML event stream.
+struct Element {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(|s| s.as_str())
+    }
+
+    fn attr_or<'a>(&'a self, name: &str, default: &'a str) -> &'a str {
+        self.attr(name).unwrap_or(default)
+    }
+
+    fn parse_attr<T: std::str::FromStr>(&self, name: &str, default: T) -> Result<T>
+    where T::Err: std::error::Error + 'static {
+        match self.attr(name) {
+            Some(value) => Ok(value.parse::<T>()?),
+            None => Ok(default),
+        }
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+}
+
+fn parse_document(source: &str) -> Result<Element> {
+    // Text is trimmed per element once it ends, so spaces around entity references survive
+    let mut reader = Reader::from_str(source);
+    let mut stack: Vec<Element> = Vec::new();
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) => stack.push(element_from(&start)?),
+            Event::Empty(start) => {
+                let element = element_from(&start)?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            Event::End(_) => {
+                let mut element = stack.pop().ok_or("unbalanced XML end tag")?;
+                element.text = element.text.trim().to_string();
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            Event::Text(text) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&text.decode()?);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&data.decode()?);
+                }
+            }
+            Event::GeneralRef(reference) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(match reference.decode()?.as_ref() {
+                        "lt" => "<",
+                        "gt" => ">",
+                        "amp" => "&",
+                        "apos" => "'",
+                        "quot" => "\"",
+                        _ => "",
+                    });
+                }
+            }
+            Event::Eof => return Err("unexpected end of XML document".into()),
+            _ => {}
+        }
+    }
+}
+
+fn element_from(start: &quick_xml::events::BytesStart) -> Result<Element> {
+    let mut attributes = HashMap::new();
+    for attribute in start.attributes() {
+        let attribute = attribute?;
+        let key = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+        let value = attribute.normalized_value(XmlVersion::Implicit1_0)?.into_owned();
+        attributes.insert(key, value);
+    }
+    Ok(Element {
+        name: String::from_utf8_lossy(start.name().as_ref()).into_owned(),
+        attributes,
+        children: Vec::new(),
+        text: String::new(),
+    })
+}
+
+fn load_document(path: &str) -> Result<Element> {
+    let source = std::fs::read_to_string(path)?;
+    parse_document(&source).map_err(|e| format!("{}: {}", path, e).into())
+}
+
+pub(crate) fn load_map(path: &str) -> Result<TiledMap> {
+    let root = load_document(path)?;
+    if root.name != "map" {
+        return Err(format!("{}: expected <map> root element", path).into());
+    }
+    if root.attr("infinite") == Some("1") {
+        return Err(format!("{}: infinite maps are not supported", path).into());
+    }
+    let mut tilesets = Vec::new();
+    for element in root.children_named("tileset") {
+        let first_gid = element.parse_attr("firstgid", 1)?;
+        let mut tileset = match element.attr("source") {
+            Some(source) => Tileset::from_file(&resolve_path(path, source))?,
+            None => parse_tileset(element, path)?,
+        };
+        tileset.first_gid = first_gid;
+        tilesets.push(tileset);
+    }
+    Ok(TiledMap {
+        orientation: Orientation::parse(root.attr_or("orientation", "orthogonal"))?,
+        width: root.parse_attr("width", 0)?,
+        height: root.parse_attr("height", 0)?,
+        tile_width: root.parse_attr("tilewidth", 0)?,
+        tile_height: root.parse_attr("tileheight", 0)?,
+        background_color: root.attr("backgroundcolor").and_then(parse_color),
+        tilesets,
+        layers: parse_layers(&root, path)?,
+        properties: parse_properties(&root, path)?,
+    })
+}
+
+pub(crate) fn load_tileset(path: &str) -> Result<Tileset> {
+    let root = load_document(path)?;
+    if root.name != "tileset" {
+        return Err(format!("{}: expected <tileset> root element", path).into());
+    }
+    parse_tileset(&root, path)
+}
+
+fn parse_tileset(element: &Element, path: &str) -> Result<Tileset> {
+    let mut tiles = HashMap::new();
+    for tile in element.children_named("tile") {
+        let id = tile.parse_attr("id", 0)?;
+        let collision = match tile.child("objectgroup") {
+            Some(group) => group.children_named("object").map(|o| parse_object(o, path)).collect::<Result<_>>()?,
+            None => Vec::new(),
+        };
+        let animation = match tile.child("animation") {
+            Some(animation) => animation.children_named("frame")
+                .map(|f| Ok(TileFrame {
+                    tile_id: f.parse_attr("tileid", 0)?,
+                    duration: f.parse_attr::<f32>("duration", 0.0)? / 1000.0,
+                }))
+                .collect::<Result<_>>()?,
+            None => Vec::new(),
+        };
+        tiles.insert(id, TileData {
+            id,
+            tile_type: tile.attr("class").or(tile.attr("type")).unwrap_or("").to_string(),
+            properties: parse_properties(tile, path)?,
+            image: tile.child("image").map(|i| parse_image(i, path)).transpose()?,
+            collision,
+            animation,
+        });
+    }
+    Ok(Tileset {
+        first_gid: 0,
+        name: element.attr_or("name", "").to_string(),
+        tile_width: element.parse_attr("tilewidth", 0)?,
+        tile_height: element.parse_attr("tileheight", 0)?,
+        spacing: element.parse_attr("spacing", 0)?,
+        margin: element.parse_attr("margin", 0)?,
+        tile_count: element.parse_attr("tilecount", 0)?,
+        columns: element.parse_attr("columns", 0)?,
+        image: element.child("image").map(|i| parse_image(i, path)).transpose()?,
+        tiles,
+        properties: parse_properties(element, path)?,
+        source: None,
+    })
+}
+
+fn parse_image(element: &Element, path: &str) -> Result<TiledImage> {
+    Ok(TiledImage {
+        source: resolve_path(path, element.attr("source").ok_or("image is missing a source")?),
+        width: element.parse_attr("width", 0)?,
+        height: element.parse_attr("height", 0)?,
+    })
+}
+
+fn parse_properties(element: &Element, path: &str) -> Result<Properties> {
+    let mut properties = Properties::new();
+    if let Some(list) = element.child("properties") {
+        for property in list.children_named("property") {
+            let name = property.attr("name").ok_or("property is missing a name")?;
+            let property_type = property.attr_or("type", "string");
+            let value = match property_type {
+                "class" => PropertyValue::Class(parse_properties(property, path)?),
+                _ => {
+                    // Multi-line strings are stored as element text rather than an attribute.
+                    let raw = property.attr("value").unwrap_or(&property.text);
+                    match PropertyValue::parse(property_type, raw)? {
+                        PropertyValue::File(file) if !file.is_empty() => PropertyValue::File(resolve_path(path, &file)),
+                        value => value,
+                    }
+                }
+            };
+            properties.insert(name.to_string(), value);
+        }
+    }
+    Ok(properties)
+}
+
+fn parse_layer_info(element: &Element, path: &str) -> Result<LayerInfo> {
+    Ok(LayerInfo {
+        id: element.parse_attr("id", 0)?,
+        name: element.attr_or("name", "").to_string(),
+        class: element.attr_or("class", "").to_string(),
+        visible: element.attr("visible") != Some("0"),
+        opacity: element.parse_attr("opacity", 1.0)?,
+        offset: Vector2::new(element.parse_attr("offsetx", 0.0)?, element.parse_attr("offsety", 0.0)?),
+        parallax: Vector2::new(element.parse_attr("parallaxx", 1.0)?, element.parse_attr("parallaxy", 1.0)?),
+        properties: parse_properties(element, path)?,
+    })
+}
+
+fn parse_layers(parent: &Element, path: &str) -> Result<Vec<Layer>> {
+    let mut layers = Vec::new();
+    for element in parent.children.iter() {
+        let layer = match element.name.as_str() {
+            "layer" => Layer::Tiles(parse_tile_layer(element, path)?),
+            "objectgroup" => Layer::Objects(ObjectLayer {
+                info: parse_layer_info(element, path)?,
+                objects: element.children_named("object").map(|o| parse_object(o, path)).collect::<Result<_>>()?,
+            }),
+            "imagelayer" => Layer::Image(ImageLayer {
+                info: parse_layer_info(element, path)?,
+                image: element.child("image").map(|i| parse_image(i, path)).transpose()?,
+                repeat_x: element.attr("repeatx") == Some("1"),
+                repeat_y: element.attr("repeaty") == Some("1"),
+            }),
+            "group" => Layer::Group(GroupLayer {
+                info: parse_layer_info(element, path)?,
+                layers: parse_layers(element, path)?,
+            }),
+            _ => continue,
+        };
+        layers.push(layer);
+    }
+    Ok(layers)
+}
+
+fn parse_tile_layer(element: &Element, path: &str) -> Result<TileLayer> {
+    let data = element.child("data").ok_or("tile layer is missing <data>")?;
+    let tiles = match data.attr("encoding") {
+        Some(encoding) => decode_tile_data(&data.text, encoding, data.attr_or("compression", ""))?,
+        None => data.children_named("tile")
+            .map(|t| Ok(LayerTile::from_raw(t.parse_attr("gid", 0)?)))
+            .collect::<Result<_>>()?,
+    };
+    TileLayer::new(
+        parse_layer_info(element, path)?,
+        element.parse_attr("width", 0)?,
+        element.parse_attr("height", 0)?,
+        tiles,
+    )
+}
+
+fn parse_points(points: &str) -> Result<Vec<Vector2>> {
+    points.split_whitespace()
+        .map(|pair| {
+            let (x, y) = pair.split_once(',').ok_or("malformed point list")?;
+            Ok(Vector2::new(x.parse()?, y.parse()?))
+        })
+        .collect()
+}
+
+fn parse_object(element: &Element, path: &str) -> Result<MapObject> {
+    let shape = if let Some(polygon) = element.child("polygon") {
+        ObjectShape::Polygon(parse_points(polygon.attr_or("points", ""))?)
+    } else if let Some(polyline) = element.child("polyline") {
+        ObjectShape::Polyline(parse_points(polyline.attr_or("points", ""))?)
+    } else if element.child("ellipse").is_some() {
+        ObjectShape::Ellipse
+    } else if element.child("point").is_some() {
+        ObjectShape::Point
+    } else if let Some(text) = element.child("text") {
+        ObjectShape::Text(text.text.clone())
+    } else {
+        ObjectShape::Rectangle
+    };
+    Ok(MapObject {
+        id: element.parse_attr("id", 0)?,
+        name: element.attr_or("name", "").to_string(),
+        object_type: element.attr("class").or(element.attr("type")).unwrap_or("").to_string(),
+        position: Vector2::new(element.parse_attr("x", 0.0)?, element.parse_attr("y", 0.0)?),
+        width: element.parse_attr("width", 0.0)?,
+        height: element.parse_attr("height", 0.0)?,
+        rotation: element.parse_attr("rotation", 0.0)?,
+        gid: element.attr("gid").map(|g| g.parse::<u32>()).transpose()?.and_then(LayerTile::from_raw).map(|t| t.gid),
+        visible: element.attr("visible") != Some("0"),
+        shape,
+        properties: parse_properties(element, path)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(xml: &str) -> MapObject {
+        parse_object(&parse_document(xml).unwrap(), "maps/level.tmx").unwrap()
+    }
+
+    #[test]
+    fn documents_keep_text_references_and_cdata() {
+        let root = parse_document("<map a=\"x &amp; y\"><text>fish &amp; chips</text><data><![CDATA[1,2]]></data><empty/></map>").unwrap();
+        assert_eq!(root.attr("a"), Some("x & y"));
+        assert_eq!(root.child("text").unwrap().text, "fish & chips");
+        assert_eq!(root.child("data").unwrap().text, "1,2");
+        assert!(root.child("empty").is_some());
+    }
+
+    #[test]
+    fn malformed_documents_are_rejected() {
+        assert!(parse_document("<map><layer></map>").is_err());
+        assert!(parse_document("<map><layer>").err().unwrap().to_string().contains("unexpected end of XML document"));
+        assert!(parse_document("").is_err());
+    }
+
+    #[test]
+    fn attributes_must_parse() {
+        let root = parse_document("<map width=\"ten\"/>").unwrap();
+        assert!(root.parse_attr::<u32>("width", 0).is_err());
+        assert_eq!(root.parse_attr::<u32>("height", 7).unwrap(), 7);
+    }
+
+    #[test]
+    fn point_lists_need_pairs() {
+        let points = parse_points("0,0 4.5,-2").unwrap();
+        assert_eq!(points.len(), 2);
+        assert!(points[1].x == 4.5 && points[1].y == -2.0);
+        assert!(parse_points("").unwrap().is_empty());
+        assert!(parse_points("1,2 3").err().unwrap().to_string().contains("malformed point list"));
+        assert!(parse_points("1,x").is_err());
+    }
+
+    #[test]
+    fn objects_read_their_shape() {
+        assert!(matches!(object("<object id=\"1\"><ellipse/></object>").shape, ObjectShape::Ellipse));
+        assert!(matches!(object("<object id=\"1\"><point/></object>").shape, ObjectShape::Point));
+        assert!(matches!(object("<object id=\"1\"/>").shape, ObjectShape::Rectangle));
+        match object("<object id=\"1\"><text wrap=\"1\">Hello</text></object>").shape {
+            ObjectShape::Text(text) => assert_eq!(text, "Hello"),
+            _ => panic!("expected a text object"),
+        }
+        match object("<object id=\"1\"><polyline points=\"0,0 1,1 2,0\"/></object>").shape {
+            ObjectShape::Polyline(points) => assert_eq!(points.len(), 3),
+            _ => panic!("expected a polyline"),
+        }
+    }
+
+    #[test]
+    fn objects_strip_flip_flags_from_gids() {
+        let tile = object("<object id=\"2\" type=\"coin\" gid=\"2147483653\" visible=\"0\" x=\"1\" y=\"2\"/>");
+        assert_eq!(tile.gid, Some(5));
+        assert_eq!(tile.object_type, "coin");
+        assert!(!tile.visible);
+        let classed = object("<object id=\"3\" class=\"door\" type=\"old\"/>");
+        assert_eq!(classed.object_type, "door");
+        assert!(parse_object(&parse_document("<object id=\"-1\"/>").unwrap(), "").is_err());
+    }
+}
@@ -0,0 +1,24 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" tiledversion="1.10.2" name="test_tiles" tilewidth="16" tileheight="16" spacing="2" margin="1" tilecount="4" columns="2">
+ <properties>
+  <property name="theme" value="test"/>
+ </properties>
+ <image source="tiles.png" width="36" height="36"/>
+ <tile id="1" type="coin">
+  <properties>
+   <property name="value" type="int" value="10"/>
+   <property name="spin" type="float" value="1.5"/>
+   <property name="collectable" type="bool" value="true"/>
+   <property name="tint" type="color" value="#ff112233"/>
+   <property name="sound" type="file" value="sounds/coin.wav"/>
+   <property name="target" type="object" value="3"/>
+  </properties>
+  <objectgroup draworder="index">
+   <object id="1" x="2" y="2" width="12" height="12"/>
+  </objectgroup>
+  <animation>
+   <frame tileid="1" duration="100"/>
+   <frame tileid="2" duration="150"/>
+  </animation>
+ </tile>
+</tileset>